@@ -0,0 +1,184 @@
+use std::fmt;
+use std::str::FromStr;
+
+use piston_window::types::Color;
+use serde::{Deserialize, Serialize};
+
+use crate::blockcolor::BlockColor;
+
+/// Palettes that keep the block colors apart for players who are color blind
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum ColorVision {
+    /// the colors of the theme
+    #[default]
+    Normal,
+    /// red and green look alike, the most common kind
+    Deuteranopia,
+    /// red and green look alike and red looks dark
+    Protanopia,
+    /// blue and green look alike, as do yellow and pink
+    Tritanopia,
+}
+
+impl FromStr for ColorVision {
+    type Err = String;
+
+    /// Reads a kind of vision written as normal, deuteranopia, protanopia or tritanopia
+    fn from_str(text: &str) -> Result<ColorVision, String> {
+        match text {
+            "normal" => Ok(ColorVision::Normal),
+            "deuteranopia" => Ok(ColorVision::Deuteranopia),
+            "protanopia" => Ok(ColorVision::Protanopia),
+            "tritanopia" => Ok(ColorVision::Tritanopia),
+            _ => Err(format!("{} is not a palette, use normal, deuteranopia, protanopia or tritanopia", text)),
+        }
+    }
+}
+
+impl fmt::Display for ColorVision {
+    /// Writes the kind of vision the way it is read
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ColorVision::Normal => write!(f, "normal"),
+            ColorVision::Deuteranopia => write!(f, "deuteranopia"),
+            ColorVision::Protanopia => write!(f, "protanopia"),
+            ColorVision::Tritanopia => write!(f, "tritanopia"),
+        }
+    }
+}
+
+impl ColorVision {
+    /// Returns the color a block color is drawn in, None keeps the color of the theme
+    /// The palettes for red and green are based on the Okabe-Ito colors, the one for blue and yellow sets reds against teals and differs in how light the colors are
+    pub fn color(self, block_color: BlockColor) -> Option<Color> {
+        let color = match (self, block_color) {
+            (ColorVision::Normal, _) => return None,
+            (ColorVision::Deuteranopia, BlockColor::Red) => [0.84, 0.37, 0.00, 1.0],
+            (ColorVision::Deuteranopia, BlockColor::Purple) => [0.80, 0.47, 0.65, 1.0],
+            (ColorVision::Protanopia, BlockColor::Red) => [0.95, 0.50, 0.20, 1.0],
+            (ColorVision::Protanopia, BlockColor::Purple) => [1.00, 0.40, 0.95, 1.0],
+            (ColorVision::Deuteranopia | ColorVision::Protanopia, BlockColor::Blue) => [0.00, 0.45, 0.70, 1.0],
+            (ColorVision::Deuteranopia | ColorVision::Protanopia, BlockColor::Green) => [0.00, 0.62, 0.45, 1.0],
+            (ColorVision::Deuteranopia | ColorVision::Protanopia, BlockColor::Yellow) => [0.94, 0.89, 0.26, 1.0],
+            (ColorVision::Deuteranopia | ColorVision::Protanopia, BlockColor::Orange) => [0.90, 0.62, 0.00, 1.0],
+            (ColorVision::Deuteranopia | ColorVision::Protanopia, BlockColor::Cyan) => [0.34, 0.71, 0.91, 1.0],
+            (ColorVision::Deuteranopia | ColorVision::Protanopia, BlockColor::Pink) => [0.95, 0.95, 0.95, 1.0],
+            (ColorVision::Tritanopia, BlockColor::Red) => [0.85, 0.10, 0.10, 1.0],
+            (ColorVision::Tritanopia, BlockColor::Blue) => [0.00, 0.25, 0.45, 1.0],
+            (ColorVision::Tritanopia, BlockColor::Green) => [0.00, 0.55, 0.50, 1.0],
+            (ColorVision::Tritanopia, BlockColor::Yellow) => [1.00, 0.78, 0.78, 1.0],
+            (ColorVision::Tritanopia, BlockColor::Purple) => [0.45, 0.00, 0.20, 1.0],
+            (ColorVision::Tritanopia, BlockColor::Orange) => [1.00, 0.55, 0.50, 1.0],
+            (ColorVision::Tritanopia, BlockColor::Cyan) => [0.40, 0.85, 0.95, 1.0],
+            (ColorVision::Tritanopia, BlockColor::Pink) => [0.95, 0.95, 0.95, 1.0],
+        };
+        Some(color)
+    }
+}
+
+/// A mark drawn inside a block so its color can be told apart without seeing it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Pattern {
+    Dot,
+    HorizontalBar,
+    VerticalBar,
+    Plus,
+    Cross,
+    Frame,
+    Corners,
+    Triangle,
+}
+
+impl Pattern {
+    /// Returns the pattern of a block color, with guideline colors every piece type has its own pattern
+    pub fn of(block_color: BlockColor) -> Pattern {
+        match block_color {
+            BlockColor::Red => Pattern::Dot,
+            BlockColor::Blue => Pattern::HorizontalBar,
+            BlockColor::Green => Pattern::VerticalBar,
+            BlockColor::Yellow => Pattern::Plus,
+            BlockColor::Purple => Pattern::Cross,
+            BlockColor::Orange => Pattern::Frame,
+            BlockColor::Cyan => Pattern::Corners,
+            BlockColor::Pink => Pattern::Triangle,
+        }
+    }
+}
+
+/// Settings for players who can't tell every block color apart
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub struct Accessibility {
+    pub vision: ColorVision,
+    /// draws the pattern of its color inside every block
+    pub patterns: bool,
+}
+
+#[cfg(test)]
+mod accessibility_tests {
+    use super::*;
+    use crate::blockcolor::COLORS;
+
+    /// how far apart two block colors have to stay in CIELAB once the vision is simulated
+    const MIN_DISTANCE: f32 = 15.0;
+
+    /// the matrices of Machado, Oliveira and Fernandes (2009) at full severity, applied to linear RGB
+    fn simulation(vision: ColorVision) -> [[f32; 3]; 3] {
+        match vision {
+            ColorVision::Normal => [[1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]],
+            ColorVision::Deuteranopia => [
+                [0.367322, 0.860646, -0.227968],
+                [0.280085, 0.672501, 0.047413],
+                [-0.011820, 0.042940, 0.968881],
+            ],
+            ColorVision::Protanopia => [
+                [0.152286, 1.052583, -0.204868],
+                [0.114503, 0.786281, 0.099216],
+                [-0.003882, -0.048116, 1.051998],
+            ],
+            ColorVision::Tritanopia => [
+                [1.255528, -0.076749, -0.178779],
+                [-0.078411, 0.930809, 0.147602],
+                [0.004733, 0.691367, 0.303900],
+            ],
+        }
+    }
+
+    /// Returns the CIELAB of a color the way someone with the vision sees it
+    fn seen(vision: ColorVision, color: Color) -> [f32; 3] {
+        let linear = |c: f32| if c <= 0.04045 { c / 12.92 } else { ((c + 0.055) / 1.055).powf(2.4) };
+        let rgb = [linear(color[0]), linear(color[1]), linear(color[2])];
+        let matrix = simulation(vision);
+        let [r, g, b] = matrix.map(|row| (row[0] * rgb[0] + row[1] * rgb[1] + row[2] * rgb[2]).clamp(0.0, 1.0));
+        let x = (0.4124 * r + 0.3576 * g + 0.1805 * b) / 0.95047;
+        let y = 0.2126 * r + 0.7152 * g + 0.0722 * b;
+        let z = (0.0193 * r + 0.1192 * g + 0.9505 * b) / 1.08883;
+        let f = |t: f32| if t > 0.008856 { t.cbrt() } else { 7.787 * t + 16.0 / 116.0 };
+        [116.0 * f(y) - 16.0, 500.0 * (f(x) - f(y)), 200.0 * (f(y) - f(z))]
+    }
+
+    #[test]
+    fn test_palettes() {
+        for vision in [ColorVision::Deuteranopia, ColorVision::Protanopia, ColorVision::Tritanopia] {
+            let colors: Vec<[f32; 3]> = COLORS.iter().map(|color| seen(vision, vision.color(*color).unwrap())).collect();
+            // no two block colors look alike to the players the palette is for
+            for (index, color) in colors.iter().enumerate() {
+                for other in &colors[index + 1..] {
+                    let distance = color.iter().zip(other).map(|(a, b)| (a - b).powi(2)).sum::<f32>().sqrt();
+                    assert!(distance >= MIN_DISTANCE, "{} colors are only {} apart", vision, distance);
+                }
+            }
+        }
+        assert_eq!(ColorVision::Normal.color(BlockColor::Red), None);
+        assert_eq!("tritanopia".parse(), Ok(ColorVision::Tritanopia));
+        assert_eq!(ColorVision::Tritanopia.to_string(), "tritanopia");
+        assert!("sepia".parse::<ColorVision>().is_err());
+    }
+
+    #[test]
+    fn test_patterns() {
+        let patterns: Vec<Pattern> = COLORS.iter().map(|color| Pattern::of(*color)).collect();
+        for (index, pattern) in patterns.iter().enumerate() {
+            assert!(!patterns[index + 1..].contains(pattern));
+        }
+    }
+}
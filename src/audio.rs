@@ -4,6 +4,7 @@ use std::collections::HashMap;
 
 use rodio::source::{Buffered, SamplesConverter};
 use rodio::{Decoder, OutputStream, OutputStreamHandle, Source, Sink};
+use serde::{Deserialize, Serialize};
 
 /// Keeps all the possible sound effects as an enum
 #[derive(Eq, Hash, PartialEq)]
@@ -30,22 +31,43 @@ impl SoundEffect {
     }
 }
 
+/// How loud the sounds are played, from 0 for silent to 1
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Volumes {
+    /// the sounds of the game itself
+    pub effects: f32,
+    /// the sound of moving through the menu
+    pub menu: f32,
+}
+
+impl Default for Volumes {
+    fn default() -> Self {
+        Volumes { effects: 0.8, menu: 0.8 }
+    }
+}
+
 /// audio system that saves and loads sounds
 pub struct Audio {
-    stream_handle: OutputStreamHandle,
-    _stream: OutputStream,
+    /// None when there is no device to play on, then the game is silent
+    output: Option<(OutputStream, OutputStreamHandle)>,
     audio_cache: HashMap<SoundEffect, Buffered<Decoder<BufReader<File>>>>,
+    volumes: Volumes,
+}
+
+impl Default for Audio {
+    fn default() -> Self {
+        Audio::new()
+    }
 }
 
 impl Audio {
     /// creating a new audio device
     pub fn new() -> Audio {
-        let (_stream, stream_handle) = rodio::OutputStream::try_default().unwrap();
-
         let mut audio = Audio {
-            stream_handle,
-            _stream,
+            output: rodio::OutputStream::try_default().ok(),
             audio_cache: HashMap::new(),
+            volumes: Volumes::default(),
         };
 
         audio.load_file(SoundEffect::Lose);
@@ -66,11 +88,29 @@ impl Audio {
         self.audio_cache.insert(sound_effect, value);
     }
 
+    /// returns how loud the sounds are played
+    pub fn volumes(&self) -> Volumes {
+        self.volumes
+    }
+
+    /// changes how loud the sounds are played, anything outside of 0 to 1 is clamped
+    pub fn set_volumes(&mut self, volumes: Volumes) {
+        self.volumes = Volumes { effects: volumes.effects.clamp(0.0, 1.0), menu: volumes.menu.clamp(0.0, 1.0) };
+    }
+
     /// plays the sound associated with the enum
     pub fn play_audio(&mut self, sound_effect: SoundEffect) {
+        let Some((_, stream_handle)) = &self.output else {
+            return;
+        };
         let buffer: SamplesConverter<Buffered<Decoder<BufReader<File>>>, i16> = self.audio_cache.get(&sound_effect).unwrap().clone().convert_samples();
-        let sink = Sink::try_new(&self.stream_handle).unwrap();
-        sink.set_volume(0.8);
+        let Ok(sink) = Sink::try_new(stream_handle) else {
+            return;
+        };
+        sink.set_volume(match sound_effect {
+            SoundEffect::Menu => self.volumes.menu,
+            _ => self.volumes.effects,
+        });
         sink.append(buffer);
         sink.play();
         sink.detach()
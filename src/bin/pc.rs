@@ -0,0 +1,78 @@
+use std::process;
+use std::str::FromStr;
+
+use rust_tetris::blockshape::{BlockShape, SHAPES};
+use rust_tetris::fumen;
+use rust_tetris::solver;
+
+/// how the program is used, shown when it can't go on
+const USAGE: &str = "Usage: tetris-pc <fumen> [--queue TILJSZO] [--hold T] [--lines n] [--all]";
+
+/// Finds the perfect clears for the first board of a fumen code and prints the best one as a fumen code
+/// The pieces come from a "#Q=[hold](current)next" comment like the ones fumen writes, or from the pieces on the pages
+/// Usage: tetris-pc <fumen> [--queue TILJSZO] [--hold T] [--lines n] [--all]
+fn main() {
+    let args: Vec<String> = std::env::args().collect();
+    let Some(code) = args.get(1).filter(|code| !code.starts_with("--")) else {
+        eprintln!("{}", USAGE);
+        process::exit(1);
+    };
+
+    let pages = fumen::decode(code).unwrap_or_else(|error| {
+        eprintln!("Could not read the fumen code: {}", error);
+        process::exit(1);
+    });
+    let Some(first) = pages.first() else {
+        eprintln!("The fumen code has no pages\n{}", USAGE);
+        process::exit(1);
+    };
+
+    let (mut hold, mut queue) = first.comment.as_deref()
+        .and_then(queue_comment)
+        .unwrap_or_else(|| (None, pages.iter().filter_map(|page| page.piece).map(|piece| piece.shape).collect()));
+    if let Some(pieces) = args.iter().position(|arg| arg == "--queue").and_then(|index| args.get(index + 1)) {
+        queue = shapes(pieces);
+    }
+    if let Some(piece) = args.iter().position(|arg| arg == "--hold").and_then(|index| args.get(index + 1)) {
+        hold = shapes(piece).first().copied();
+    }
+    let lines = flag(&args, "--lines", 4);
+
+    let solutions = solver::solve(&first.board, hold, &queue, lines);
+    println!("{} solutions", solutions.len());
+
+    let shown = if args.iter().any(|arg| arg == "--all") { solutions.len() } else { 1 };
+    for solution in solutions.iter().take(shown) {
+        println!("{}", solution.fumen(&first.board));
+    }
+}
+
+/// Reads the hold and queue from a comment like "#Q=[T](I)LJSZ", the hold can be left empty as "[]"
+fn queue_comment(comment: &str) -> Option<(Option<BlockShape>, Vec<BlockShape>)> {
+    let rest = comment.trim().strip_prefix("#Q=")?;
+    let (hold, rest) = rest.strip_prefix('[')?.split_once(']')?;
+    let (current, next) = rest.strip_prefix('(')?.split_once(')')?;
+
+    let mut queue = shapes(current);
+    queue.extend(shapes(next));
+    Some((shapes(hold).first().copied(), queue))
+}
+
+/// Turns letters like "TIO" into shapes, anything that is not the letter of a shape is skipped
+fn shapes(letters: &str) -> Vec<BlockShape> {
+    letters.chars()
+        .filter_map(|letter| SHAPES.iter().find(|shape| format!("{:?}", shape) == letter.to_ascii_uppercase().to_string()))
+        .copied()
+        .collect()
+}
+
+/// Returns the value after the flag or the default when it is not given, a value that can't be read stops the program
+fn flag<T: FromStr>(args: &[String], name: &str, default: T) -> T {
+    let Some(value) = args.iter().position(|arg| arg == name).and_then(|index| args.get(index + 1)) else {
+        return default;
+    };
+    value.parse().unwrap_or_else(|_| {
+        eprintln!("{} is not a valid value for {}\n{}", value, name, USAGE);
+        process::exit(1);
+    })
+}
@@ -0,0 +1,123 @@
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::TcpStream;
+use std::process;
+use std::time::{Duration, Instant};
+
+use serde_json::Value;
+
+/// the address the game streams to when started with --spectate and no address
+const DEFAULT_ADDRESS: &str = "127.0.0.1:7878";
+/// the terminal is redrawn at most this often, the game sends a lot more frames than that
+const REDRAW_PERIOD: Duration = Duration::from_millis(50);
+/// how many of the latest events are shown under the boards
+const EVENT_LINES: usize = 5;
+/// the space between two boards
+const BOARD_GAP: &str = "   ";
+
+/// A minimal terminal client that watches a game started with --spectate
+/// Usage: tetris-spectate [address]
+fn main() {
+    let address = std::env::args().nth(1).unwrap_or_else(|| DEFAULT_ADDRESS.to_string());
+    let stream = TcpStream::connect(&address).unwrap_or_else(|error| {
+        eprintln!("Could not connect to {}: {}", address, error);
+        process::exit(1)
+    });
+
+    let mut events: Vec<String> = Vec::new();
+    let mut last_draw: Option<Instant> = None;
+
+    for line in BufReader::new(stream).lines() {
+        let Ok(line) = line else {
+            break;
+        };
+        let Ok(frame) = serde_json::from_str::<Value>(&line) else {
+            continue;
+        };
+
+        // events are only sent once so they are collected even when the frame is not drawn
+        for event in frame["events"].as_array().into_iter().flatten() {
+            events.push(describe_event(event));
+        }
+        if events.len() > EVENT_LINES {
+            events.drain(..events.len() - EVENT_LINES);
+        }
+
+        if last_draw.is_none_or(|time| time.elapsed() >= REDRAW_PERIOD) {
+            draw(&frame, &events);
+            last_draw = Some(Instant::now());
+        }
+    }
+
+    println!("The game stopped streaming");
+}
+
+/// Clears the terminal and draws all boards next to each other with the latest events below
+fn draw(frame: &Value, events: &[String]) {
+    let players: Vec<Vec<String>> = frame["players"].as_array()
+        .into_iter()
+        .flatten()
+        .enumerate()
+        .map(|(index, player)| player_lines(index, player))
+        .collect();
+
+    let mut screen = String::from("\x1b[H\x1b[2J");
+    screen += &format!("status: {}\r\n\r\n", frame["status"].as_str().unwrap_or("unknown"));
+
+    let rows = players.iter().map(Vec::len).max().unwrap_or(0);
+    for row in 0..rows {
+        let line: Vec<&str> = players.iter().map(|lines| lines.get(row).map_or("", String::as_str)).collect();
+        screen += &line.join(BOARD_GAP);
+        screen += "\r\n";
+    }
+
+    screen += "\r\n";
+    for event in events {
+        screen += event;
+        screen += "\r\n";
+    }
+
+    let mut stdout = io::stdout().lock();
+    let _ = stdout.write_all(screen.as_bytes());
+    let _ = stdout.flush();
+}
+
+/// Turns the board of one player into lines of text, every cell is two characters wide
+fn player_lines(index: usize, player: &Value) -> Vec<String> {
+    let width = player["width"].as_u64().unwrap_or(0) as usize;
+    let cells = player["cells"].as_array().cloned().unwrap_or_default();
+
+    let mut lines = vec![
+        format!("{:<1$}", format!("player {}  score {}", index + 1, player["score"]), width * 2),
+        format!("{:<1$}", format!("garbage {}  piece {}", player["garbage"], player["piece"]["shape"].as_str().unwrap_or("?")), width * 2),
+    ];
+
+    for row in cells.chunks(width.max(1)) {
+        let mut line = String::new();
+        for cell in row {
+            match cell.as_array() {
+                Some(color) => {
+                    let channel = |index: usize| (color.get(index).and_then(Value::as_f64).unwrap_or(0.0) * 255.0) as u8;
+                    line += &format!("\x1b[48;2;{};{};{}m  \x1b[0m", channel(0), channel(1), channel(2));
+                },
+                None => line += " .",
+            }
+        }
+        lines.push(line);
+    }
+
+    lines
+}
+
+/// Describes an event in a few words
+fn describe_event(event: &Value) -> String {
+    let player = event["player"].as_u64().unwrap_or(0) + 1;
+
+    match event["type"].as_str() {
+        Some("locked") => {
+            let fault = if event["fault"] == true { ", finesse fault" } else { "" };
+            format!("player {} cleared {} lines and sent {} garbage{}", player, event["lines"], event["sent"], fault)
+        },
+        Some("topped_out") => format!("player {} topped out", player),
+        _ => event.to_string(),
+    }
+}
@@ -0,0 +1,143 @@
+use std::process;
+
+use clap::Parser;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use rayon::prelude::*;
+use rust_tetris::bot::{Bot, HeadlessResult, Weights};
+
+/// how likely it is that a single weight of a child is changed a bit
+const MUTATION_CHANCE: f64 = 0.1;
+/// how much a mutation changes a weight at most
+const MUTATION_SIZE: f64 = 0.2;
+/// how much a cleared line is worth compared to a piece that was placed without topping out
+/// surviving counts as well, otherwise weights that never clear a line can't be told apart
+const LINE_FITNESS: usize = 10;
+
+/// Evolves weights for the built-in bot by letting every set of weights play the same seeded games without a window
+///
+/// The best half of every generation survives and the rest is replaced by mixes of them with small random changes
+#[derive(Parser)]
+#[command(name = "tetris-train", version)]
+struct Settings {
+    /// How many generations to train for
+    #[arg(long, default_value_t = 20, value_parser = clap::builder::RangedU64ValueParser::<usize>::new().range(1..))]
+    generations: usize,
+    /// How many sets of weights every generation has, at least 2
+    #[arg(long, default_value_t = 32, value_parser = clap::builder::RangedU64ValueParser::<usize>::new().range(2..))]
+    population: usize,
+    /// The amount of games every set of weights plays each generation
+    #[arg(long, default_value_t = 4, value_parser = clap::builder::RangedU64ValueParser::<usize>::new().range(1..))]
+    games: usize,
+    /// The amount of pieces after which a game is stopped, good weights would otherwise play forever
+    #[arg(long, default_value_t = 300, value_parser = clap::builder::RangedU64ValueParser::<usize>::new().range(1..))]
+    pieces: usize,
+    /// Where the random weights and games come from, the same seed trains the same way
+    #[arg(long, default_value_t = 0)]
+    seed: u64,
+    /// The file the best weights are written to
+    #[arg(long, value_name = "FILE", default_value = "weights.json")]
+    output: String,
+}
+
+/// Trains the weights with the flags, see tetris-train --help
+fn main() {
+    let settings = Settings::parse();
+
+    let mut rng = StdRng::seed_from_u64(settings.seed);
+    let mut population: Vec<Weights> = (0..settings.population)
+        .map(|_| from_genes(normalize((0..5).map(|_| rng.gen_range(-1.0..1.0)).collect())))
+        .collect();
+
+    let mut saved = false;
+    for generation in 1..=settings.generations {
+        // every set of weights plays the same games, the games change every generation so nothing is learned by heart
+        let seeds: Vec<u64> = (0..settings.games).map(|_| rng.gen()).collect();
+        let mut scored: Vec<(usize, Weights)> = population.par_iter()
+            .map(|weights| {
+                let bot = Bot::quick(*weights);
+                let fitness = seeds.iter().map(|seed| fitness(bot.play_headless(*seed, settings.pieces))).sum();
+                (fitness, *weights)
+            })
+            .collect();
+        scored.sort_by_key(|(fitness, _)| std::cmp::Reverse(*fitness));
+
+        let (best_fitness, best) = scored[0];
+        let average = scored.iter().map(|(fitness, _)| *fitness).sum::<usize>() as f64 / scored.len() as f64;
+        println!(
+            "generation {}: best fitness {:.1} per game, average {:.1}, {:?}",
+            generation,
+            best_fitness as f64 / settings.games as f64,
+            average / settings.games as f64,
+            best,
+        );
+
+        // saved every generation so stopping early does not lose anything
+        if let Err(error) = best.save(&settings.output) {
+            eprintln!("Could not write {}: {}", settings.output, error);
+            process::exit(1);
+        }
+        saved = true;
+
+        population = next_generation(&scored, settings.population, &mut rng);
+    }
+
+    if saved {
+        println!("The best weights were written to {}, play against them with tetris --weights {}", settings.output, settings.output);
+    }
+}
+
+/// Scores a game, every piece that was placed counts and every cleared line a lot more
+fn fitness(result: HeadlessResult) -> usize {
+    result.pieces + result.lines * LINE_FITNESS
+}
+
+/// Keeps the best half and fills the rest with children of two random survivors
+fn next_generation(scored: &[(usize, Weights)], size: usize, rng: &mut StdRng) -> Vec<Weights> {
+    let survivors = &scored[..scored.len().div_ceil(2)];
+    let mut population: Vec<Weights> = survivors.iter().map(|(_, weights)| *weights).collect();
+
+    while population.len() < size {
+        let (first_fitness, first) = survivors[rng.gen_range(0..survivors.len())];
+        let (second_fitness, second) = survivors[rng.gen_range(0..survivors.len())];
+
+        // the fitter parent passes on more of its weights
+        let share = (first_fitness as f64 + 1.0) / (first_fitness as f64 + second_fitness as f64 + 2.0);
+        let child = genes(&first).iter()
+            .zip(genes(&second))
+            .map(|(first, second)| {
+                let gene = first * share + second * (1.0 - share);
+                if rng.gen_bool(MUTATION_CHANCE) { gene + rng.gen_range(-MUTATION_SIZE..MUTATION_SIZE) } else { gene }
+            })
+            .collect();
+
+        population.push(from_genes(normalize(child)));
+    }
+
+    population
+}
+
+/// Returns the weights as a list of numbers so they can be mixed and changed
+fn genes(weights: &Weights) -> [f64; 5] {
+    [weights.height, weights.lines, weights.holes, weights.bumpiness, weights.wells]
+}
+
+fn from_genes(genes: Vec<f64>) -> Weights {
+    Weights {
+        height: genes[0],
+        lines: genes[1],
+        holes: genes[2],
+        bumpiness: genes[3],
+        wells: genes[4],
+    }
+}
+
+/// Scales the weights to a length of one, only how they relate to each other matters to the bot
+fn normalize(genes: Vec<f64>) -> Vec<f64> {
+    let length = genes.iter().map(|gene| gene * gene).sum::<f64>().sqrt();
+    if length == 0.0 {
+        return genes;
+    }
+    genes.iter().map(|gene| gene / length).collect()
+}
+
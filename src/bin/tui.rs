@@ -0,0 +1,254 @@
+use std::io;
+use std::time::{Duration, Instant};
+
+use ratatui::{
+    backend::TestBackend,
+    buffer::Buffer,
+    crossterm::event::{self, Event, KeyCode, KeyEventKind},
+    layout::{Constraint, Layout, Rect},
+    style::Color,
+    text::Line,
+    widgets::{Block, Paragraph, Widget},
+    DefaultTerminal, Frame, Terminal,
+};
+use rust_tetris::{
+    board::{CellStatus, Size},
+    gamestate::{GameEvent, GameStatus},
+    player::{Controls, InputType, Player, PlayerEvent},
+};
+
+/// how long to wait for a key before the game is updated again
+const FRAME_TIME: Duration = Duration::from_millis(16);
+/// the upper half of a character is the upper cell, the background is the lower cell
+const UPPER_HALF: &str = "▀";
+/// used when only the lower cell is filled
+const LOWER_HALF: &str = "▄";
+/// how many frames the smoke test plays
+const SMOKE_FRAMES: usize = 600;
+/// the keys the smoke test presses, one every few frames
+const SMOKE_KEYS: [KeyCode; 6] = [KeyCode::Char('f'), KeyCode::Left, KeyCode::Up, KeyCode::Right, KeyCode::Right, KeyCode::Down];
+
+/// a grid of colored cells drawn with half blocks so two rows fit in one line, every cell is two characters wide
+struct HalfBlocks {
+    rows: Vec<Vec<Option<Color>>>,
+}
+
+impl Widget for HalfBlocks {
+    fn render(self, area: Rect, buf: &mut Buffer) {
+        for (line, pair) in self.rows.chunks(2).enumerate() {
+            let upper = &pair[0];
+            let lower = pair.get(1);
+
+            for (x, top) in upper.iter().enumerate() {
+                let bottom = lower.and_then(|row| row[x]);
+                let (symbol, fg, bg) = match (top, bottom) {
+                    (Some(top), bottom) => (UPPER_HALF, *top, bottom.unwrap_or(Color::Reset)),
+                    (None, Some(bottom)) => (LOWER_HALF, bottom, Color::Reset),
+                    (None, None) => (" ", Color::Reset, Color::Reset),
+                };
+
+                for column in 0..2 {
+                    let position = (area.x + (x * 2 + column) as u16, area.y + line as u16);
+                    if let Some(cell) = buf.cell_mut(position).filter(|_| area.contains(position.into())) {
+                        cell.set_symbol(symbol).set_fg(fg).set_bg(bg);
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// a single player game that is played in the terminal
+struct TerminalGame {
+    player: Player,
+    status: GameStatus,
+    /// how big the board is, kept for when the game starts over
+    size: Size,
+}
+
+impl TerminalGame {
+    fn new(size: Size) -> TerminalGame {
+        TerminalGame {
+            player: Player::sized(Controls::single(), size),
+            status: GameStatus::Startup,
+            size,
+        }
+    }
+
+    /// Handles a key press, returns false when the player wants to quit
+    fn input(&mut self, key: KeyCode) -> bool {
+        let input_type = match key {
+            KeyCode::Char('q') | KeyCode::Esc => return false,
+            KeyCode::Char('p') => {
+                self.status.update(GameEvent::Pause);
+                return true;
+            },
+            KeyCode::Char('f') => {
+                if self.status == GameStatus::GameOver {
+                    self.player = Player::sized(Controls::single(), self.size);
+                }
+                self.status.update(GameEvent::Start);
+                return true;
+            },
+            KeyCode::Left | KeyCode::Char('a') => InputType::Left,
+            KeyCode::Right | KeyCode::Char('d') => InputType::Right,
+            KeyCode::Down | KeyCode::Char('s') => InputType::Down,
+            KeyCode::Up | KeyCode::Char('w') | KeyCode::Char('r') => InputType::Rotate,
+            _ => return true,
+        };
+
+        if self.status == GameStatus::Playing {
+            self.player.handle_input(input_type);
+        }
+        true
+    }
+
+    /// Updates the game based on the elapsed time since the last update
+    fn update(&mut self, dt: f64) {
+        if self.status == GameStatus::Playing {
+            if let Some(PlayerEvent::ToppedOut) = self.player.update(dt) {
+                self.status.update(GameEvent::End);
+            }
+        }
+    }
+
+    /// Draws the board on the left and the score, next piece and controls on the right
+    fn draw(&self, frame: &mut Frame) {
+        let board = self.player.board();
+        let board_width = (board.width() * 2 + 2) as u16;
+        let board_height = (board.size().height.div_ceil(2) + 2) as u16;
+        let [board_area, hud_area] = Layout::horizontal([Constraint::Length(board_width), Constraint::Min(0)]).areas(frame.area());
+        let board_area = Rect { height: board_height.min(board_area.height), ..board_area };
+
+        let board_block = Block::bordered().title("Tetris");
+        let inner = board_block.inner(board_area);
+        frame.render_widget(board_block, board_area);
+
+        // the hidden rows the pieces spawn in are left out
+        let rows = board.data[board.hidden()..].iter()
+            .map(|row| row.iter()
+                .map(|cell| (cell.status != CellStatus::Empty).then(|| terminal_color(cell.color)))
+                .collect())
+            .collect();
+        frame.render_widget(HalfBlocks { rows }, inner);
+
+        let hud_block = Block::bordered().title("Score");
+        let hud_inner = hud_block.inner(hud_area);
+        frame.render_widget(hud_block, hud_area);
+
+        let message = match self.status {
+            GameStatus::Startup => "Press F to start",
+            GameStatus::Paused => "Paused, press P to resume",
+            GameStatus::GameOver => "Game over, press F to restart",
+            _ => "",
+        };
+        let text = vec![
+            Line::from(format!("Current score: {}", self.player.score)),
+            Line::from(""),
+            Line::from("Next:"),
+            Line::from(""),
+            Line::from(""),
+            Line::from(""),
+            Line::from(message),
+            Line::from(""),
+            Line::from("Arrows/WASD move, Up/W/R rotate"),
+            Line::from("P pause, Q quit"),
+        ];
+        frame.render_widget(Paragraph::new(text), hud_inner);
+
+        let (shape, color) = self.player.preview();
+        let rows = self.player.pieces().piece(shape).matrix.iter()
+            .skip_while(|row| row.iter().all(|cell| *cell == 0))
+            .map(|row| row.iter().map(|cell| (*cell == 1).then(|| terminal_color(color.to_color()))).collect())
+            .collect();
+        let preview_area = Rect { y: hud_inner.y + 3, height: 2, ..hud_inner }.intersection(hud_inner);
+        frame.render_widget(HalfBlocks { rows }, preview_area);
+    }
+}
+
+/// Turns a color of the game into a terminal color
+fn terminal_color(color: [f32; 4]) -> Color {
+    Color::Rgb((color[0] * 255.0) as u8, (color[1] * 255.0) as u8, (color[2] * 255.0) as u8)
+}
+
+/// Plays in the terminal until the player quits
+fn run(terminal: &mut DefaultTerminal, size: Size) -> io::Result<()> {
+    let mut game = TerminalGame::new(size);
+    let mut last_update = Instant::now();
+
+    loop {
+        terminal.draw(|frame| game.draw(frame))?;
+
+        if event::poll(FRAME_TIME)? {
+            if let Event::Key(key) = event::read()? {
+                // terminals only tell when a key goes down, some also report when it is let go
+                if key.kind == KeyEventKind::Press && !game.input(key.code) {
+                    return Ok(());
+                }
+            }
+        }
+
+        game.update(last_update.elapsed().as_secs_f64());
+        last_update = Instant::now();
+    }
+}
+
+/// Plays a scripted game on an in-memory terminal and returns the last screen as text
+/// This needs no real terminal so it can run in CI
+fn smoke_test(frames: usize) -> String {
+    let mut terminal = Terminal::new(TestBackend::new(60, 12)).expect("A test terminal can always be created");
+    let mut game = TerminalGame::new(Size::default());
+
+    for frame in 0..frames {
+        if frame % 5 == 0 {
+            game.input(SMOKE_KEYS[(frame / 5) % SMOKE_KEYS.len()]);
+        }
+        game.update(FRAME_TIME.as_secs_f64());
+        terminal.draw(|screen| game.draw(screen)).expect("Drawing to a test terminal can't fail");
+    }
+
+    let buffer = terminal.backend().buffer();
+    let mut screen = String::new();
+    for y in 0..buffer.area.height {
+        for x in 0..buffer.area.width {
+            screen += buffer[(x, y)].symbol();
+        }
+        screen.push('\n');
+    }
+    screen
+}
+
+/// Plays the game in the terminal, use --smoke to play a scripted game without a terminal and print the last screen
+fn main() -> io::Result<()> {
+    let args: Vec<String> = std::env::args().collect();
+    if args.iter().any(|arg| arg == "--smoke") {
+        print!("{}", smoke_test(SMOKE_FRAMES));
+        return Ok(());
+    }
+
+    // --size 10x20 plays on a board of another size
+    let size = match args.iter().position(|arg| arg == "--size").and_then(|index| args.get(index + 1)) {
+        Some(size) => size.parse().map_err(|error| io::Error::new(io::ErrorKind::InvalidInput, error))?,
+        None => Size::default(),
+    };
+
+    let mut terminal = ratatui::init();
+    let result = run(&mut terminal, size);
+    ratatui::restore();
+    result
+}
+
+#[cfg(test)]
+mod tui_tests {
+    use super::*;
+
+    #[test]
+    fn test_smoke() {
+        let screen = smoke_test(SMOKE_FRAMES);
+
+        assert!(screen.contains("Tetris"));
+        assert!(screen.contains("Current score"));
+        // blocks were drawn on the board
+        assert!(screen.contains(UPPER_HALF) || screen.contains(LOWER_HALF));
+    }
+}
@@ -1,17 +1,22 @@
 use crate::{
     blockcolor::BlockColor,
-    blockshape::{self, BlockShape},
-    board::{self, Board, Cell, CellStatus},
+    blockshape::{self, BlockShape, Matrix},
+    board::{Board, Cell, CellStatus},
+    pieces::Piece,
+    player::InputType,
 };
 
+#[derive(Clone)]
 pub struct Block {
     shape: BlockShape,
-    matrix: [[i32; 4]; 4],
+    matrix: Matrix,
+    /// y, x the block tries to move by when it can't rotate where it is
+    kicks: Vec<(isize, isize)>,
     pub color: BlockColor,
     pub status: BlockStatus,
     pub position: (isize, isize),
 }
-#[derive(PartialEq)]
+#[derive(PartialEq, Clone, Copy)]
 pub enum BlockStatus {
     Moving,
     Frozen,
@@ -20,13 +25,22 @@ pub enum BlockStatus {
 impl Block {
     /// This method creates a new Block instance and adds it to the board at the specified position
     pub fn new(board: &mut Board, position: (isize, isize)) -> Block {
-        let shape = BlockShape::random();
-        let color = BlockColor::random();
-        let matrix = shape.get_shape();
+        Block::with_shape(board, position, BlockShape::random())
+    }
+
+    /// This method creates a new Block instance of the given shape and adds it to the board at the specified position
+    pub fn with_shape(board: &mut Board, position: (isize, isize), shape: BlockShape) -> Block {
+        Block::with_piece(board, position, &Piece::from(shape))
+    }
+
+    /// This method creates a new Block instance of the given piece and adds it to the board at the specified position
+    pub fn with_piece(board: &mut Board, position: (isize, isize), piece: &Piece) -> Block {
+        let color = piece.color.unwrap_or_else(BlockColor::random);
 
         let mut block = Block {
-            shape,
-            matrix,
+            shape: piece.shape,
+            matrix: piece.matrix.clone(),
+            kicks: piece.kicks.clone(),
             color,
             status: BlockStatus::Moving,
             position,
@@ -36,17 +50,16 @@ impl Block {
         block
     }
 
-    /// This method creates a new Block instance with a different shape and color from the old block
+    /// This method creates a new Block instance of the given piece and a different color from the old block, unless the piece has its own color
     /// Adds it to the board at the specified position if the space is not already filled
     /// If the space is filled it returns a None and the game should end
-    pub fn next(board: &mut Board, position: (isize, isize), old_block: &Block) -> Option<Block> {
-        let shape = BlockShape::random_except(old_block.shape);
-        let color = BlockColor::next_color(old_block.color);
-        let matrix = shape.get_shape();
+    pub fn next(board: &mut Board, position: (isize, isize), piece: &Piece, old_block: &Block) -> Option<Block> {
+        let color = piece.color.unwrap_or_else(|| BlockColor::next_color(old_block.color));
 
         let mut block = Block {
-            shape,
-            matrix,
+            shape: piece.shape,
+            matrix: piece.matrix.clone(),
+            kicks: piece.kicks.clone(),
             color,
             status: BlockStatus::Moving,
             position,
@@ -60,6 +73,34 @@ impl Block {
         }
     }
 
+    /// Returns the shape of the block
+    pub fn shape(&self) -> BlockShape {
+        self.shape
+    }
+
+    /// Returns the matrix of the block as it is currently rotated
+    pub fn matrix(&self) -> &Matrix {
+        &self.matrix
+    }
+
+    /// Changes the y, x the block tries to move by when it can't rotate where it is
+    pub fn set_kicks(&mut self, kicks: Vec<(isize, isize)>) {
+        self.kicks = kicks;
+    }
+
+    /// Returns the y, x of every cell on the board that is filled by this block
+    pub fn cells(&self) -> Vec<(usize, usize)> {
+        let mut cells = Vec::new();
+        for y in 0..self.matrix.len() {
+            for x in 0..self.matrix[y].len() {
+                if self.matrix[y][x] == 1 {
+                    cells.push(((self.position.0 + y as isize) as usize, (self.position.1 + x as isize) as usize));
+                }
+            }
+        }
+        cells
+    }
+
     /// This method erases the block from the board
     fn erase_from_board(&mut self, board: &mut Board) {
         for y in 0..self.matrix.len() {
@@ -121,7 +162,7 @@ impl Block {
         for y in 0..self.matrix.len() {
             for x in 0..self.matrix[y].len() {
                 if self.matrix[y][x] == 1 {
-                    if Block::is_out_of_bounds(next_position, y, x) {
+                    if Block::is_out_of_bounds(board, next_position, y, x) {
                         return self.freeze_if(y_change);
                     } else if self.get_cell_at_specific_position(board, next_position, y, x).status != CellStatus::Empty {
                         let local_pos: (usize, usize) = Self::coord_add_i16_to_usize((y, x), (y_change, x_change));
@@ -148,28 +189,31 @@ impl Block {
     }
 
     /// This method checks if the specified position is out of bounds of the game board
-    fn is_out_of_bounds(position: (isize, isize), y: usize, x: usize) -> bool {
-        position.0 + y as isize >= board::HEIGHT as isize
-            || position.1 >= board::WIDTH as isize
-            || (position.1 + x as isize) >= board::WIDTH as isize
+    fn is_out_of_bounds(board: &Board, position: (isize, isize), y: usize, x: usize) -> bool {
+        position.0 + y as isize >= board.height() as isize
+            || position.1 >= board.width() as isize
+            || (position.1 + x as isize) >= board.width() as isize
             || (position.0 + y as isize) < 0
             || (position.1 + x as isize) < 0
     }
 
-    /// This method checks if the block can be rotated
-    fn can_rotate(&mut self, board: &mut Board, matrix: &[[i32; 4]; 4]) -> bool {
+    /// This method checks if the block can be rotated into the matrix at the specified position
+    fn can_rotate(&mut self, board: &mut Board, matrix: &Matrix, position: (isize, isize)) -> bool {
         if self.status != BlockStatus::Moving {
             return false;
         }
+        let cells = self.cells();
         // checks if the block can be rotated (yes this is different than moving)
         for y in 0..matrix.len() {
             for x in 0..matrix[y].len() {
                 if matrix[y][x] == 1 {
                     // is it inside the board
-                    if Block::is_out_of_bounds(self.position, y, x) {
+                    if Block::is_out_of_bounds(board, position, y, x) {
                         return false
-                    // is the block it is moving into empty
-                    } else if self.get_cell_at_current_position(board, y, x).status != CellStatus::Empty && self.matrix[y][x] == 0  {
+                    }
+                    // is the block it is moving into empty or a part of this block
+                    let cell = ((position.0 + y as isize) as usize, (position.1 + x as isize) as usize);
+                    if board.data[cell.0][cell.1].status != CellStatus::Empty && !cells.contains(&cell) {
                         return false;
                     }
                 }
@@ -179,10 +223,12 @@ impl Block {
     }
 
     /// This method checks if the space the block would fill is already taken and returns true if it is taken
+    /// Space outside of the board counts as taken, a big piece may not fit on a narrow board
     fn check_if_space_filled(&mut self, board: &mut Board) -> bool {
         for y in 0..self.matrix.len() {
             for x in 0..self.matrix[y].len() {
-                if self.matrix[y][x] == 1 && self.get_cell_at_current_position(board, y, x).status != CellStatus::Empty {
+                if self.matrix[y][x] == 1 && (Block::is_out_of_bounds(board, self.position, y, x)
+                    || self.get_cell_at_current_position(board, y, x).status != CellStatus::Empty) {
                     return true
                 }
             }
@@ -213,6 +259,21 @@ impl Block {
         &mut board.data[(position.0 + y as isize) as usize][(position.1 + x as isize) as usize]
     }
 
+    /// Moves or rotates the block according to the input
+    pub fn handle_input(&mut self, board: &mut Board, input_type: InputType) {
+        /// to go left go -1 on the x-axis
+        const LEFT_X: i16 = -1;
+        /// to go right go +1 on the x-axis
+        const RIGHT_X: i16 = 1;
+
+        match input_type {
+            InputType::Left => self.move_sideways(board, LEFT_X),
+            InputType::Right => self.move_sideways(board, RIGHT_X),
+            InputType::Down => self.move_down(board),
+            InputType::Rotate => self.rotate(board),
+        }
+    }
+
     /// This method tries to move a block sideways on the game board
     pub fn move_sideways(&mut self, board: &mut Board, x_change: i16) {
         self.update(board, 0, x_change)
@@ -224,23 +285,30 @@ impl Block {
     }
 
     /// This method tries to rotate a block 90 degrees clockwise on the game board
+    /// When it doesn't fit where it is, the kicks of its piece are tried in order
     pub fn rotate(&mut self, board: &mut Board) {
         if self.shape == blockshape::BlockShape::O {
             return;
         }
 
-        let mut rotated_matrix: [[i32; 4]; 4] = self.matrix;
+        let mut rotated_matrix = self.matrix.clone();
         BlockShape::rotate_matrix(&mut rotated_matrix);
 
-        if self.can_rotate(board, &rotated_matrix) {
-            // erases the old position
-            self.erase_from_board(board);
+        let kicks = std::iter::once((0, 0)).chain(self.kicks.clone());
+        for (y, x) in kicks {
+            let position = (self.position.0 + y, self.position.1 + x);
+            if self.can_rotate(board, &rotated_matrix, position) {
+                // erases the old position
+                self.erase_from_board(board);
 
-            // changes the matrix to be rotated
-            self.matrix = rotated_matrix;
+                // changes the matrix to be rotated
+                self.matrix = rotated_matrix;
 
-            // moves to the new position
-            self.add_to_board(board, self.position);
+                // moves to the new position
+                self.add_to_board(board, position);
+                self.position = position;
+                return;
+            }
         }
     }
 }
@@ -316,11 +384,11 @@ mod block_tests {
         let i_position: (isize, isize) = (1, 1);
         let mut block = Block::new(&mut board, position);
 
-        let mut rotated_matrix: [[i32; 4]; 4] = block.matrix;
+        let mut rotated_matrix = block.matrix.clone();
         BlockShape::rotate_matrix(&mut rotated_matrix);    
 
         // it can rotate
-        assert_eq!(block.can_rotate(&mut board, &rotated_matrix), true);
+        assert_eq!(block.can_rotate(&mut board, &rotated_matrix, block.position), true);
 
         // it cant rotate into other blocks
         for y in 0..block.matrix.len() {
@@ -331,11 +399,11 @@ mod block_tests {
             }
         } 
         // it cannot rotate because the cells inserted above are in the way
-        assert_eq!(block.can_rotate(&mut board, &rotated_matrix), false);
+        assert_eq!(block.can_rotate(&mut board, &rotated_matrix, block.position), false);
 
         // it cannot rotate out of bounds
         block.position = (20, 20);
-        assert_eq!(block.can_rotate(&mut board, &rotated_matrix), false);
+        assert_eq!(block.can_rotate(&mut board, &rotated_matrix, block.position), false);
 
     }
 
@@ -345,10 +413,33 @@ mod block_tests {
         let position = (1, 1);
         let block = Block::new(&mut board, position);
 
-        let new_block: Option<Block> = Block::next(&mut board, (5, 1), &block); 
+        let new_block: Option<Block> = Block::next(&mut board, (5, 1), &Piece::from(block.shape), &block); 
         assert_eq!(new_block.is_some(), true);
 
-        let new_block2: Option<Block> = Block::next(&mut board, (1, 1), &block); 
+        let new_block2: Option<Block> = Block::next(&mut board, (1, 1), &Piece::from(block.shape), &block); 
         assert_eq!(new_block2.is_none(), true);
     }
+
+    #[test]
+    fn test_kicks() {
+        let mut board = Board::new();
+        // a standing domino against the left wall can't lie down without moving right
+        let piece = Piece {
+            shape: BlockShape::Custom(0),
+            name: String::from("domino"),
+            matrix: vec![vec![0, 1], vec![0, 1]],
+            color: None,
+            kicks: vec![(0, 1)],
+        };
+        let mut block = Block::with_piece(&mut board, (0, -1), &piece);
+        block.rotate(&mut board);
+        assert_eq!(block.cells(), [(1, 0), (1, 1)]);
+
+        // without kicks it stays as it is
+        let mut board = Board::new();
+        let piece = Piece { kicks: Vec::new(), ..piece };
+        let mut block = Block::with_piece(&mut board, (0, -1), &piece);
+        block.rotate(&mut board);
+        assert_eq!(block.cells(), [(0, 0), (1, 0)]);
+    }
 }
\ No newline at end of file
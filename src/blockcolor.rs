@@ -1,7 +1,25 @@
+use std::fmt;
+use std::str::FromStr;
+
 use piston_window::types::Color;
 use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+use crate::blockshape::BlockShape;
+
+/// every block color there is
+pub const COLORS: [BlockColor; 8] = [
+    BlockColor::Red,
+    BlockColor::Blue,
+    BlockColor::Green,
+    BlockColor::Yellow,
+    BlockColor::Purple,
+    BlockColor::Orange,
+    BlockColor::Cyan,
+    BlockColor::Pink,
+];
 
-#[derive(Debug, PartialEq, Copy, Clone)]
+#[derive(Debug, PartialEq, Eq, Hash, Copy, Clone, Serialize, Deserialize)]
 pub enum BlockColor {
     Red,
     Blue,
@@ -23,25 +41,34 @@ impl BlockColor {
             BlockColor::Yellow => [1.00, 1.00, 0.30, 1.0],
             BlockColor::Purple => [0.60, 0.10, 1.00, 1.0],
             BlockColor::Orange => [1.00, 0.60, 0.00, 1.0],
-            BlockColor::Cyan => [0.00, 0.00, 0.50, 1.0],
+            BlockColor::Cyan => [0.00, 0.90, 1.00, 1.0],
             BlockColor::Pink => [1.00, 0.20, 1.00, 1.0],
         }
     }
 
+    /// Get the block color that is drawn in the given color, None for colors like garbage that are not a block color
+    pub fn from_color(color: Color) -> Option<BlockColor> {
+        COLORS.iter().copied().find(|block_color| block_color.to_color() == color)
+    }
+
     /// Get a random block color
     pub fn random() -> Self {
-        let colors = [
-            BlockColor::Red,
-            BlockColor::Blue,
-            BlockColor::Green,
-            BlockColor::Yellow,
-            BlockColor::Purple,
-            BlockColor::Orange,
-            BlockColor::Cyan,
-            BlockColor::Pink,
-        ];
         let mut rng = rand::thread_rng();
-        colors[rng.gen_range(0..colors.len())]
+        COLORS[rng.gen_range(0..COLORS.len())]
+    }
+
+    /// Get the color the guideline gives the shape, custom pieces have none
+    pub fn guideline(shape: BlockShape) -> Option<BlockColor> {
+        match shape {
+            BlockShape::I => Some(BlockColor::Cyan),
+            BlockShape::J => Some(BlockColor::Blue),
+            BlockShape::L => Some(BlockColor::Orange),
+            BlockShape::O => Some(BlockColor::Yellow),
+            BlockShape::S => Some(BlockColor::Green),
+            BlockShape::T => Some(BlockColor::Purple),
+            BlockShape::Z => Some(BlockColor::Red),
+            BlockShape::Custom(_) => None,
+        }
     }
 
     /// Get the next block color in the sequence
@@ -57,4 +84,79 @@ impl BlockColor {
             BlockColor::Pink => BlockColor::Red,
         }
     }
-}
\ No newline at end of file
+}
+
+/// How blocks get their colors
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum ColorPolicy {
+    /// every shape always has the same color, I is cyan, O yellow, T purple, S green, Z red, J blue and L orange
+    Guideline,
+    /// every block has the color that comes after the one of the block before it
+    #[default]
+    Cycle,
+    /// every block gets a random color
+    Random,
+}
+
+impl FromStr for ColorPolicy {
+    type Err = String;
+
+    /// Reads a policy written as guideline, cycle or random
+    fn from_str(text: &str) -> Result<ColorPolicy, String> {
+        match text {
+            "guideline" => Ok(ColorPolicy::Guideline),
+            "cycle" => Ok(ColorPolicy::Cycle),
+            "random" => Ok(ColorPolicy::Random),
+            _ => Err(format!("{} is not a way to color pieces, use guideline, cycle or random", text)),
+        }
+    }
+}
+
+impl fmt::Display for ColorPolicy {
+    /// Writes the policy the way it is read
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ColorPolicy::Guideline => write!(f, "guideline"),
+            ColorPolicy::Cycle => write!(f, "cycle"),
+            ColorPolicy::Random => write!(f, "random"),
+        }
+    }
+}
+
+impl ColorPolicy {
+    /// Returns the color a shape always has with this policy, if it has one
+    pub fn fixed(self, shape: BlockShape) -> Option<BlockColor> {
+        match self {
+            ColorPolicy::Guideline => BlockColor::guideline(shape),
+            ColorPolicy::Cycle | ColorPolicy::Random => None,
+        }
+    }
+
+    /// Returns the color of a block of the shape that comes after a block of the previous color
+    /// random is the color a random block gets, it is passed in so the preview can show it before the block spawns
+    /// Shapes without a guideline color cycle
+    pub fn color(self, shape: BlockShape, previous: BlockColor, random: BlockColor) -> BlockColor {
+        match self {
+            ColorPolicy::Random => random,
+            _ => self.fixed(shape).unwrap_or_else(|| BlockColor::next_color(previous)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod blockcolor_tests {
+    use super::*;
+
+    #[test]
+    fn test_color_policy() {
+        let previous = BlockColor::Red;
+        assert_eq!(ColorPolicy::Guideline.color(BlockShape::T, previous, BlockColor::Pink), BlockColor::Purple);
+        assert_eq!(ColorPolicy::Guideline.color(BlockShape::Custom(0), previous, BlockColor::Pink), BlockColor::Blue);
+        assert_eq!(ColorPolicy::Cycle.color(BlockShape::T, previous, BlockColor::Pink), BlockColor::Blue);
+        assert_eq!(ColorPolicy::Random.color(BlockShape::T, previous, BlockColor::Pink), BlockColor::Pink);
+
+        assert_eq!("guideline".parse(), Ok(ColorPolicy::Guideline));
+        assert_eq!(ColorPolicy::Random.to_string(), "random");
+        assert!("rainbow".parse::<ColorPolicy>().is_err());
+    }
+}
@@ -1,6 +1,21 @@
 use rand::Rng;
+use serde::{Deserialize, Serialize};
 
-#[derive(Debug, PartialEq, Copy, Clone)]
+/// every shape there is
+pub const SHAPES: [BlockShape; 7] = [
+    BlockShape::I,
+    BlockShape::J,
+    BlockShape::L,
+    BlockShape::O,
+    BlockShape::S,
+    BlockShape::T,
+    BlockShape::Z,
+];
+
+/// the cells of a piece in a square, 1 is filled and 0 is empty
+pub type Matrix = Vec<Vec<i32>>;
+
+#[derive(Debug, PartialEq, Eq, Hash, Copy, Clone, Serialize, Deserialize)]
 pub enum BlockShape {
     I,
     J,
@@ -9,46 +24,34 @@ pub enum BlockShape {
     S,
     T,
     Z,
+    /// a piece from a piece set file, the number is where it is in the set
+    Custom(u8),
 }
 
 impl BlockShape {
     /// Generates a random BlockShape
     pub fn random() -> Self {
-        let blocks = [
-            BlockShape::I,
-            BlockShape::J,
-            BlockShape::L,
-            BlockShape::O,
-            BlockShape::S,
-            BlockShape::T,
-            BlockShape::Z,
-        ];
-        let mut rng = rand::thread_rng();
-        blocks[rng.gen_range(0..blocks.len())]
+        BlockShape::random_from(&mut rand::thread_rng())
+    }
+
+    /// Generates a random BlockShape with the given random number generator, the same seed gives the same shapes
+    pub fn random_from(rng: &mut impl Rng) -> Self {
+        SHAPES[rng.gen_range(0..SHAPES.len())]
     }
 
     /// Generates a random BlockShape except for the provided excluded shape
-    pub fn random_except(excluded_shape: BlockShape) -> BlockShape {
-        let blocks = [
-            BlockShape::I,
-            BlockShape::J,
-            BlockShape::L,
-            BlockShape::O,
-            BlockShape::S,
-            BlockShape::T,
-            BlockShape::Z,
-        ];
-        let mut rng = rand::thread_rng();
-        let mut index = rng.gen_range(0..blocks.len());
-        while blocks[index] == excluded_shape {
-            index = rng.gen_range(0..blocks.len());
+    pub fn random_except(excluded_shape: BlockShape, rng: &mut impl Rng) -> BlockShape {
+        let mut index = rng.gen_range(0..SHAPES.len());
+        while SHAPES[index] == excluded_shape {
+            index = rng.gen_range(0..SHAPES.len());
         }
-        blocks[index]
-    }    
+        SHAPES[index]
+    }
 
     /// Returns the shape matrix of the BlockShape
-    pub fn get_shape(&self) -> [[i32; 4]; 4] {
-        match *self {
+    /// Custom pieces keep their matrix in their piece set, so theirs is empty
+    pub fn get_shape(&self) -> Matrix {
+        let matrix = match *self {
             BlockShape::I => [
                 [0, 0, 0, 0],
                 [1, 1, 1, 1],
@@ -91,14 +94,32 @@ impl BlockShape {
                 [0, 0, 0, 0],
                 [0, 0, 0, 0],
             ],
+            BlockShape::Custom(_) => return Matrix::new(),
+        };
+        matrix.iter().map(|row| row.to_vec()).collect()
+    }
+
+    /// Returns x, y of the cells around the center of the piece when it spawns, with y going up
+    /// This is how other tetris programs see pieces, used to talk to them, they don't know custom pieces so those have none
+    pub fn srs_cells(&self) -> &'static [(i32, i32)] {
+        match *self {
+            BlockShape::I => &[(-1, 0), (0, 0), (1, 0), (2, 0)],
+            BlockShape::J => &[(-1, 0), (0, 0), (1, 0), (-1, 1)],
+            BlockShape::L => &[(-1, 0), (0, 0), (1, 0), (1, 1)],
+            BlockShape::O => &[(0, 0), (1, 0), (0, 1), (1, 1)],
+            BlockShape::S => &[(-1, 0), (0, 0), (0, 1), (1, 1)],
+            BlockShape::T => &[(-1, 0), (0, 0), (1, 0), (0, 1)],
+            BlockShape::Z => &[(-1, 1), (0, 1), (0, 0), (1, 0)],
+            BlockShape::Custom(_) => &[],
         }
     }
 
-    /// rotates a given a given matrix clockwise 90 degrees
-    pub fn rotate_matrix(matrix: &mut [[i32; 4]; 4]) {
+    /// rotates a given a given square matrix of any size clockwise 90 degrees
+    pub fn rotate_matrix(matrix: &mut Matrix) {
+        let size = matrix.len();
         // transpose the matrix
-        for i in 0..4 {
-            for j in i+1..4 {
+        for i in 0..size {
+            for j in i+1..size {
                 let temp = matrix[i][j];
                 matrix[i][j] = matrix[j][i];
                 matrix[j][i] = temp;
@@ -106,11 +127,11 @@ impl BlockShape {
         }
     
         // flip the matrix horizontally
-        for i in 0..4 {
-            for j in 0..2 {
+        for i in 0..size {
+            for j in 0..size / 2 {
                 let temp = matrix[i][j];
-                matrix[i][j] = matrix[i][3-j];
-                matrix[i][3-j] = temp;
+                matrix[i][j] = matrix[i][size-1-j];
+                matrix[i][size-1-j] = temp;
             }
         }
     }
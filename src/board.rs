@@ -1,20 +1,37 @@
+use std::fmt;
+use std::ops::RangeInclusive;
+use std::str::FromStr;
+
 use piston_window::color::BLACK;
 use piston_window::types::Color;
 use piston_window::Context;
 use piston_window::G2d;
+use serde::{Deserialize, Serialize};
 
-use crate::renderer;
+use crate::renderer::Renderer;
 
-/// the width of the playing board
+/// the width of the playing board when no other size is picked
 pub const WIDTH: usize = 10;
-/// the height of the playing board
+/// the height of the playing board when no other size is picked
 pub const HEIGHT: usize = 16;
+/// the narrowest and widest boards that can be played on
+pub const WIDTHS: RangeInclusive<usize> = 4..=20;
+/// the lowest and highest boards that can be played on, pieces need a few rows to spawn and turn in
+pub const HEIGHTS: RangeInclusive<usize> = 8..=40;
+/// the rows above the visible board where pieces spawn when no other amount is picked
+pub const HIDDEN: usize = 2;
+/// the most hidden rows a board can have
+pub const MAX_HIDDEN: usize = 20;
+/// the color of garbage rows sent over by an opponent
+pub const GARBAGE_COLOR: Color = [0.5, 0.5, 0.5, 1.0];
 
 /// this struct holds a 2d vector array of cells 
-/// size is HEIGHT * WIDTH
-#[derive(PartialEq)]
+/// size is (hidden + height) * width, every row has the same length
+/// the first rows are hidden, pieces spawn in them but they are not drawn
+#[derive(PartialEq, Clone, Debug)]
 pub struct Board {
     pub data: Vec<Vec<Cell>>,
+    hidden: usize,
 }
 
 /// holds data about cells; the color and status
@@ -31,22 +48,157 @@ pub enum CellStatus {
     Frozen,
 }
 
+/// How many cells wide and high a board is
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Size {
+    pub width: usize,
+    /// the rows that are shown
+    pub height: usize,
+    /// the rows above the ones that are shown
+    pub hidden: usize,
+}
+
+impl Default for Size {
+    fn default() -> Self {
+        Size { width: WIDTH, height: HEIGHT, hidden: HIDDEN }
+    }
+}
+
+impl FromStr for Size {
+    type Err = String;
+
+    /// Reads a size written as width x height, like 10x20, the hidden rows can be added after a plus, like 10x20+20
+    fn from_str(text: &str) -> Result<Size, String> {
+        let (width, height) = text.split_once('x').ok_or_else(|| format!("{} is not written as width x height", text))?;
+        let (height, hidden) = height.split_once('+').unwrap_or((height, ""));
+        let width: usize = width.trim().parse().map_err(|_| format!("{} is not a width", width))?;
+        let height: usize = height.trim().parse().map_err(|_| format!("{} is not a height", height))?;
+        let hidden: usize = if hidden.is_empty() {
+            HIDDEN
+        } else {
+            hidden.trim().parse().map_err(|_| format!("{} is not an amount of hidden rows", hidden))?
+        };
+
+        if !WIDTHS.contains(&width) || !HEIGHTS.contains(&height) || hidden > MAX_HIDDEN {
+            return Err(format!(
+                "boards can be {} to {} wide and {} to {} high with up to {} hidden rows",
+                WIDTHS.start(), WIDTHS.end(), HEIGHTS.start(), HEIGHTS.end(), MAX_HIDDEN,
+            ));
+        }
+        Ok(Size { width, height, hidden })
+    }
+}
+
+impl fmt::Display for Size {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}x{}+{}", self.width, self.height, self.hidden)
+    }
+}
+
+impl Default for Board {
+    fn default() -> Self {
+        Board::new()
+    }
+}
+
 impl Board {
-    /// Creates a new empty board filled with empty cells
+    /// Creates a new empty board of the default size filled with empty cells, without hidden rows
     pub fn new() -> Board {
-        let data = vec![vec![Cell{color: BLACK, status: CellStatus::Empty}; WIDTH]; HEIGHT];
+        Board::with_size(Size { hidden: 0, ..Size::default() })
+    }
 
-        Board { data }
+    /// Creates a new empty board of the given size filled with empty cells
+    pub fn with_size(size: Size) -> Board {
+        let data = vec![vec![Cell{color: BLACK, status: CellStatus::Empty}; size.width]; size.hidden + size.height];
+
+        Board { data, hidden: size.hidden }
     }
 
-    /// Draws all blocks to the window
-    pub fn draw(&self, context: &Context, g2d: &mut G2d) {
-        for y in 0..HEIGHT {
-            for x in 0..WIDTH {
-                if self.data[y][x].status != CellStatus::Empty {
-                    renderer::draw_block(
-                        self.data[y][x].color,
+    /// Returns a copy of the board with the given amount of empty hidden rows above the rows that are shown
+    pub fn with_hidden(&self, hidden: usize) -> Board {
+        let mut board = Board::with_size(Size { hidden, ..self.size() });
+        board.data[hidden..].clone_from_slice(&self.data[self.hidden..]);
+        board
+    }
+
+    /// Returns the amount of cells in every row
+    pub fn width(&self) -> usize {
+        self.data.first().map_or(0, Vec::len)
+    }
+
+    /// Returns the amount of rows, the hidden ones included
+    pub fn height(&self) -> usize {
+        self.data.len()
+    }
+
+    /// Returns the amount of rows at the top that are not shown
+    pub fn hidden(&self) -> usize {
+        self.hidden
+    }
+
+    /// Returns how many cells wide and high the board is
+    pub fn size(&self) -> Size {
+        Size { width: self.width(), height: self.height() - self.hidden, hidden: self.hidden }
+    }
+
+    /// Creates a board from the colors of its cells, row by row with width cells in every row, every colored cell is frozen
+    /// The board has no hidden rows
+    pub fn from_snapshot(cells: &[Option<Color>], width: usize) -> Board {
+        let width = width.max(1);
+        let mut board = Board::with_size(Size { width, height: cells.len() / width, hidden: 0 });
+
+        for (index, color) in cells.iter().enumerate().take(board.width() * board.height()) {
+            if let Some(color) = color {
+                board.data[index / width][index % width] = Cell{color: *color, status: CellStatus::Frozen};
+            }
+        }
+
+        board
+    }
+
+    /// Returns the color of every cell that is shown row by row, None for empty cells, used to show the board on another screen
+    pub fn snapshot(&self) -> Vec<Option<Color>> {
+        self.data[self.hidden..].iter()
+            .flatten()
+            .map(|cell| if cell.status == CellStatus::Empty { None } else { Some(cell.color) })
+            .collect()
+    }
+
+    /// Returns a copy of the board without the falling block
+    pub fn frozen(&self) -> Board {
+        let mut board = self.clone();
+        for cell in board.data.iter_mut().flatten() {
+            if cell.status == CellStatus::Moving {
+                *cell = Cell{color: BLACK, status: CellStatus::Empty};
+            }
+        }
+        board
+    }
+
+    /// Returns the amount of empty cells that have a frozen cell somewhere above them, the falling block does not count
+    pub fn holes(&self) -> usize {
+        (0..self.width())
+            .map(|x| (0..self.height())
+                .skip_while(|&y| self.data[y][x].status != CellStatus::Frozen)
+                .filter(|&y| self.data[y][x].status != CellStatus::Frozen)
+                .count())
+            .sum()
+    }
+
+    /// Returns true if nothing is frozen on the board, the falling block does not count
+    pub fn is_empty(&self) -> bool {
+        self.data.iter().flatten().all(|cell| cell.status != CellStatus::Frozen)
+    }
+
+    /// Draws all blocks that are not in the hidden rows to the window, offset is the x in pixels where the board starts
+    pub fn draw(&self, offset: f64, renderer: &Renderer, context: &Context, g2d: &mut G2d) {
+        for (y, row) in self.data[self.hidden..].iter().enumerate() {
+            for (x, cell) in row.iter().enumerate() {
+                if cell.status != CellStatus::Empty {
+                    renderer.draw_block(
+                        cell.color,
                         x as f64, y as f64,
+                        offset,
                         context, g2d,
                     );
                 };
@@ -54,32 +206,58 @@ impl Board {
         }
     }
 
+    /// Returns the rows that are completely filled, from the bottom up
+    pub fn full_rows(&self) -> Vec<usize> {
+        (0..self.height()).rev()
+            .filter(|&y| self.data[y].iter().all(|cell| cell.status != CellStatus::Empty))
+            .collect()
+    }
+
     /// This checks the board for completed lines 
     /// If completed lines are found they are removed, score is increased and calls move_down
-    pub fn update(&mut self, score: &mut u16) {
-        for y in (0..HEIGHT).rev() {
+    /// Returns the amount of lines that were removed
+    pub fn update(&mut self, score: &mut u16) -> usize {
+        for y in (0..self.height()).rev() {
             let mut cell_count = 0;
-            for x in 0..WIDTH {
+            for x in 0..self.width() {
                 if self.data[y][x].status != CellStatus::Empty {
                     cell_count += 1;
                 }
 
             }
-            if cell_count == WIDTH {
-                for x in 0..WIDTH {
+            if cell_count == self.width() {
+                for x in 0..self.width() {
                     self.data[y][x].status = CellStatus::Empty;
                 }
                 *score += 1;
                 self.move_down(y);
-                return self.update(score);
+                return 1 + self.update(score);
             }
         }
+        0
+    }
+
+    /// Pushes the whole board up and fills the bottom with garbage rows that have one empty cell at hole
+    /// Returns false if filled cells were pushed out of the top of the board, which means the player lost
+    pub fn add_garbage(&mut self, lines: usize, hole: usize) -> bool {
+        let (width, lines) = (self.width(), lines.min(self.height()));
+        let pushed_out = self.data[..lines].iter()
+            .any(|row| row.iter().any(|cell| cell.status != CellStatus::Empty));
+
+        self.data.drain(..lines);
+        for _ in 0..lines {
+            let mut row = vec![Cell{color: GARBAGE_COLOR, status: CellStatus::Frozen}; width];
+            row[hole] = Cell{color: BLACK, status: CellStatus::Empty};
+            self.data.push(row);
+        }
+
+        !pushed_out
     }
 
     /// Moves down all blocks starting at Y
     fn move_down(&mut self, y: usize) {
         for y in (0..y).rev() {
-            for x in 0..WIDTH {
+            for x in 0..self.width() {
                 if self.data[y][x].status == CellStatus::Frozen {
                     (self.data[y][x], self.data[y + 1][x]) = (self.data[y + 1][x], self.data[y][x]);
                 }
@@ -188,4 +366,107 @@ mod boards_tests {
         // check that the score increased by 2 when 2 row was completed
         assert_eq!(score, 2);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_add_garbage() {
+        let mut board = Board::new();
+
+        let test_cell: Cell = Cell{
+            color: BLACK,
+            status: CellStatus::Frozen,
+        };
+
+        // a single cell on the bottom row that should be pushed up by the garbage
+        board.data[HEIGHT - 1][0] = test_cell;
+
+        assert!(board.add_garbage(2, 3));
+
+        // the old bottom row is now 2 rows higher
+        assert_eq!(board.data[HEIGHT - 3][0].status, CellStatus::Frozen);
+
+        // the garbage rows are filled except for the hole
+        for y in HEIGHT - 2..HEIGHT {
+            for x in 0..WIDTH {
+                if x == 3 {
+                    assert_eq!(board.data[y][x].status, CellStatus::Empty);
+                } else {
+                    assert_eq!(board.data[y][x].status, CellStatus::Frozen);
+                }
+            }
+        }
+
+        // garbage pushing cells out of the top of the board means the player lost
+        board.data[0][0] = test_cell;
+        assert!(!board.add_garbage(1, 3));
+    }
+
+    #[test]
+    fn test_snapshot() {
+        let mut board = Board::new();
+
+        board.data[HEIGHT - 1][2] = Cell{
+            color: [1.0, 0.0, 0.0, 1.0],
+            status: CellStatus::Moving,
+        };
+
+        let copy = Board::from_snapshot(&board.snapshot(), WIDTH);
+
+        // the copy has the same colors but everything in it is frozen
+        assert_eq!(copy.data[HEIGHT - 1][2].color, [1.0, 0.0, 0.0, 1.0]);
+        assert_eq!(copy.data[HEIGHT - 1][2].status, CellStatus::Frozen);
+        assert_eq!(copy.data[HEIGHT - 1][3].status, CellStatus::Empty);
+    }
+
+    #[test]
+    fn test_size() {
+        assert_eq!("10x20".parse(), Ok(Size { width: 10, height: 20, hidden: HIDDEN }));
+        assert_eq!("10x20+20".parse(), Ok(Size { width: 10, height: 20, hidden: 20 }));
+        assert!("3x20".parse::<Size>().is_err());
+        assert!("10".parse::<Size>().is_err());
+        assert!("10x20+21".parse::<Size>().is_err());
+        assert_eq!(Size { width: 6, height: 12, hidden: 0 }.to_string(), "6x12+0");
+
+        let mut board = Board::with_size(Size { width: 6, height: 12, hidden: 0 });
+        assert_eq!((board.width(), board.height()), (6, 12));
+
+        // a full row of the narrow board is cleared and the snapshot keeps the shape of the board
+        for x in 0..6 {
+            board.data[11][x] = Cell{color: GARBAGE_COLOR, status: CellStatus::Frozen};
+        }
+        board.data[10][0] = Cell{color: GARBAGE_COLOR, status: CellStatus::Frozen};
+        assert_eq!(board.update(&mut 0), 1);
+        let copy = Board::from_snapshot(&board.snapshot(), 6);
+        assert_eq!(copy.size(), board.size());
+        assert_eq!(copy.snapshot(), board.snapshot());
+    }
+
+    #[test]
+    fn test_hidden_rows() {
+        let mut board = Board::new();
+        board.data[HEIGHT - 1][0] = Cell{color: GARBAGE_COLOR, status: CellStatus::Frozen};
+
+        // the rows that are shown stay the same, the hidden ones come on top
+        let hidden = board.with_hidden(3);
+        assert_eq!(hidden.size(), Size { width: WIDTH, height: HEIGHT, hidden: 3 });
+        assert_eq!(hidden.data[HEIGHT + 2][0], board.data[HEIGHT - 1][0]);
+
+        // only the rows that are shown are sent to other screens
+        assert_eq!(hidden.snapshot(), board.snapshot());
+        assert_eq!(hidden.with_hidden(0), board);
+    }
+
+    #[test]
+    fn test_holes() {
+        let mut board = Board::new();
+        assert!(board.is_empty());
+
+        let frozen = Cell{color: GARBAGE_COLOR, status: CellStatus::Frozen};
+        board.data[HEIGHT - 3][0] = frozen;
+        board.data[HEIGHT - 1][0] = frozen;
+        board.data[HEIGHT - 1][5] = frozen;
+
+        // only the cell under the overhang is a hole, the empty columns next to it are not
+        assert_eq!(board.holes(), 1);
+        assert!(!board.is_empty());
+    }
+}
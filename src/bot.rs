@@ -0,0 +1,380 @@
+use std::collections::{HashSet, VecDeque};
+use std::fs::{self, File};
+use std::io::{self, BufReader};
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    block::{Block, BlockStatus},
+    blockshape::Matrix,
+    board::{Board, CellStatus},
+    pieces::Piece,
+    player::{self, Controls, InputType, Player, PlayerEvent},
+};
+
+/// how many seconds the bot waits between two inputs, so people can follow what it does
+pub const MOVE_PERIOD: f64 = 0.1;
+/// every input the bot tries from every position the block can reach
+const INPUTS: [InputType; 4] = [InputType::Left, InputType::Right, InputType::Rotate, InputType::Down];
+
+/// y, x of a block and how it is rotated
+pub type Spot = ((isize, isize), Matrix);
+
+/// how much every property of a board counts towards its score, negative weights are things the bot avoids
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct Weights {
+    /// the heights of all columns added together
+    pub height: f64,
+    /// lines that were completed
+    pub lines: f64,
+    /// empty cells that have a filled cell somewhere above them
+    pub holes: f64,
+    /// the differences in height between neighbouring columns added together
+    pub bumpiness: f64,
+    /// how much lower columns are than both their neighbours
+    pub wells: f64,
+}
+
+impl Default for Weights {
+    fn default() -> Self {
+        Weights {
+            height: -0.51,
+            lines: 0.76,
+            holes: -0.36,
+            bumpiness: -0.18,
+            wells: -0.1,
+        }
+    }
+}
+
+impl Weights {
+    /// Reads weights from a json file, like the one tetris-train writes
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<Weights> {
+        let file = File::open(path)?;
+        Ok(serde_json::from_reader(BufReader::new(file))?)
+    }
+
+    /// Writes the weights to a json file
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        fs::write(path, serde_json::to_string_pretty(self)?)
+    }
+}
+
+/// a spot the block can be locked in and the inputs that get it there
+pub struct Placement {
+    /// y, x of the block when it is locked
+    pub position: (isize, isize),
+    /// the rotation of the block when it is locked
+    pub matrix: Matrix,
+    /// the inputs that move the block from where it is now into this spot, the last one locks it
+    pub inputs: Vec<InputType>,
+    /// the board after the block was locked and completed lines were cleared
+    pub board: Board,
+    /// the amount of lines this placement completes
+    pub lines: usize,
+}
+
+impl Placement {
+    /// Returns where the block ends up and how it is rotated
+    pub fn spot(&self) -> Spot {
+        (self.position, self.matrix.clone())
+    }
+
+    /// Returns the y, x of every cell on the board the block fills once it is locked, sorted
+    pub fn cells(&self) -> Vec<(usize, usize)> {
+        let mut cells = Vec::new();
+        for y in 0..self.matrix.len() {
+            for x in 0..self.matrix[y].len() {
+                if self.matrix[y][x] == 1 {
+                    cells.push(((self.position.0 + y as isize) as usize, (self.position.1 + x as isize) as usize));
+                }
+            }
+        }
+        cells
+    }
+}
+
+/// how far a bot got in a game played without a window
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct HeadlessResult {
+    /// the amount of pieces that were locked
+    pub pieces: usize,
+    /// the amount of lines that were cleared
+    pub lines: usize,
+}
+
+/// anything that plays for a player by pressing the same keys a person would
+pub trait Controller {
+    /// Decides what to press next for the block of the player
+    fn update(&mut self, dt: f64, player: &Player) -> Option<InputType>;
+}
+
+/// Finds every spot the block can be locked in, including the ones that need a tuck or spin under an overhang
+/// This tries every input from every position the block can reach, so only moves the game allows are used
+pub fn placements(board: &Board, block: &Block) -> Vec<Placement> {
+    let mut placements = Vec::new();
+    let mut seen = HashSet::from([(block.position, block.matrix().clone())]);
+    let mut locked = HashSet::new();
+    let mut queue = VecDeque::from([(board.clone(), block.clone(), Vec::new())]);
+
+    while let Some((board, block, inputs)) = queue.pop_front() {
+        for input_type in INPUTS {
+            let mut board = board.clone();
+            let mut block = block.clone();
+            block.handle_input(&mut board, input_type);
+
+            let mut inputs = inputs.clone();
+            inputs.push(input_type);
+            let key = (block.position, block.matrix().clone());
+
+            if block.status == BlockStatus::Frozen {
+                // the block could not go down any further so this is where it stays
+                if locked.insert(key.clone()) {
+                    let lines = board.update(&mut 0);
+                    placements.push(Placement { position: key.0, matrix: key.1, inputs, board, lines });
+                }
+            } else if seen.insert(key) {
+                queue.push_back((board, block, inputs));
+            }
+        }
+    }
+
+    placements
+}
+
+/// Returns how many rows the highest filled cell of a column is above the bottom
+fn column_height(board: &Board, x: usize) -> usize {
+    (0..board.height())
+        .find(|&y| board.data[y][x].status != CellStatus::Empty)
+        .map_or(0, |y| board.height() - y)
+}
+
+/// Scores a board after lines were cleared, the higher the better
+pub fn evaluate(board: &Board, lines: usize, weights: &Weights) -> f64 {
+    let heights: Vec<usize> = (0..board.width()).map(|x| column_height(board, x)).collect();
+
+    let holes = board.holes();
+    let bumpiness: usize = heights.windows(2).map(|pair| pair[0].abs_diff(pair[1])).sum();
+    // the walls count as being as high as the board
+    let wells: usize = (0..board.width())
+        .map(|x| {
+            let left = if x == 0 { board.height() } else { heights[x - 1] };
+            let right = heights.get(x + 1).copied().unwrap_or(board.height());
+            left.min(right).saturating_sub(heights[x])
+        })
+        .sum();
+
+    weights.height * heights.iter().sum::<usize>() as f64
+        + weights.lines * lines as f64
+        + weights.holes * holes as f64
+        + weights.bumpiness * bumpiness as f64
+        + weights.wells * wells as f64
+}
+
+/// a computer player that presses the same keys a person would
+pub struct Bot {
+    weights: Weights,
+    /// if the next piece is taken into account, this is a lot slower
+    look_ahead: bool,
+    /// the position and rotation the current block is heading for
+    target: Option<Spot>,
+    waiting_time: f64,
+}
+
+impl Bot {
+    pub fn new(weights: Weights) -> Bot {
+        Bot {
+            weights,
+            look_ahead: true,
+            target: None,
+            waiting_time: 0.0,
+        }
+    }
+
+    /// Creates a bot that only looks at the current piece, it plays worse but decides a lot faster
+    pub fn quick(weights: Weights) -> Bot {
+        Bot { look_ahead: false, ..Bot::new(weights) }
+    }
+
+    /// Picks the best spot for the block, taking into account where the next piece can go after it unless the bot is quick
+    pub fn choose(&self, board: &Board, block: &Block, next: &Piece) -> Option<Placement> {
+        let mut options = placements(board, block);
+        let best = self.best(&options, block, next)?;
+        Some(options.swap_remove(best))
+    }
+
+    /// Returns the index of the option that leaves the best board, once the next piece is placed as well when looking ahead
+    fn best(&self, options: &[Placement], block: &Block, next: &Piece) -> Option<usize> {
+        options.iter()
+            .map(|placement| if self.look_ahead {
+                self.score_with_next(placement, block, next)
+            } else {
+                evaluate(&placement.board, placement.lines, &self.weights)
+            })
+            .enumerate()
+            .max_by(|(_, a), (_, b)| a.total_cmp(b))
+            .map(|(index, _)| index)
+    }
+
+    /// Scores a placement by the best board the next piece can leave behind after it
+    fn score_with_next(&self, placement: &Placement, block: &Block, next: &Piece) -> f64 {
+        let mut board = placement.board.clone();
+        let spawn = player::spawn_position(&board);
+
+        match Block::next(&mut board, spawn, next, block) {
+            Some(next_block) => placements(&board, &next_block).iter()
+                .map(|second| evaluate(&second.board, placement.lines + second.lines, &self.weights))
+                .fold(f64::NEG_INFINITY, f64::max),
+            // the next piece would not fit so this placement loses the game
+            None => f64::NEG_INFINITY,
+        }
+    }
+
+
+    /// Plays a game without a window as fast as possible and returns how far the bot got
+    /// The game ends when the bot tops out or after max_pieces pieces, the same seed gives the same pieces
+    pub fn play_headless(&self, seed: u64, max_pieces: usize) -> HeadlessResult {
+        self.play(Player::seeded(Controls::none(), seed), max_pieces)
+    }
+
+    /// Plays the game of the player without a window as fast as possible, like play_headless, on whatever board and pieces it has
+    pub fn play(&self, mut player: Player, max_pieces: usize) -> HeadlessResult {
+        let mut result = HeadlessResult { pieces: 0, lines: 0 };
+
+        for _ in 0..max_pieces {
+            let Some(placement) = self.choose(player.board(), player.block(), &player.next_piece()) else {
+                break;
+            };
+            for input_type in placement.inputs {
+                player.handle_input(input_type);
+            }
+
+            match player.next_block() {
+                PlayerEvent::Locked { lines, .. } => {
+                    result.pieces += 1;
+                    result.lines += lines;
+                },
+                PlayerEvent::ToppedOut => break,
+            }
+        }
+
+        result
+    }
+}
+
+impl Controller for Bot {
+    /// Decides what to press next for the block of the player, only presses something every MOVE_PERIOD seconds
+    fn update(&mut self, dt: f64, player: &Player) -> Option<InputType> {
+        self.waiting_time += dt;
+
+        if self.waiting_time < MOVE_PERIOD || player.block().status == BlockStatus::Frozen {
+            return None;
+        }
+        self.waiting_time = 0.0;
+
+        let options = placements(player.board(), player.block());
+        // gravity can move the block past the spot it was heading for, then a new spot is chosen
+        let index = match self.target.as_ref().and_then(|target| options.iter().position(|option| option.spot() == *target)) {
+            Some(index) => index,
+            None => self.best(&options, player.block(), &player.next_piece())?,
+        };
+
+        let placement = &options[index];
+        // the last input locks the block, the next block needs a new target
+        self.target = if placement.inputs.len() > 1 { Some(placement.spot()) } else { None };
+        placement.inputs.first().copied()
+    }
+}
+
+#[cfg(test)]
+mod bot_tests {
+    use super::*;
+    use crate::blockshape::BlockShape;
+    use crate::board::{self, Cell, Size};
+
+    /// Puts a block of the given shape on the board at the spawn position
+    fn spawn(board: &mut Board, shape: BlockShape) -> Block {
+        let old_block = Block::new(&mut Board::new(), (0, 0));
+        let spawn = player::spawn_position(board);
+        Block::next(board, spawn, &Piece::from(shape), &old_block).unwrap()
+    }
+
+    fn fill(board: &mut Board, y: usize, x: usize) {
+        board.data[y][x] = Cell{color: [1.0; 4], status: CellStatus::Frozen};
+    }
+
+    #[test]
+    fn test_placements() {
+        let mut board = Board::new();
+        let block = spawn(&mut board, BlockShape::O);
+
+        // the O can go in every column but the last and does not rotate
+        let placements = placements(&board, &block);
+        assert_eq!(placements.len(), board::WIDTH - 1);
+        assert!(placements.iter().all(|placement| placement.inputs.last() == Some(&InputType::Down)));
+    }
+
+    #[test]
+    fn test_narrow_board() {
+        let mut board = Board::with_size(Size { width: 4, height: 8, hidden: 0 });
+        let block = spawn(&mut board, BlockShape::O);
+
+        // the walls are where the board ends, not where a default board would end
+        let placements = placements(&board, &block);
+        assert_eq!(placements.len(), 3);
+        assert!(placements.iter().all(|placement| placement.cells().iter().all(|(y, x)| *y >= 6 && *x < 4)));
+    }
+
+    #[test]
+    fn test_evaluate() {
+        let weights = Weights::default();
+        let flat = Board::new();
+
+        // a filled cell over an empty one is a hole and is worse than the same cell on the floor
+        let mut hole = Board::new();
+        fill(&mut hole, board::HEIGHT - 2, 0);
+        let mut floor = Board::new();
+        fill(&mut floor, board::HEIGHT - 1, 0);
+
+        assert!(evaluate(&flat, 0, &weights) > evaluate(&floor, 0, &weights));
+        assert!(evaluate(&floor, 0, &weights) > evaluate(&hole, 0, &weights));
+    }
+
+    #[test]
+    fn test_play_headless() {
+        let bot = Bot::quick(Weights::default());
+
+        // the same seed gives the same game
+        let result = bot.play_headless(3, 60);
+        assert_eq!(result, bot.play_headless(3, 60));
+        assert_eq!(result.pieces, 60);
+        assert!(result.lines > 0);
+    }
+
+    #[test]
+    fn test_weights_file() {
+        let path = std::env::temp_dir().join(format!("tetris-weights-{}.json", std::process::id()));
+        let weights = Weights { holes: -1.0, ..Weights::default() };
+
+        weights.save(&path).unwrap();
+        assert_eq!(Weights::load(&path).unwrap(), weights);
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_choose_clears_line() {
+        let mut board = Board::new();
+        // the bottom row is only missing the four cells on the left
+        for x in 4..board::WIDTH {
+            fill(&mut board, board::HEIGHT - 1, x);
+        }
+        let block = spawn(&mut board, BlockShape::I);
+
+        let bot = Bot::new(Weights::default());
+        let placement = bot.choose(&board, &block, &Piece::from(BlockShape::O)).unwrap();
+
+        assert_eq!(placement.lines, 1);
+        assert!(placement.board.data[board::HEIGHT - 1].iter().all(|cell| cell.status == CellStatus::Empty));
+    }
+}
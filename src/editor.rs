@@ -0,0 +1,294 @@
+use std::fs::{self, File};
+use std::io::{self, BufReader};
+use std::path::Path;
+
+use piston_window::{color::BLACK, types::Color, Context, G2d, Glyphs, Key};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    blockcolor::BlockColor,
+    blockshape::{BlockShape, SHAPES},
+    board::{self, Board, Cell, CellStatus, Size, GARBAGE_COLOR},
+    fumen::Page,
+    renderer::{self, Renderer, BLOCK_SIZE},
+};
+
+/// the file the editor saves to when no other file is given
+pub const DEFAULT_POSITION_FILE: &str = "position.json";
+/// the colors that can be painted with and the character each one is saved as, the number keys pick them in this order
+/// None is garbage
+const PALETTE: [(char, Option<BlockColor>); 9] = [
+    ('#', None),
+    ('r', Some(BlockColor::Red)),
+    ('b', Some(BlockColor::Blue)),
+    ('g', Some(BlockColor::Green)),
+    ('y', Some(BlockColor::Yellow)),
+    ('p', Some(BlockColor::Purple)),
+    ('o', Some(BlockColor::Orange)),
+    ('c', Some(BlockColor::Cyan)),
+    ('k', Some(BlockColor::Pink)),
+];
+/// the keys that pick a color from the palette
+const PALETTE_KEYS: [Key; 9] = [Key::D1, Key::D2, Key::D3, Key::D4, Key::D5, Key::D6, Key::D7, Key::D8, Key::D9];
+/// where the chosen color is shown in the header
+const SWATCH_LOCATION: (f64, f64) = (200.0, 30.0);
+/// the character an empty cell is saved as
+const EMPTY: char = '.';
+
+/// A board together with the pieces that come next and the piece in hold, everything a practice game starts from
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Position {
+    pub board: Board,
+    /// the first piece is the one that falls first
+    pub queue: Vec<BlockShape>,
+    pub hold: Option<BlockShape>,
+}
+
+/// how a position is written to a file, the board is a line of characters for every row so it can be edited by hand as well
+#[derive(Serialize, Deserialize)]
+struct PositionFile {
+    rows: Vec<String>,
+    queue: Vec<BlockShape>,
+    hold: Option<BlockShape>,
+}
+
+impl Position {
+    /// Reads a position from a json file, like the one the editor writes
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<Position> {
+        let file: PositionFile = serde_json::from_reader(BufReader::new(File::open(path)?))?;
+        Ok(Position { board: board_from_rows(&file.rows), queue: file.queue, hold: file.hold })
+    }
+
+    /// Writes the position to a json file
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let file = PositionFile { rows: board_rows(&self.board), queue: self.queue.clone(), hold: self.hold };
+        fs::write(path, serde_json::to_string_pretty(&file)?)
+    }
+}
+
+/// Reads a board from a line of characters for every row from the top, '#' is garbage, '.' is empty and the
+/// letters r, b, g, y, p, o, c and k are the colors of the pieces
+/// The last row is the bottom of the board, so only the rows that have something in them have to be given
+/// The board is as wide as the longest row and at least as high as the default, so saved boards of any size come back the same
+pub fn board_from_rows(rows: &[String]) -> Board {
+    let width = rows.iter().map(|row| row.chars().count()).max().unwrap_or(board::WIDTH);
+    let size = Size {
+        width: width.clamp(*board::WIDTHS.start(), *board::WIDTHS.end()),
+        height: rows.len().clamp(board::HEIGHT, *board::HEIGHTS.end()),
+        hidden: 0,
+    };
+
+    let mut board = Board::with_size(size);
+    let rows = &rows[rows.len().saturating_sub(size.height)..];
+    for (row, line) in board.data.iter_mut().skip(size.height - rows.len()).zip(rows) {
+        for (cell, character) in row.iter_mut().zip(line.chars()) {
+            if let Some(index) = PALETTE.iter().position(|(saved, _)| *saved == character) {
+                *cell = Cell{color: palette_color(index), status: CellStatus::Frozen};
+            }
+        }
+    }
+    board
+}
+
+/// Writes a board as a line of characters for every row, the way board_from_rows reads it
+pub fn board_rows(board: &Board) -> Vec<String> {
+    board.data.iter()
+        .map(|row| row.iter()
+            .map(|cell| match cell.status {
+                CellStatus::Empty => EMPTY,
+                // colors that are not in the palette are saved as garbage
+                _ => (0..PALETTE.len()).find(|index| palette_color(*index) == cell.color).map_or(PALETTE[0].0, |index| PALETTE[index].0),
+            })
+            .collect())
+        .collect()
+}
+
+impl From<&Page> for Position {
+    /// The board of the page with its piece as the first piece
+    fn from(page: &Page) -> Position {
+        Position {
+            board: page.board.clone(),
+            queue: page.piece.iter().map(|piece| piece.shape).collect(),
+            hold: None,
+        }
+    }
+}
+
+/// A screen where a position is put together by painting cells with the mouse and picking the pieces with the keyboard
+pub struct Editor {
+    pub position: Position,
+    /// the place in the palette of the color that is painted with
+    color: usize,
+    /// where the position is saved to and loaded from
+    path: String,
+    /// the last thing that happened, shown in the header
+    message: String,
+}
+
+impl Editor {
+    /// Creates an editor for the position in the file, an empty board of the given size is used when the file can't be read
+    pub fn new(path: &str, size: Size) -> Editor {
+        let (position, message) = match Position::load(path) {
+            Ok(position) => (position, format!("Loaded {}", path)),
+            Err(_) => (Position { board: Board::with_size(size), ..Position::default() }, String::from("F5 save, F9 load, Enter play")),
+        };
+
+        Editor { position, color: 0, path: String::from(path), message }
+    }
+
+    /// Paints the cell under the mouse with the chosen color or empties it, x and y are in pixels from the top left of the board
+    pub fn paint(&mut self, x: f64, y: f64, erase: bool) {
+        if x < 0.0 || y < 0.0 {
+            return;
+        }
+
+        let (column, row) = ((x / BLOCK_SIZE) as usize, (y / BLOCK_SIZE) as usize + self.position.board.hidden());
+        if let Some(cell) = self.position.board.data.get_mut(row).and_then(|row| row.get_mut(column)) {
+            *cell = if erase {
+                Cell{color: BLACK, status: CellStatus::Empty}
+            } else {
+                Cell{color: palette_color(self.color), status: CellStatus::Frozen}
+            };
+        }
+    }
+
+    /// Handles the keys of the editor, the number keys pick a color, the letters of the pieces add them to the queue,
+    /// Backspace takes the last piece off the queue, H changes the piece in hold, Delete empties the board, F5 saves and F9 loads
+    pub fn input(&mut self, key: &Key) {
+        let shape = match key {
+            Key::I => Some(BlockShape::I),
+            Key::J => Some(BlockShape::J),
+            Key::L => Some(BlockShape::L),
+            Key::O => Some(BlockShape::O),
+            Key::S => Some(BlockShape::S),
+            Key::T => Some(BlockShape::T),
+            Key::Z => Some(BlockShape::Z),
+            _ => None,
+        };
+        if let Some(shape) = shape {
+            self.position.queue.push(shape);
+            return;
+        }
+
+        if let Some(color) = PALETTE_KEYS.iter().position(|palette_key| palette_key == key) {
+            self.color = color;
+            return;
+        }
+
+        match key {
+            Key::Backspace => {
+                self.position.queue.pop();
+            },
+            Key::H => self.position.hold = next_hold(self.position.hold),
+            Key::Delete => self.position.board = Board::with_size(self.position.board.size()),
+            Key::F5 => {
+                self.message = match self.position.save(&self.path) {
+                    Ok(()) => format!("Saved {}", self.path),
+                    Err(error) => format!("Could not save: {}", error),
+                };
+            },
+            Key::F9 => {
+                self.message = match Position::load(&self.path) {
+                    Ok(position) => {
+                        self.position = position;
+                        format!("Loaded {}", self.path)
+                    },
+                    Err(error) => format!("Could not load: {}", error),
+                };
+            },
+            _ => {}
+        }
+    }
+
+    /// Draws the board being edited with the queue, hold and chosen color in the header
+    pub fn draw(&self, renderer: &Renderer, glyphs: &mut Glyphs, context: &Context, g2d: &mut G2d) {
+        renderer.draw_image("header", 0.0, 0.0, context, g2d);
+        self.position.board.draw(0.0, renderer, context, g2d);
+
+        let queue: String = self.position.queue.iter().map(|shape| format!("{:?}", shape)).collect();
+        let hold = self.position.hold.map_or(String::from("-"), |shape| format!("{:?}", shape));
+        renderer.draw_text(&format!("Queue: {}", queue), 10.0, 25.0, glyphs, context, g2d);
+        renderer.draw_text(&format!("Hold: {}", hold), 10.0, 50.0, glyphs, context, g2d);
+        renderer.draw_text(&self.message, 10.0, 75.0, glyphs, context, g2d);
+
+        renderer::draw_rect(palette_color(self.color), SWATCH_LOCATION.0, SWATCH_LOCATION.1, BLOCK_SIZE, BLOCK_SIZE, context, g2d);
+    }
+}
+
+/// Returns the color at the given place in the palette
+fn palette_color(index: usize) -> Color {
+    PALETTE[index].1.map_or(GARBAGE_COLOR, |color| color.to_color())
+}
+
+/// Returns the piece that comes after the given one in hold, after the last shape hold is empty again
+fn next_hold(hold: Option<BlockShape>) -> Option<BlockShape> {
+    match hold {
+        None => Some(SHAPES[0]),
+        Some(shape) => SHAPES.iter().skip_while(|other| **other != shape).nth(1).copied(),
+    }
+}
+
+#[cfg(test)]
+mod editor_tests {
+    use super::*;
+    use crate::player::{Controls, Player};
+
+    #[test]
+    fn test_paint_and_save() {
+        let path = std::env::temp_dir().join("rust_tetris_test_position.json");
+        let mut editor = Editor::new(path.to_str().unwrap(), Size::default());
+        editor.position = Position::default();
+
+        editor.input(&Key::D4);
+        editor.paint(0.0, (board::HEIGHT - 1) as f64 * BLOCK_SIZE, false);
+        editor.paint(BLOCK_SIZE * 1.5, (board::HEIGHT - 1) as f64 * BLOCK_SIZE, false);
+        editor.paint(BLOCK_SIZE * 1.5, (board::HEIGHT - 1) as f64 * BLOCK_SIZE, true);
+        // outside the board nothing happens
+        editor.paint(-1.0, 0.0, false);
+        editor.paint(0.0, board::HEIGHT as f64 * BLOCK_SIZE, false);
+
+        editor.input(&Key::T);
+        editor.input(&Key::I);
+        editor.input(&Key::H);
+        editor.input(&Key::F5);
+
+        let bottom = &editor.position.board.data[board::HEIGHT - 1];
+        assert_eq!(bottom[0], Cell{color: BlockColor::Green.to_color(), status: CellStatus::Frozen});
+        assert_eq!(bottom[1].status, CellStatus::Empty);
+
+        let loaded = Position::load(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(loaded, editor.position);
+        assert_eq!(loaded.queue, vec![BlockShape::T, BlockShape::I]);
+        assert_eq!(loaded.hold, Some(SHAPES[0]));
+    }
+
+    #[test]
+    fn test_practice_position() {
+        let mut position = Position { queue: vec![BlockShape::T, BlockShape::O], hold: Some(BlockShape::I), ..Position::default() };
+        position.board.data[board::HEIGHT - 1][0] = Cell{color: GARBAGE_COLOR, status: CellStatus::Frozen};
+
+        let mut player = Player::practice(Controls::none(), &position.board, &position.queue, position.hold);
+        assert_eq!(player.block().shape(), BlockShape::T);
+        assert_eq!(player.preview().0, BlockShape::O);
+        // the board gets hidden rows on top, the rows under them are the same
+        assert_eq!(player.board().hidden(), board::HIDDEN);
+        assert_eq!(player.board().data[board::HIDDEN + board::HEIGHT - 1][0], position.board.data[board::HEIGHT - 1][0]);
+
+        // holding swaps with the piece in hold, only once per piece
+        player.hold();
+        assert_eq!((player.block().shape(), player.held_piece()), (BlockShape::I, Some(BlockShape::T)));
+        player.hold();
+        assert_eq!((player.block().shape(), player.held_piece()), (BlockShape::I, Some(BlockShape::T)));
+    }
+
+    #[test]
+    fn test_next_hold() {
+        let mut hold = None;
+        for shape in SHAPES {
+            hold = next_hold(hold);
+            assert_eq!(hold, Some(shape));
+        }
+        assert_eq!(next_hold(hold), None);
+    }
+}
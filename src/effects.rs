@@ -0,0 +1,105 @@
+use piston_window::{types::Color, Context, G2d};
+
+use crate::{board::Board, renderer::Renderer};
+
+/// how many seconds a block flashes after it locks
+const LOCK_FLASH_TIME: f64 = 0.15;
+/// the color blocks flash in, it fades out over the flash
+const FLASH_COLOR: Color = [1.0, 1.0, 1.0, 0.7];
+
+/// Short animations drawn over a board, they only show what happened and never change the game
+#[derive(Debug, Clone, Default)]
+pub struct Effects {
+    /// the cells of blocks that just locked and how many more seconds they flash
+    locks: Vec<(Vec<(usize, usize)>, f64)>,
+    /// the rows that are being cleared, how many seconds that takes and how many have gone by
+    clearing: Option<(Vec<usize>, f64, f64)>,
+}
+
+impl Effects {
+    /// Flashes the cells of a block that locked
+    pub fn lock(&mut self, cells: Vec<(usize, usize)>) {
+        self.locks.push((cells, LOCK_FLASH_TIME));
+    }
+
+    /// Flashes and dissolves the rows over the given amount of seconds, until they are cleared
+    pub fn clear(&mut self, rows: Vec<usize>, time: f64) {
+        self.clearing = Some((rows, time, 0.0));
+    }
+
+    /// Stops the rows from being drawn as cleared and moves the flashing cells down with the rest of the board
+    pub fn collapse(&mut self, rows: &[usize]) {
+        self.clearing = None;
+        for (cells, _) in self.locks.iter_mut() {
+            cells.retain(|(y, _)| !rows.contains(y));
+            for (y, _) in cells.iter_mut() {
+                *y += rows.iter().filter(|row| **row > *y).count();
+            }
+        }
+    }
+
+    /// Moves the animations along by the elapsed time
+    pub fn update(&mut self, dt: f64) {
+        for (_, time) in self.locks.iter_mut() {
+            *time -= dt;
+        }
+        self.locks.retain(|(_, time)| *time > 0.0);
+
+        if let Some((_, time, elapsed)) = &mut self.clearing {
+            *elapsed = (*elapsed + dt).min(*time);
+        }
+    }
+
+    /// Draws the animations over the board, offset is the x in pixels where the board starts
+    /// Rows that are being cleared flash and their cells disappear from the middle outwards
+    pub fn draw(&self, board: &Board, offset: f64, renderer: &Renderer, context: &Context, g2d: &mut G2d) {
+        let (width, hidden) = (board.width(), board.hidden());
+        for (cells, time) in &self.locks {
+            let color = faded(time / LOCK_FLASH_TIME);
+            for (y, x) in cells.iter().filter(|(y, _)| *y >= hidden) {
+                renderer.draw_block(color, *x as f64, (y - hidden) as f64, offset, context, g2d);
+            }
+        }
+
+        if let Some((rows, time, elapsed)) = &self.clearing {
+            let progress = if *time > 0.0 { elapsed / time } else { 1.0 };
+            let middle = (width as f64 - 1.0) / 2.0;
+            for y in rows.iter().filter(|y| **y >= hidden) {
+                for x in 0..width {
+                    let gone = (x as f64 - middle).abs() < progress * width as f64 / 2.0;
+                    let color = if gone { renderer.theme().background } else { faded(1.0 - progress) };
+                    renderer.draw_block(color, x as f64, (y - hidden) as f64, offset, context, g2d);
+                }
+            }
+        }
+    }
+}
+
+/// Returns the flash color with the given part of its strength left
+fn faded(strength: f64) -> Color {
+    let [red, green, blue, alpha] = FLASH_COLOR;
+    [red, green, blue, alpha * strength.clamp(0.0, 1.0) as f32]
+}
+
+#[cfg(test)]
+mod effects_tests {
+    use super::*;
+
+    #[test]
+    fn test_collapse() {
+        let mut effects = Effects::default();
+        effects.lock(vec![(17, 4), (18, 4), (18, 5), (19, 4)]);
+        effects.clear(vec![18], 0.3);
+
+        effects.update(0.1);
+        assert_eq!(effects.clearing.as_ref().map(|(_, _, elapsed)| *elapsed), Some(0.1));
+
+        // the cells in the cleared row are gone and the ones above it moved down
+        effects.collapse(&[18]);
+        assert_eq!(effects.locks[0].0, [(18, 4), (19, 4)]);
+        assert!(effects.clearing.is_none());
+
+        effects.update(0.1);
+        assert!(effects.locks.is_empty());
+    }
+}
@@ -0,0 +1,143 @@
+use std::collections::{HashMap, VecDeque};
+
+use crate::{
+    block::{Block, BlockStatus},
+    board::Board,
+    bot::Spot,
+    player::InputType,
+};
+
+/// every input that is tried to reach a spot, true when its key is held until the block stops at a wall
+const MOVES: [(InputType, bool); 6] = [
+    (InputType::Down, false),
+    (InputType::Left, false),
+    (InputType::Right, false),
+    (InputType::Rotate, false),
+    (InputType::Left, true),
+    (InputType::Right, true),
+];
+
+/// Returns true if the input counts towards finesse, moving down is free because gravity does it as well
+pub fn counts(input_type: InputType) -> bool {
+    input_type != InputType::Down
+}
+
+/// Returns the least amount of moves and rotations needed to lock the block in the spot, None if it can't get there
+/// Holding left or right slides the block to the wall for a single press
+/// The board and block should be as they were when the block spawned
+pub fn minimum_inputs(board: &Board, block: &Block, spot: Spot) -> Option<usize> {
+    let mut best = HashMap::from([((block.position, block.matrix().clone()), 0)]);
+    // inputs that are free go to the front so positions come out of the queue with the lowest cost first
+    let mut queue = VecDeque::from([(board.clone(), block.clone(), 0)]);
+
+    while let Some((board, block, cost)) = queue.pop_front() {
+        if best.get(&(block.position, block.matrix().clone())).is_some_and(|&known| known < cost) {
+            continue;
+        }
+
+        for (input_type, held) in MOVES {
+            let mut board = board.clone();
+            let mut block = block.clone();
+            block.handle_input(&mut board, input_type);
+            let mut before = None;
+            while held && before != Some(block.position) {
+                before = Some(block.position);
+                block.handle_input(&mut board, input_type);
+            }
+
+            let cost = if counts(input_type) { cost + 1 } else { cost };
+            let key = (block.position, block.matrix().clone());
+
+            if block.status == BlockStatus::Frozen {
+                if key == spot {
+                    return Some(cost);
+                }
+            } else if best.get(&key).is_none_or(|&known| cost < known) {
+                best.insert(key, cost);
+                if counts(input_type) {
+                    queue.push_back((board, block, cost));
+                } else {
+                    queue.push_front((board, block, cost));
+                }
+            }
+        }
+    }
+
+    None
+}
+
+#[cfg(test)]
+mod finesse_tests {
+    use super::*;
+    use crate::{
+        blockshape::BlockShape,
+        board,
+        pieces::Piece,
+        player::{self, Controls, Player, PlayerEvent},
+    };
+
+    /// Returns where the block locks after the inputs and then dropping it all the way
+    fn drop_after(board: &Board, block: &Block, inputs: &[InputType]) -> Spot {
+        let mut board = board.clone();
+        let mut block = block.clone();
+        for input_type in inputs {
+            block.handle_input(&mut board, *input_type);
+        }
+        while block.status != BlockStatus::Frozen {
+            block.move_down(&mut board);
+        }
+        (block.position, block.matrix().clone())
+    }
+
+    #[test]
+    fn test_minimum_inputs() {
+        let mut board = Board::new();
+        let old_block = Block::new(&mut Board::new(), (0, 0));
+        let spawn = player::spawn_position(&board);
+        let block = Block::next(&mut board, spawn, &Piece::from(BlockShape::T), &old_block).unwrap();
+
+        // dropping straight down costs nothing
+        assert_eq!(minimum_inputs(&board, &block, drop_after(&board, &block, &[])), Some(0));
+
+        // going left and right again ends up in the same spot
+        let wasteful = drop_after(&board, &block, &[InputType::Left, InputType::Right]);
+        assert_eq!(minimum_inputs(&board, &block, wasteful), Some(0));
+
+        // the T spawns in the middle, holding left or right takes it to the wall with one press
+        let wall = drop_after(&board, &block, &[InputType::Left; board::WIDTH]);
+        assert_eq!(minimum_inputs(&board, &block, wall), Some(1));
+        let wall = drop_after(&board, &block, &[InputType::Right; board::WIDTH]);
+        assert_eq!(minimum_inputs(&board, &block, wall), Some(1));
+
+        // next to the far wall it is quicker to hold to the wall and tap back than to tap all the way
+        let mut inputs = vec![InputType::Right; board::WIDTH];
+        inputs.push(InputType::Left);
+        let next_to_wall = drop_after(&board, &block, &inputs);
+        assert_eq!(minimum_inputs(&board, &block, next_to_wall), Some(2));
+    }
+
+    #[test]
+    fn test_fault() {
+        let mut player = Player::new(Controls::none());
+
+        // moving away and back again is two inputs more than needed
+        player.handle_input(InputType::Left);
+        player.handle_input(InputType::Right);
+        while player.block().status != BlockStatus::Frozen {
+            player.handle_input(InputType::Down);
+        }
+
+        assert!(matches!(player.next_block(), PlayerEvent::Locked { fault: true, .. }));
+        assert_eq!(player.faults, 1);
+
+        // tapping all the way to the wall is a fault when holding the key gets there with one press
+        for _ in 0..board::WIDTH / 2 {
+            player.handle_input(InputType::Left);
+        }
+        while player.block().status != BlockStatus::Frozen {
+            player.handle_input(InputType::Down);
+        }
+        assert!(matches!(player.next_block(), PlayerEvent::Locked { fault: true, .. }));
+        assert_eq!(player.faults, 2);
+    }
+}
@@ -0,0 +1,557 @@
+use std::fmt;
+
+use piston_window::types::Color;
+
+use crate::{
+    block::Block,
+    blockcolor::BlockColor,
+    blockshape::BlockShape,
+    board::{self, Board, Cell, CellStatus, GARBAGE_COLOR},
+    tbp::{Orientation, PieceLocation},
+};
+
+/// every code starts with this, only version 115 is supported
+const PREFIX: &str = "v115@";
+/// the characters fumen writes the numbers 0 to 63 with
+const TABLE: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+/// the characters a comment is written with, a character is its place in this table
+const COMMENT_TABLE: &[u8] = b" !\"#$%&'()*+,-./0123456789:;<=>?@ABCDEFGHIJKLMNOPQRSTUVWXYZ[\\]^_`abcdefghijklmnopqrstuvwxyz{|}~";
+/// the amount of values a comment character can have
+const COMMENT_BASE: u32 = 96;
+/// comments can't be longer than this after escaping
+const MAX_COMMENT_LENGTH: usize = 4095;
+/// the amount of rows of a field, the garbage row under it is not counted
+const FIELD_TOP: usize = 23;
+/// fields are always this wide
+const FIELD_WIDTH: usize = 10;
+/// the amount of cells of a field including the garbage row
+const FIELD_CELLS: usize = (FIELD_TOP + 1) * FIELD_WIDTH;
+/// a filled cell that does not belong to a piece
+const GRAY: u8 = 8;
+/// the number fumen gives every piece and the color its cells get on our board
+const PIECES: [(u8, BlockShape, BlockColor); 7] = [
+    (1, BlockShape::I, BlockColor::Cyan),
+    (2, BlockShape::L, BlockColor::Orange),
+    (3, BlockShape::O, BlockColor::Yellow),
+    (4, BlockShape::Z, BlockColor::Red),
+    (5, BlockShape::T, BlockColor::Purple),
+    (6, BlockShape::J, BlockColor::Blue),
+    (7, BlockShape::S, BlockColor::Green),
+];
+
+/// the cells of a field row by row from the top, the last row is the garbage row under the field
+type Field = [u8; FIELD_CELLS];
+
+/// Reasons a fumen code can't be read
+#[derive(Debug, PartialEq)]
+pub enum FumenError {
+    /// the code is not a version 115 code
+    Version,
+    /// the code has a character fumen does not use
+    Character(char),
+    /// the code stops in the middle of a page
+    Truncated,
+    /// the field has filled cells above the top of our board
+    TooTall,
+}
+
+impl fmt::Display for FumenError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            FumenError::Version => write!(f, "only v115 fumen codes are supported"),
+            FumenError::Character(character) => write!(f, "'{}' does not belong in a fumen code", character),
+            FumenError::Truncated => write!(f, "the fumen code is cut off"),
+            FumenError::TooTall => write!(f, "the field is taller than the board"),
+        }
+    }
+}
+
+impl std::error::Error for FumenError {}
+
+/// One page of a fumen, a board with the piece that is about to be placed on it
+#[derive(Debug, Clone, PartialEq)]
+pub struct Page {
+    /// only has frozen cells, the piece is kept apart
+    pub board: Board,
+    pub piece: Option<PieceLocation>,
+    pub comment: Option<String>,
+}
+
+impl Page {
+    /// Creates a page from a board and the block that is falling on it
+    pub fn new(board: &Board, block: Option<&Block>) -> Page {
+        Page {
+            board: board.frozen(),
+            piece: block.and_then(|block| PieceLocation::from_block(block, board)),
+            comment: None,
+        }
+    }
+}
+
+/// reads the numbers a code is made of
+struct Reader {
+    values: Vec<u32>,
+    position: usize,
+}
+
+impl Reader {
+    fn new(data: &str) -> Result<Reader, FumenError> {
+        let values = data.chars()
+            // question marks only break the code up so it can be pasted in more places
+            .filter(|character| *character != '?')
+            .map(|character| TABLE.iter()
+                .position(|value| *value as char == character)
+                .map(|value| value as u32)
+                .ok_or(FumenError::Character(character)))
+            .collect::<Result<_, _>>()?;
+
+        Ok(Reader { values, position: 0 })
+    }
+
+    fn is_empty(&self) -> bool {
+        self.position >= self.values.len()
+    }
+
+    /// Reads a number that is written with the given amount of characters, the lowest part comes first
+    fn poll(&mut self, count: usize) -> Result<u32, FumenError> {
+        let values = self.values.get(self.position..self.position + count).ok_or(FumenError::Truncated)?;
+        self.position += count;
+        Ok(values.iter().rev().fold(0, |number, value| number * 64 + value))
+    }
+}
+
+/// Writes a number with the given amount of characters, the lowest part comes first
+fn push(values: &mut Vec<u32>, mut number: u32, count: usize) {
+    for _ in 0..count {
+        values.push(number % 64);
+        number /= 64;
+    }
+}
+
+/// The orientation of a piece from the number fumen uses for it
+fn orientation_from(number: u32) -> Orientation {
+    match number {
+        0 => Orientation::South,
+        1 => Orientation::East,
+        2 => Orientation::North,
+        _ => Orientation::West,
+    }
+}
+
+/// The number fumen uses for an orientation
+fn orientation_number(orientation: Orientation) -> u32 {
+    match orientation {
+        Orientation::South => 0,
+        Orientation::East => 1,
+        Orientation::North => 2,
+        Orientation::West => 3,
+    }
+}
+
+/// fumen does not always use the same center for a piece as other programs, this is how far it is off
+fn center_offset(shape: BlockShape, orientation: Orientation) -> (i32, i32) {
+    match (shape, orientation) {
+        (BlockShape::O, Orientation::West) => (1, -1),
+        (BlockShape::O, Orientation::South) => (1, 0),
+        (BlockShape::O, Orientation::North) => (0, -1),
+        (BlockShape::I, Orientation::South) => (1, 0),
+        (BlockShape::I, Orientation::West) => (0, -1),
+        (BlockShape::S, Orientation::North) => (0, -1),
+        (BlockShape::S, Orientation::East) => (-1, 0),
+        (BlockShape::Z, Orientation::North) => (0, -1),
+        (BlockShape::Z, Orientation::West) => (1, 0),
+        _ => (0, 0),
+    }
+}
+
+/// Returns the index of the cell in a field, y goes up from the bottom row and -1 is the garbage row
+fn field_index(x: i32, y: i32) -> Option<usize> {
+    let row = FIELD_TOP as i32 - 1 - y;
+    (0..FIELD_WIDTH as i32).contains(&x)
+        .then(|| (0..=FIELD_TOP as i32).contains(&row).then_some(row as usize * FIELD_WIDTH + x as usize))
+        .flatten()
+}
+
+/// Returns the color fumen shows the piece in
+pub fn color(shape: BlockShape) -> Color {
+    PIECES.iter()
+        .find(|(_, piece, _)| *piece == shape)
+        .map_or(GARBAGE_COLOR, |(_, _, color)| color.to_color())
+}
+
+/// Turns a field into a board of the default size, the garbage row is left out
+fn to_board(field: &Field) -> Result<Board, FumenError> {
+    let mut board = Board::new();
+    // the board sits at the bottom of the field
+    let top = FIELD_TOP - board::HEIGHT;
+    if field[..top * FIELD_WIDTH].iter().any(|value| *value != 0) {
+        return Err(FumenError::TooTall);
+    }
+
+    for (y, row) in board.data.iter_mut().enumerate() {
+        for (x, cell) in row.iter_mut().enumerate() {
+            let value = field[(top + y) * FIELD_WIDTH + x];
+            if value != 0 {
+                let color = PIECES.iter()
+                    .find(|(number, _, _)| *number == value)
+                    .map_or(GARBAGE_COLOR, |(_, _, color)| color.to_color());
+                *cell = Cell{color, status: CellStatus::Frozen};
+            }
+        }
+    }
+    Ok(board)
+}
+
+/// Turns the frozen cells of a board into a field, cells are the piece that has their color or gray
+/// Cells of wider or higher boards that are outside of the field are left out
+fn to_field(board: &Board) -> Field {
+    let mut field = [0; FIELD_CELLS];
+    for (y, row) in board.data.iter().enumerate() {
+        // the board sits at the bottom of the field
+        let Some(field_row) = (FIELD_TOP + y).checked_sub(board.height()) else {
+            continue;
+        };
+        for (x, cell) in row.iter().enumerate().take(FIELD_WIDTH) {
+            if cell.status == CellStatus::Frozen {
+                field[field_row * FIELD_WIDTH + x] = PIECES.iter()
+                    .find(|(_, _, color)| color.to_color() == cell.color)
+                    .map_or(GRAY, |(number, _, _)| *number);
+            }
+        }
+    }
+    field
+}
+
+/// Puts the piece in the field, clears full rows and moves the field up or mirrors it when the page asks for it
+fn lock(field: &mut Field, piece: Option<PieceLocation>, rise: bool, mirror: bool) {
+    if let Some(piece) = piece {
+        let number = PIECES.iter().find(|(_, shape, _)| *shape == piece.shape).map_or(GRAY, |(number, _, _)| *number);
+        for (x, y) in piece.positions() {
+            if let Some(index) = field_index(x, y) {
+                field[index] = number;
+            }
+        }
+    }
+
+    let (rows, garbage) = field.split_at_mut(FIELD_TOP * FIELD_WIDTH);
+    let kept: Vec<u8> = rows.chunks(FIELD_WIDTH)
+        .filter(|row| row.contains(&0))
+        .flatten()
+        .copied()
+        .collect();
+    rows.fill(0);
+    rows[FIELD_TOP * FIELD_WIDTH - kept.len()..].copy_from_slice(&kept);
+
+    if rise {
+        rows.copy_within(FIELD_WIDTH.., 0);
+        rows[(FIELD_TOP - 1) * FIELD_WIDTH..].copy_from_slice(garbage);
+        garbage.fill(0);
+    }
+    if mirror {
+        rows.chunks_mut(FIELD_WIDTH).for_each(|row| row.reverse());
+    }
+}
+
+/// Reads how the field changed since the last page, returns false if nothing changed
+fn read_field(reader: &mut Reader, field: &mut Field) -> Result<bool, FumenError> {
+    let mut changed = true;
+    let mut index = 0;
+
+    // the changes are stored as runs of cells that changed by the same amount
+    while index < FIELD_CELLS {
+        let run = reader.poll(2)?;
+        let difference = run / FIELD_CELLS as u32;
+        let length = (run % FIELD_CELLS as u32) as usize + 1;
+        if difference == 8 && length == FIELD_CELLS {
+            changed = false;
+        }
+
+        for cell in field.iter_mut().skip(index).take(length) {
+            *cell = (*cell as u32 + difference).saturating_sub(8) as u8;
+        }
+        index += length;
+    }
+
+    Ok(changed)
+}
+
+/// Writes how the field changed since the last page, returns false if nothing changed
+fn write_field(values: &mut Vec<u32>, previous: &Field, field: &Field) -> bool {
+    let mut runs: Vec<(u32, usize)> = Vec::new();
+    for (cell, previous) in field.iter().zip(previous) {
+        let difference = *cell as u32 + 8 - *previous as u32;
+        match runs.last_mut() {
+            Some((last, length)) if *last == difference => *length += 1,
+            _ => runs.push((difference, 1)),
+        }
+    }
+
+    for (difference, length) in &runs {
+        push(values, difference * FIELD_CELLS as u32 + *length as u32 - 1, 2);
+    }
+    runs != [(8, FIELD_CELLS)]
+}
+
+/// Turns a comment into the characters it is stored with, the way javascript's escape does it
+fn escape(text: &str) -> String {
+    let mut escaped = String::new();
+    for unit in text.encode_utf16() {
+        match char::from_u32(unit as u32) {
+            Some(character) if character.is_ascii_alphanumeric() || "@*_+-./".contains(character) => escaped.push(character),
+            _ if unit < 256 => escaped += &format!("%{:02X}", unit),
+            _ => escaped += &format!("%u{:04X}", unit),
+        }
+    }
+    escaped
+}
+
+/// Turns the stored characters of a comment back into text
+fn unescape(text: &str) -> String {
+    let mut units = Vec::new();
+    let mut rest = text;
+
+    while let Some(character) = rest.chars().next() {
+        let code = if let Some(hex) = rest.strip_prefix("%u").and_then(|hex| hex.get(..4)) {
+            u16::from_str_radix(hex, 16).ok().map(|unit| (unit, 6))
+        } else if let Some(hex) = rest.strip_prefix('%').and_then(|hex| hex.get(..2)) {
+            u16::from_str_radix(hex, 16).ok().map(|unit| (unit, 3))
+        } else {
+            None
+        };
+
+        match code {
+            Some((unit, length)) => {
+                units.push(unit);
+                rest = &rest[length..];
+            },
+            None => {
+                let mut buffer = [0; 2];
+                units.extend_from_slice(character.encode_utf16(&mut buffer));
+                rest = &rest[character.len_utf8()..];
+            },
+        }
+    }
+
+    String::from_utf16_lossy(&units)
+}
+
+fn read_comment(reader: &mut Reader) -> Result<String, FumenError> {
+    let length = reader.poll(2)? as usize;
+    let mut escaped = String::new();
+
+    // every 5 characters of the code hold 4 characters of the comment
+    for _ in 0..length.div_ceil(4) {
+        let mut value = reader.poll(5)?;
+        for _ in 0..4 {
+            escaped.push(COMMENT_TABLE.get((value % COMMENT_BASE) as usize).map_or(' ', |character| *character as char));
+            value /= COMMENT_BASE;
+        }
+    }
+
+    escaped.truncate(length);
+    Ok(unescape(&escaped))
+}
+
+fn write_comment(values: &mut Vec<u32>, comment: &str) {
+    let escaped: Vec<u32> = escape(comment).bytes()
+        .take(MAX_COMMENT_LENGTH)
+        .map(|byte| COMMENT_TABLE.iter().position(|character| *character == byte).unwrap_or(0) as u32)
+        .collect();
+
+    push(values, escaped.len() as u32, 2);
+    for chunk in escaped.chunks(4) {
+        let value = chunk.iter().rev().fold(0, |value, character| value * COMMENT_BASE + character);
+        push(values, value, 5);
+    }
+}
+
+/// Reads every page of a fumen code, a link to fumen with the code in it works as well
+pub fn decode(code: &str) -> Result<Vec<Page>, FumenError> {
+    let (_, data) = code.split_once(PREFIX).ok_or(FumenError::Version)?;
+    let mut reader = Reader::new(data.trim())?;
+
+    let mut pages = Vec::new();
+    let mut field = [0; FIELD_CELLS];
+    // the amount of pages that follow with a field that did not change
+    let mut repeat = 0;
+    // a page without a comment of its own keeps the comment of the page before it
+    let mut last_comment = String::new();
+
+    while !reader.is_empty() {
+        if repeat > 0 {
+            repeat -= 1;
+        } else if !read_field(&mut reader, &mut field)? {
+            repeat = reader.poll(1)?;
+        }
+
+        // the piece, its place and a few flags are all stored in one number
+        let action = reader.poll(3)?;
+        let number = (action % 8) as u8;
+        let orientation = orientation_from(action / 8 % 4);
+        let coordinate = (action / 32 % FIELD_CELLS as u32) as i32;
+        let flags = action / 32 / FIELD_CELLS as u32;
+        let (rise, mirror, has_comment, locks) = (flags & 1 != 0, flags & 2 != 0, flags & 8 != 0, flags & 16 == 0);
+
+        if has_comment {
+            last_comment = read_comment(&mut reader)?;
+        }
+        let comment = Some(last_comment.clone()).filter(|comment| !comment.is_empty());
+
+        let piece = PIECES.iter().find(|(piece, _, _)| *piece == number).map(|(_, shape, _)| {
+            let (x_offset, y_offset) = center_offset(*shape, orientation);
+            PieceLocation {
+                shape: *shape,
+                orientation,
+                x: coordinate % FIELD_WIDTH as i32 + x_offset,
+                y: FIELD_TOP as i32 - coordinate / FIELD_WIDTH as i32 - 1 + y_offset,
+            }
+        });
+
+        pages.push(Page { board: to_board(&field)?, piece, comment });
+
+        if locks {
+            lock(&mut field, piece, rise, mirror);
+        }
+    }
+
+    Ok(pages)
+}
+
+/// Writes the pages as a fumen code, every piece is locked before the next page
+pub fn encode(pages: &[Page]) -> String {
+    let mut values = Vec::new();
+    let mut field = [0; FIELD_CELLS];
+    // where the amount of pages that repeat the field is stored, so the next page that repeats can add to it
+    let mut repeat_index: Option<usize> = None;
+    let mut last_comment = "";
+
+    for (index, page) in pages.iter().enumerate() {
+        let current = to_field(&page.board);
+        let mut changes = Vec::new();
+        let changed = write_field(&mut changes, &field, &current);
+
+        match repeat_index {
+            Some(repeat) if !changed && values[repeat] < 63 => values[repeat] += 1,
+            _ if !changed => {
+                values.extend(changes);
+                values.push(0);
+                repeat_index = Some(values.len() - 1);
+            },
+            _ => {
+                values.extend(changes);
+                repeat_index = None;
+            },
+        }
+        field = current;
+
+        let (number, rotation, coordinate) = match page.piece {
+            Some(piece) => {
+                let (x_offset, y_offset) = center_offset(piece.shape, piece.orientation);
+                let number = PIECES.iter().find(|(_, shape, _)| *shape == piece.shape).map_or(0, |(number, _, _)| *number);
+                let coordinate = (FIELD_TOP as i32 - (piece.y - y_offset) - 1) * FIELD_WIDTH as i32 + piece.x - x_offset;
+                (number as u32, orientation_number(piece.orientation), coordinate.clamp(0, FIELD_CELLS as i32 - 1) as u32)
+            },
+            None => (0, 0, 0),
+        };
+
+        // the first page turns on colors, a comment is only written when it changed and an empty one clears it
+        let text = page.comment.as_deref().unwrap_or("");
+        let comment = Some(text).filter(|text| *text != last_comment);
+        let flags = if index == 0 { 4 } else { 0 } + if comment.is_some() { 8 } else { 0 };
+        push(&mut values, ((flags * FIELD_CELLS as u32 + coordinate) * 4 + rotation) * 8 + number, 3);
+
+        if let Some(comment) = comment {
+            write_comment(&mut values, comment);
+            last_comment = comment;
+        }
+
+        lock(&mut field, page.piece, false, false);
+    }
+
+    let data: String = values.iter().map(|value| TABLE[*value as usize] as char).collect();
+    // fumen puts a question mark after the first 42 characters and then after every 47
+    let mut code = String::from(PREFIX);
+    for (index, character) in data.chars().enumerate() {
+        if index >= 42 && (index - 42) % 47 == 0 {
+            code.push('?');
+        }
+        code.push(character);
+    }
+    code
+}
+
+#[cfg(test)]
+mod fumen_tests {
+    use super::*;
+
+    fn page(board: Board, piece: Option<PieceLocation>) -> Page {
+        Page { board, piece, comment: None }
+    }
+
+    #[test]
+    fn test_empty() {
+        let pages = vec![page(Board::new(), None)];
+
+        assert_eq!(encode(&pages), "v115@vhAAgH");
+        assert_eq!(decode("v115@vhAAgH").unwrap(), pages);
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let mut board = Board::new();
+        for x in 0..board::WIDTH - 1 {
+            board.data[board::HEIGHT - 1][x] = Cell{color: GARBAGE_COLOR, status: CellStatus::Frozen};
+        }
+        board.data[board::HEIGHT - 2][0] = Cell{color: BlockColor::Blue.to_color(), status: CellStatus::Frozen};
+
+        // a standing I that fills the hole and clears the bottom row
+        let i = PieceLocation { shape: BlockShape::I, orientation: Orientation::West, x: 9, y: 1 };
+        let mut pages = vec![page(board.clone(), Some(i))];
+
+        // locking the I clears a line, so the next board is the blue cell on the bottom with the rest of the I next to it
+        let mut next = Board::new();
+        next.data[board::HEIGHT - 1][0] = Cell{color: BlockColor::Blue.to_color(), status: CellStatus::Frozen};
+        for y in board::HEIGHT - 3..board::HEIGHT {
+            next.data[y][9] = Cell{color: BlockColor::Cyan.to_color(), status: CellStatus::Frozen};
+        }
+
+        // pieces in every orientation with boards that did not change and comments
+        for (index, orientation) in [Orientation::North, Orientation::East, Orientation::South, Orientation::West].iter().enumerate() {
+            let o = PieceLocation { shape: BlockShape::O, orientation: *orientation, x: 4, y: 10 };
+            let mut page = page(next.clone(), Some(o));
+            page.comment = Some(format!("page {} with a comment, 100% ✓", index + 2));
+            pages.push(page);
+            lock_on(&mut next, o);
+        }
+        pages.push(page(next, None));
+
+        let code = encode(&pages);
+        assert!(code.contains('?'));
+        assert_eq!(decode(&code).unwrap(), pages);
+    }
+
+    #[test]
+    fn test_repeated_comments() {
+        let comments = [Some("same"), Some("same"), None, Some("other"), Some("other"), None];
+        let pages: Vec<Page> = comments.iter()
+            .map(|comment| Page { comment: comment.map(String::from), ..page(Board::new(), None) })
+            .collect();
+
+        // the comment is only written on the first of the pages that have it, the next one carries it over
+        assert_eq!(decode(&encode(&pages)).unwrap(), pages);
+    }
+
+    /// Puts a piece on the board the same way a page is locked
+    fn lock_on(board: &mut Board, piece: PieceLocation) {
+        for (y, x) in piece.cells(board).unwrap() {
+            board.data[y][x] = Cell{color: BlockColor::Yellow.to_color(), status: CellStatus::Frozen};
+        }
+    }
+
+    #[test]
+    fn test_errors() {
+        assert_eq!(decode("v110@vhAAgH"), Err(FumenError::Version));
+        assert_eq!(decode("v115@vh"), Err(FumenError::Truncated));
+        assert_eq!(decode("v115@v!AAgH"), Err(FumenError::Character('!')));
+    }
+}
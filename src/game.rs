@@ -1,137 +1,1005 @@
-use std::path::Path;
+use std::io;
+use std::net::ToSocketAddrs;
+use std::path::{Path, PathBuf};
 use crate::{
-    block::{Block, BlockStatus}, 
-    board::{self, Board},
-    gamestate::{GameStatus, GameEvent}, 
-    renderer::{self, Renderer, BORDER}, audio::{Audio, SoundEffect}};
+    blockcolor::ColorPolicy,
+    board::{self, Board, Size},
+    bot::{Bot, Controller, Weights},
+    editor::{Editor, Position, DEFAULT_POSITION_FILE},
+    fumen::{self, Page},
+    gamestate::{GameStatus, GameEvent, GameMode},
+    highscores::{HighScore, HighScores, HIGH_SCORES_FILE},
+    menu::{self, Action, Item, Menu, MenuInput, Screen, Setting},
+    puzzle::{Outcome, Puzzles},
+    solver::{self, Step},
+    net::Network,
+    online::{Online, OnlineEvent},
+    particles::Particles,
+    pieces::{PieceSet, RotationSystem},
+    player::{Controls, Delays, Handling, InputType, Player, PlayerEvent, Stats, TopOut},
+    queue::Randomizer,
+    replay::{Frame, Input, Recorder, Replay},
+    settings::{Settings, SETTINGS_FILE},
+    spectator::{SpectatorServer, StreamEvent},
+    theme::Theme,
+    accessibility::{Accessibility, ColorVision},
+    renderer::{self, Renderer, Viewport, BORDER, BLOCK_SIZE}, audio::{Audio, SoundEffect, Volumes}};
 use piston_window::types::Color;
 use piston_window::*;
+use sdl2_window::Sdl2Window;
 extern crate piston_window;
 
-/// The color used for the background of the game
-const BACK_COLOR: Color = [0.2, 0.5, 0.5, 1.0];
-/// The color that is shown when you are game over
-const GAME_OVER_COLOR: Color = [0.8, 0.0, 0.0, 0.8];
-/// How many seconds it takes before the piece falls one row down
-const MOVING_PERIOD: f64 = 0.5;
-/// the width of the window 
+/// the color of the bars around the game when the window has another shape
+const LETTERBOX_COLOR: Color = [0.0, 0.0, 0.0, 1.0];
+/// The color of the meter that shows how much garbage is about to come in
+const GARBAGE_METER_COLOR: Color = [0.8, 0.0, 0.0, 1.0];
+/// the least room a board gets on screen, smaller boards get this much so the header and the menus still fit
 pub const SCREEN_WIDTH: f64 = (board::WIDTH as f64) * renderer::BLOCK_SIZE;
-/// the height of the window 
+/// the least room a board gets on screen under the header
 pub const SCREEN_HEIGHT: f64 = (board::HEIGHT as f64) * renderer::BLOCK_SIZE;
-/// y, x where a block will start at when the game is loaded or a new block is created
-const BLOCK_SPAWN_POSITION: (isize, isize) = (0, (board::WIDTH as isize / 2) - 1);
-/// the width that images should be in to be used as menu items 
+/// the space between the two boards in versus mode, the garbage meters are drawn in here
+const VERSUS_GAP: f64 = 40.0;
+/// the width of a garbage meter
+const GARBAGE_METER_WIDTH: f64 = 10.0;
+/// the width that images should be in to be used as menu items
 const MENU_IMAGE_WIDTH: f64 = 200.0;
-/// the height that images should be in to be used as menu items 
+/// the height that images should be in to be used as menu items
 const MENU_IMAGE_HEIGHT: f64 = 80.0;
-/// where menu images will be placed X - this is used to offset it so it's neatly centered
-const IMAGE_LOCATION_X: f64 = (SCREEN_WIDTH - MENU_IMAGE_WIDTH) / 2.0;
-/// where menu images will be placed Y - this is used to offset it so it's neatly centered
-const IMAGE_LOCATION_Y: f64 = (SCREEN_HEIGHT - MENU_IMAGE_HEIGHT) / 2.0;
-
-enum InputType {
-    Left,
-    Right,
-    Down,
-    Rotate,
+/// the space between the lines of text over the board
+const STARTUP_LINE_HEIGHT: f64 = 30.0;
+/// where the menu is drawn, relative to the start of the first board
+const MENU_LOCATION: (f64, f64) = (15.0, BORDER + 40.0);
+/// how much a delay changes with every step in the menu, in seconds
+const DELAY_STEP: f64 = 0.05;
+/// how much the auto shift delay and repeat rate change with every step in the menu, in seconds
+const HANDLING_STEP: f64 = 0.01;
+/// the longest delay that can be picked in the menu, in seconds
+const MAX_DELAY: f64 = 1.0;
+/// the fastest soft drop that can be picked in the menu
+const MAX_SOFT_DROP: f64 = 40.0;
+/// how much the volume changes with every step in the menu
+const VOLUME_STEP: f32 = 0.1;
+/// where the page of a practice fumen is shown, relative to the start of the board
+const PAGE_LOCATION: (f64, f64) = (10.0, 100.0);
+/// the most lines a perfect clear hint may use, more than this takes too long to search while playing
+const HINT_LINES: usize = 4;
+/// the color the cells of the hint are drawn over the board in
+const HINT_COLOR: Color = [1.0, 1.0, 1.0, 0.4];
+
+/// What stays the same from one game to the next, restarting only rebuilds the rest of the game
+struct Config {
+    /// how the built-in bot judges boards
+    weights: Weights,
+    /// how big the boards of new games are
+    size: Size,
+    /// what makes the players lose
+    top_out: TopOut,
+    /// how long the players wait to clear lines and spawn blocks
+    delays: Delays,
+    /// how the blocks move while their keys are held
+    handling: Handling,
+    /// how the random pieces are picked
+    randomizer: Randomizer,
+    /// what the blocks try when they can't rotate where they are
+    rotation: RotationSystem,
+    /// the keys of a player that has the keyboard to themselves
+    controls: Controls,
+    /// where the pieces come from, the same seed always gives the same pieces
+    seed: Option<u64>,
+    /// the level new games start on
+    level: usize,
+    /// where the settings are read from and written to
+    settings_file: PathBuf,
+    /// the pieces new games are played with
+    pieces: PieceSet,
+    /// how the blocks of every player get their colors
+    colors: ColorPolicy,
+    /// how the game looks, the window picks up a new theme on the next frame
+    theme: Theme,
+    /// the palette and patterns for players who can't tell every block color apart
+    accessibility: Accessibility,
+    /// whether the window fills the screen, F11 switches it
+    fullscreen: bool,
 }
 
 /// struct holding all the game data
 pub struct Game {
-    board: Board,
-    block: Block,
+    players: Vec<Player>,
     audio: Audio,
-    waiting_time: f64,
-    score: u16,
-    status: GameStatus
+    mode: GameMode,
+    status: GameStatus,
+    /// the player that topped out, only set when the game is over
+    loser: Option<usize>,
+    /// the other players when playing over the network
+    online: Option<Online>,
+    /// streams the game to anyone who wants to watch
+    spectator: Option<SpectatorServer>,
+    /// the players that are controlled by a bot and the bot that controls them
+    bots: Vec<(usize, Box<dyn Controller>)>,
+    /// what new games are played with, it lives through restarts
+    config: Config,
+    /// keeps the game of a single player to play it again later
+    recorder: Option<Recorder>,
+    /// the updates of a replay that are left to play, the keyboard does not move the blocks while it plays
+    playback: Option<std::vec::IntoIter<Frame>>,
+    /// the bursts, streaks and shaking made from what happens to the players
+    particles: Particles,
+    /// the positions that can be practiced on and the one that is being played
+    pages: Vec<Position>,
+    page: usize,
+    /// where the next piece goes for a perfect clear, Some(None) when there is none
+    hint: Option<Option<Step>>,
+    /// the pack of puzzles that is being played
+    puzzles: Option<Puzzles>,
+    /// kept open because on some systems what was copied is gone as soon as the clipboard is closed
+    clipboard: Option<arboard::Clipboard>,
+    /// the position that is being put together, only while editing
+    editor: Option<Editor>,
+    /// where the mouse is in the window
+    cursor: [f64; 2],
+    /// Some while a mouse button is held down in the editor, true when it erases instead of paints
+    painting: Option<bool>,
+    /// the screens of the menu that are open, shown on the startup screen and while paused
+    menu: Menu,
+    /// the best single player games
+    high_scores: HighScores,
+    /// the group of controls the next key is bound to, while the menu waits for it
+    binding: Option<usize>,
+    /// true when the window should close
+    exit: bool,
+    /// the keys that are down, the keyboard repeats the presses of keys that are held and a held key only hard drops once
+    held: Vec<Key>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            weights: Weights::default(),
+            size: Size::default(),
+            top_out: TopOut::default(),
+            delays: Delays::default(),
+            handling: Handling::default(),
+            randomizer: Randomizer::default(),
+            rotation: RotationSystem::default(),
+            controls: Controls::single(),
+            seed: None,
+            level: 1,
+            settings_file: PathBuf::from(SETTINGS_FILE),
+            pieces: PieceSet::standard(),
+            colors: ColorPolicy::default(),
+            theme: Theme::default(),
+            accessibility: Accessibility::default(),
+            fullscreen: false,
+        }
+    }
+}
+
+impl Default for Game {
+    fn default() -> Self {
+        Game::new(Settings::default())
+    }
 }
 
 impl Game {
-    /// Creates a new instance of the game
-    pub fn new() -> Game {
-        let mut board = board::Board::new();
-        let block = Block::new(&mut board, BLOCK_SPAWN_POSITION);
+    /// Creates a new instance of the game with the given settings
+    pub fn new(settings: Settings) -> Game {
+        Game::with_settings(GameMode::Single, settings)
+    }
+
+    /// Creates a new instance of the game for the mode with the given settings
+    fn with_settings(mode: GameMode, settings: Settings) -> Game {
+        let mut game = Game::with_mode(mode);
+        game.apply_settings(settings);
+        game
+    }
 
+    /// Creates a new instance of the game with a player for every side of the screen
+    pub fn with_mode(mode: GameMode) -> Game {
         Game {
-            board,
-            block,
+            players: Game::players(mode, Size::default()),
             audio: Audio::new(),
-            score: 0,
-            waiting_time: 0.0,
-            status: GameStatus::Startup
+            mode,
+            status: GameStatus::Startup,
+            loser: None,
+            online: None,
+            spectator: None,
+            bots: Game::bots(mode, Weights::default()),
+            config: Config::default(),
+            recorder: None,
+            playback: None,
+            particles: Particles::default(),
+            pages: Vec::new(),
+            page: 0,
+            hint: None,
+            clipboard: None,
+            puzzles: None,
+            editor: None,
+            cursor: [0.0, 0.0],
+            painting: None,
+            menu: Menu::new(Screen::Main),
+            high_scores: HighScores::load(HIGH_SCORES_FILE),
+            binding: None,
+            exit: false,
+            held: Vec::new(),
+        }
+    }
+
+    /// Creates a player for every side of the screen with boards of the given size
+    fn players(mode: GameMode, size: Size) -> Vec<Player> {
+        match mode {
+            GameMode::Single | GameMode::Online | GameMode::Finesse | GameMode::Practice | GameMode::Puzzle => vec![Player::sized(Controls::single(), size)],
+            GameMode::Versus => vec![Player::sized(Controls::wasd(), size), Player::sized(Controls::arrows(), size)],
+            GameMode::Bot => vec![Player::sized(Controls::single(), size), Player::sized(Controls::none(), size)],
+            GameMode::Demo => vec![Player::sized(Controls::none(), size)],
+        }
+    }
+
+    /// Creates the built-in bots a mode needs together with the index of the player they control
+    fn bots(mode: GameMode, weights: Weights) -> Vec<(usize, Box<dyn Controller>)> {
+        match mode {
+            GameMode::Bot => vec![(1, Box::new(Bot::new(weights)))],
+            GameMode::Demo => vec![(0, Box::new(Bot::new(weights)))],
+            _ => Vec::new(),
+        }
+    }
+
+    /// Changes how the built-in bot judges boards, for example to weights found by tetris-train
+    pub fn set_weights(&mut self, weights: Weights) {
+        self.config.weights = weights;
+        self.bots = Game::bots(self.mode, weights);
+    }
+
+    /// Changes the size of the boards, practice positions and puzzles keep the size of their own boards
+    pub fn set_size(&mut self, size: Size) {
+        self.config.size = size;
+        if !matches!(self.mode, GameMode::Practice | GameMode::Puzzle) {
+            self.set_players(Game::players(self.mode, size));
+        }
+    }
+
+    /// Changes what makes the players lose, block, lock or partial lock out
+    pub fn set_top_out(&mut self, top_out: TopOut) {
+        self.config.top_out = top_out;
+        for player in self.players.iter_mut() {
+            player.top_out = top_out;
+        }
+    }
+
+    /// Changes how long the players wait for lines to clear and blocks to spawn
+    pub fn set_delays(&mut self, delays: Delays) {
+        self.config.delays = delays;
+        for player in self.players.iter_mut() {
+            player.delays = delays;
+        }
+    }
+
+    /// Changes how the blocks move while their keys are held
+    pub fn set_handling(&mut self, handling: Handling) {
+        self.config.handling = handling;
+        for player in self.players.iter_mut() {
+            player.handling = handling;
+        }
+    }
+
+    /// Changes how the random pieces are picked, practice positions and puzzles keep the pieces they come with
+    /// A seeded game starts over with the pieces of its seed
+    pub fn set_randomizer(&mut self, randomizer: Randomizer) {
+        self.config.randomizer = randomizer;
+        if !matches!(self.mode, GameMode::Practice | GameMode::Puzzle) {
+            for player in self.players.iter_mut() {
+                player.set_randomizer(randomizer);
+                if let Some(seed) = self.config.seed {
+                    player.set_seed(seed);
+                }
+            }
+        }
+    }
+
+    /// Gets the pieces of new games from the seed, so the same pieces come every time, for before the game starts
+    /// Practice positions and puzzles keep the pieces they come with
+    pub fn set_seed(&mut self, seed: u64) {
+        self.config.seed = Some(seed);
+        if !matches!(self.mode, GameMode::Practice | GameMode::Puzzle) {
+            for player in self.players.iter_mut() {
+                player.set_seed(seed);
+            }
+        }
+    }
+
+    /// Changes the level new games start on, the blocks fall faster on higher levels
+    pub fn set_level(&mut self, level: usize) {
+        self.config.level = level;
+        for player in self.players.iter_mut() {
+            player.set_level(level);
         }
     }
 
+    /// Reads and writes the settings in the given file from now on, instead of the settings file next to the game
+    pub fn set_settings_file<P: AsRef<Path>>(&mut self, path: P) {
+        self.config.settings_file = path.as_ref().to_path_buf();
+    }
+
+    /// Keeps the next single player game to play it again later, it is written to the file once it is over
+    /// A game that is not seeded gets a random seed, the pieces of a replay have to come from one
+    pub fn record_to<P: AsRef<Path>>(&mut self, path: P) {
+        if self.config.seed.is_none() {
+            self.set_seed(rand::random());
+        }
+        self.recorder = Some(Recorder::new(path));
+    }
+
+    /// Starts a new game in the mode right away, without the menu
+    pub fn play(&mut self, mode: GameMode) {
+        self.restart(mode);
+        self.status.update(GameEvent::Start);
+    }
+
+    /// Plays the replay back from the start with the rules it was recorded with, the look and sound stay as they are
+    pub fn watch(&mut self, replay: Replay) {
+        self.restart(GameMode::Single);
+        let rules = &replay.settings;
+        self.set_size(rules.size);
+        self.set_top_out(rules.top_out);
+        self.set_colors(rules.colors);
+        self.set_rotation(rules.rotation);
+        self.set_delays(rules.delays);
+        self.set_handling(rules.handling);
+        self.set_level(replay.level);
+        self.set_randomizer(rules.randomizer);
+        self.set_seed(replay.seed);
+        self.playback = Some(replay.frames.into_iter());
+        self.status.update(GameEvent::Start);
+    }
+
+    /// Changes what the blocks try when they can't rotate where they are
+    pub fn set_rotation(&mut self, rotation: RotationSystem) {
+        self.config.rotation = rotation;
+        for player in self.players.iter_mut() {
+            player.set_rotation(rotation);
+        }
+    }
+
+    /// Changes the keys of a player that has the keyboard to themselves, the players in versus keep their own side
+    pub fn set_controls(&mut self, controls: Controls) {
+        self.config.controls = controls;
+        if !matches!(self.mode, GameMode::Versus | GameMode::Demo) {
+            if let Some(player) = self.players.first_mut() {
+                player.set_controls(self.config.controls.clone());
+            }
+        }
+    }
+
+    /// Changes how loud the sounds are
+    pub fn set_volumes(&mut self, volumes: Volumes) {
+        self.audio.set_volumes(volumes);
+    }
+
+    /// Returns the settings the game is played with right now
+    pub fn settings(&self) -> Settings {
+        Settings {
+            size: self.config.size,
+            top_out: self.config.top_out,
+            colors: self.config.colors,
+            randomizer: self.config.randomizer,
+            rotation: self.config.rotation,
+            delays: self.config.delays,
+            handling: self.config.handling,
+            volumes: self.audio.volumes(),
+            theme: self.config.theme.directory.clone(),
+            fullscreen: self.config.fullscreen,
+            particles: self.particles.enabled,
+            shake: self.particles.shake,
+            accessibility: self.config.accessibility,
+            controls: self.config.controls.clone(),
+        }
+    }
+
+    /// Plays with the given settings from now on, a theme that can't be loaded is left as it is
+    pub fn apply_settings(&mut self, settings: Settings) {
+        self.set_size(settings.size);
+        self.set_top_out(settings.top_out);
+        self.set_colors(settings.colors);
+        self.set_randomizer(settings.randomizer);
+        self.set_rotation(settings.rotation);
+        self.set_delays(settings.delays);
+        self.set_handling(settings.handling);
+        self.set_volumes(settings.volumes);
+        if settings.theme != self.config.theme.directory {
+            match Theme::load(&settings.theme) {
+                Ok(theme) => self.set_theme(theme),
+                Err(error) => eprintln!("Could not load the theme in {}: {}", settings.theme.display(), error),
+            }
+        }
+        self.set_fullscreen(settings.fullscreen);
+        self.set_particles(settings.particles, settings.shake);
+        self.set_accessibility(settings.accessibility);
+        self.set_controls(settings.controls);
+    }
+
+    /// Writes the settings to the settings file so the next game starts with them
+    fn save_settings(&self) {
+        if let Err(error) = self.settings().save(&self.config.settings_file) {
+            eprintln!("Could not save the settings: {}", error);
+        }
+    }
+
+    /// Changes the pieces that are played with, practice positions and puzzles keep the usual pieces
+    pub fn set_pieces(&mut self, pieces: PieceSet) {
+        self.config.pieces = pieces;
+        if !matches!(self.mode, GameMode::Practice | GameMode::Puzzle) {
+            self.set_players(Game::players(self.mode, self.config.size));
+        }
+    }
+
+    /// Changes how the blocks get their colors, guideline, cycle or random
+    pub fn set_colors(&mut self, colors: ColorPolicy) {
+        self.config.colors = colors;
+        for player in self.players.iter_mut() {
+            player.set_colors(colors);
+        }
+    }
+
+    /// Changes how the game looks
+    pub fn set_theme(&mut self, theme: Theme) {
+        self.config.theme = theme;
+    }
+
+    /// Changes the palette and patterns for players who can't tell every block color apart
+    pub fn set_accessibility(&mut self, accessibility: Accessibility) {
+        self.config.accessibility = accessibility;
+    }
+
+    /// Makes the window fill the screen or go back to a window
+    pub fn set_fullscreen(&mut self, fullscreen: bool) {
+        self.config.fullscreen = fullscreen;
+    }
+
+    /// Turns the particles of cleared rows and hard drops and the shaking on a tetris on or off
+    pub fn set_particles(&mut self, enabled: bool, shake: bool) {
+        self.particles.enabled = enabled;
+        self.particles.shake = shake;
+    }
+
+    /// Switches to the theme the given amount of steps after the current one in the themes directory
+    fn next_theme(&mut self, step: isize) {
+        let themes = Theme::available();
+        let next = themes.iter()
+            .position(|directory| *directory == self.config.theme.directory)
+            .map_or(0, |index| (index as isize + step).rem_euclid(themes.len().max(1) as isize) as usize);
+
+        match themes.get(next).map(Theme::load) {
+            Some(Ok(theme)) => self.config.theme = theme,
+            Some(Err(error)) => eprintln!("Could not load the theme: {}", error),
+            None => {},
+        }
+    }
+
+    /// Puts new players in the game, they play with the top out rule, the pieces and the colors of the game
+    fn set_players(&mut self, mut players: Vec<Player>) {
+        for player in players.iter_mut() {
+            if !matches!(self.mode, GameMode::Practice | GameMode::Puzzle) {
+                player.set_pieces(self.config.pieces.clone());
+                player.set_randomizer(self.config.randomizer);
+            }
+            player.set_colors(self.config.colors);
+            player.set_rotation(self.config.rotation);
+            player.set_level(self.config.level);
+            // the seed goes last so the pieces only depend on it, the pieces and the randomizer
+            if let (Some(seed), false) = (self.config.seed, matches!(self.mode, GameMode::Practice | GameMode::Puzzle)) {
+                player.set_seed(seed);
+            }
+        }
+        self.players = players;
+        self.set_top_out(self.config.top_out);
+        self.set_delays(self.config.delays);
+        self.set_handling(self.config.handling);
+        self.set_controls(self.config.controls.clone());
+    }
+
+    /// Lets others watch the game by connecting to the given address
+    pub fn enable_spectators<A: ToSocketAddrs>(&mut self, address: A) -> io::Result<()> {
+        self.spectator = Some(SpectatorServer::new(address)?);
+        Ok(())
+    }
+
+    /// Creates a new instance of the game where the given controller plays on its own, like an external bot
+    pub fn demo(controller: Box<dyn Controller>, settings: Settings) -> Game {
+        let mut game = Game::with_settings(GameMode::Demo, settings);
+        game.bots = vec![(0, controller)];
+        game
+    }
+
+    /// Creates a new instance of the game to practice on the given positions, PageUp and PageDown switch between them
+    pub fn practice(pages: Vec<Position>, settings: Settings) -> Game {
+        let mut game = Game::with_settings(GameMode::Practice, settings);
+        game.pages = pages;
+        game.load_page(0);
+        game
+    }
+
+    /// Creates a new instance of the game that plays a pack of puzzles, PageUp and PageDown switch between them
+    pub fn puzzles(mut puzzles: Puzzles, settings: Settings) -> Game {
+        let mut game = Game::with_settings(GameMode::Puzzle, settings);
+        game.set_players(vec![puzzles.select(puzzles.index())]);
+        game.puzzles = Some(puzzles);
+        game
+    }
+
+    /// Creates a new instance of the game that starts in the editor, working on the position in the given file
+    /// A new position gets a board of the size of the settings
+    pub fn editor(path: &str, settings: Settings) -> Game {
+        let mut game = Game::new(settings);
+        game.editor = Some(Editor::new(path, game.config.size));
+        game.status.update(GameEvent::Edit);
+        game
+    }
+
+    /// Starts over on the given practice position
+    fn load_page(&mut self, page: usize) {
+        let Some(current) = self.pages.get(page) else {
+            return;
+        };
+
+        self.page = page;
+        self.hint = None;
+        self.set_players(vec![Player::practice(Controls::single(), &current.board, &current.queue, current.hold)]);
+        self.loser = None;
+    }
+
+    /// Throws away the current game and starts over in the given mode, anyone watching keeps watching
+    /// The bots keep playing when the mode stays the same, so an external bot does not have to be started again
+    fn restart(&mut self, mode: GameMode) {
+        // a replay only holds one game, it is written once that game is thrown away
+        if self.recorder.as_ref().is_some_and(|recorder| !recorder.is_empty()) {
+            self.finish_recording();
+        }
+        if mode != self.mode {
+            self.bots = Game::bots(mode, self.config.weights);
+        }
+        self.mode = mode;
+        self.status = GameStatus::Startup;
+        self.loser = None;
+        self.online = None;
+        self.playback = None;
+        self.hint = None;
+        self.editor = None;
+        self.painting = None;
+        self.menu = Menu::new(Screen::Main);
+        self.binding = None;
+        self.held.clear();
+        if mode != GameMode::Practice {
+            self.pages.clear();
+        }
+        if mode != GameMode::Puzzle {
+            self.puzzles = None;
+        }
+
+        self.set_players(Game::players(mode, self.config.size));
+        if mode == GameMode::Practice {
+            self.load_page(self.page);
+        }
+        if let Some(puzzles) = self.puzzles.as_mut() {
+            let player = puzzles.restart();
+            self.set_players(vec![player]);
+        }
+    }
+
+    /// Writes the replay that is being recorded to its file, nothing more is recorded after this
+    fn finish_recording(&mut self) {
+        if let Some(recorder) = self.recorder.take() {
+            match recorder.save() {
+                Ok(()) => println!("The replay was written to {}", recorder.path().display()),
+                Err(error) => eprintln!("Could not save the replay: {}", error),
+            }
+        }
+    }
+
+    /// Keeps what the first player did for the replay that is being recorded
+    fn record_input(&mut self, index: usize, input: Input) {
+        if let (0, GameMode::Single, Some(recorder)) = (index, self.mode, &mut self.recorder) {
+            recorder.input(input);
+        }
+    }
+
+    /// Looks for the lowest perfect clear with the pieces that can be seen and keeps the first piece of it as a hint
+    fn find_hint(&mut self) {
+        let player = &self.players[0];
+        let mut queue = vec![player.block().shape()];
+        queue.extend(player.queue().pieces());
+
+        let hint = (1..=HINT_LINES)
+            .map(|lines| solver::solve(player.board(), player.held_piece(), &queue, lines))
+            .find(|solutions| !solutions.is_empty())
+            .map(|solutions| solutions[0].steps[0].clone());
+        self.hint = Some(hint);
+    }
+
+    /// Copies the boards of the local players as a fumen with a page for every player
+    /// The code is printed as well, in case there is no clipboard
+    fn copy_fumen(&mut self) {
+        let pages: Vec<Page> = self.players.iter().map(|player| Page::new(player.board(), Some(player.block()))).collect();
+        let code = fumen::encode(&pages);
+        println!("{}", code);
+
+        if self.clipboard.is_none() {
+            self.clipboard = arboard::Clipboard::new().ok();
+        }
+        if let Some(clipboard) = &mut self.clipboard {
+            if let Err(error) = clipboard.set_text(code) {
+                eprintln!("Could not copy the fumen: {}", error);
+            }
+        }
+    }
+
+    /// Creates a new instance of the game that is played against others over the network, starting in the lobby
+    pub fn online(network: Network, settings: Settings) -> Game {
+        let mut game = Game::with_settings(GameMode::Online, settings);
+        game.online = Some(Online::new(network));
+        game.status.update(GameEvent::Wait);
+        game
+    }
+
     /// Starts the main game loop and handles user input and rendering
     pub fn start_loop(&mut self) {
-        let mut window: PistonWindow = WindowSettings::new("Tetris", (SCREEN_WIDTH, SCREEN_HEIGHT + BORDER))
-            .exit_on_esc(true)
-            .automatic_close(true)
-            .build()
-            .expect("Window failed to load");
+        let mut window_size = self.window_size();
+        let mut fullscreen = self.config.fullscreen;
+        let mut window = self.build_window(window_size);
+
+        let mut renderer = Renderer::new(&mut window, &self.config.theme);
 
-        let renderer = Renderer::new(&mut window);
+        let mut glyphs = window.load_font(self.config.theme.file(&self.config.theme.font)).expect("Could not load font");
 
-        let mut glyphs = window.load_font(Path::new("assets/gillsans.ttf")).expect("Could not load font");
+        // the layout is always window_size, it is scaled to whatever size the window has
+        let mut viewport = Viewport::fit(window_size.into(), window.size().into());
+        let mut pixel_ratio = window.draw_size().width / window.size().width;
 
         while let Some(event) = window.next() {
+            if let Some(Button::Keyboard(key)) = event.press_args() {
+                self.press(&key);
+            }
             if let Some(Button::Keyboard(key)) = event.release_args() {
-                self.input(&key)
+                self.input(&key);
+            }
+            if let Some(button @ (Button::Controller(_) | Button::Hat(_))) = event.press_args() {
+                if let Some(input) = MenuInput::from_button(&button) {
+                    self.menu_input(input);
+                }
+            }
+            if self.exit {
+                window.set_should_close(true);
+            }
+            if let Some(args) = event.resize_args() {
+                viewport = Viewport::fit(window_size.into(), args.window_size);
+                pixel_ratio = args.draw_size[0] as f64 / args.window_size[0];
             }
+            if let Some(position) = event.mouse_cursor_args() {
+                self.cursor = viewport.to_layout(position);
+                self.paint();
+            }
+            if let Some(Button::Mouse(button)) = event.press_args() {
+                self.painting = Some(button != MouseButton::Left);
+                self.paint();
+            }
+            if let Some(Button::Mouse(_)) = event.release_args() {
+                self.painting = None;
+            }
+
+            // switching to versus or players joining online changes how many boards fit in the window
+            // the window keeps how much it was scaled up, unless it fills the screen
+            if self.window_size() != window_size {
+                window_size = self.window_size();
+                if !fullscreen {
+                    window.set_size((window_size.0 * viewport.scale, window_size.1 * viewport.scale));
+                }
+                viewport = Viewport::fit(window_size.into(), window.size().into());
+            }
+            // a window can't go fullscreen once it is open so a new one is opened, which needs its own textures
+            if self.config.fullscreen != fullscreen {
+                fullscreen = self.config.fullscreen;
+                window = self.build_window(window_size);
+                renderer = Renderer::new(&mut window, &self.config.theme);
+                glyphs = window.load_font(self.config.theme.file(&self.config.theme.font)).expect("Could not load font");
+                viewport = Viewport::fit(window_size.into(), window.size().into());
+                pixel_ratio = window.draw_size().width / window.size().width;
+            }
+            if *renderer.theme() != self.config.theme {
+                renderer = Renderer::new(&mut window, &self.config.theme);
+                glyphs = window.load_font(self.config.theme.file(&self.config.theme.font)).expect("Could not load font");
+            }
+            renderer.set_accessibility(self.config.accessibility);
+            renderer.set_text_scale(viewport.scale * pixel_ratio);
 
-            window.draw_2d(&event, |context, g2d: &mut G2d, device| self.draw(context, g2d, device, &renderer, &mut glyphs));
+            window.draw_2d(&event, |context, g2d: &mut G2d, device| {
+                clear(LETTERBOX_COLOR, g2d);
+                self.draw(viewport.transform(context), g2d, device, &renderer, &mut glyphs)
+            });
             event.update(|update_args: &UpdateArgs| self.update(update_args));
         }
+        self.finish_recording();
+    }
+
+    /// Opens a window of the given size, or one that fills the screen, with the gamepads that are plugged in
+    fn build_window(&self, size: (f64, f64)) -> PistonWindow<Sdl2Window> {
+        let mut window: PistonWindow<Sdl2Window> = WindowSettings::new("Tetris", size)
+            .exit_on_esc(false)
+            .automatic_close(true)
+            .fullscreen(self.config.fullscreen)
+            .resizable(true)
+            .build()
+            .expect("Window failed to load");
+        // sdl only reports the buttons of gamepads that were opened
+        if let Err(error) = window.window.init_joysticks() {
+            eprintln!("Could not open the gamepads: {}", error);
+        }
+        window
+    }
+
+    /// The size the window needs to be to fit the boards of all players
+    fn window_size(&self) -> (f64, f64) {
+        let boards = self.screen_boards();
+        let gaps = boards.len().saturating_sub(1) as f64 * VERSUS_GAP;
+        let width = boards.iter().map(|board| screen_size(board).0).sum::<f64>() + gaps;
+        let height = boards.iter().map(|board| screen_size(board).1).fold(SCREEN_HEIGHT, f64::max);
+        (width, height + BORDER)
+    }
+
+    /// The boards on screen, the local players come first and then the players online
+    /// While editing it is only the board that is being edited
+    fn screen_boards(&self) -> Vec<&Board> {
+        if let (GameStatus::Editing, Some(editor)) = (&self.status, &self.editor) {
+            return vec![&editor.position.board];
+        }
+
+        let remotes = self.online.iter().flat_map(|online| online.remotes()).map(|remote| &remote.board);
+        self.players.iter().map(Player::board).chain(remotes).collect()
+    }
+
+    /// The amount of boards on screen, the local players come first and then the players online
+    fn boards(&self) -> usize {
+        self.players.len() + self.online.as_ref().map_or(0, |online| online.remotes().len())
+    }
+
+    /// Returns true if the player the board belongs to has lost
+    fn has_lost(&self, index: usize) -> bool {
+        match index.checked_sub(self.players.len()) {
+            None => self.loser == Some(index),
+            Some(remote) => self.online.as_ref().is_some_and(|online| online.remotes()[remote].topped_out),
+        }
+    }
+
+    /// the x in pixels where the board of a player starts
+    fn board_offset(&self, index: usize) -> f64 {
+        self.screen_boards().iter().take(index).map(|board| screen_size(board).0 + VERSUS_GAP).sum()
+    }
+
+    /// where a menu image is drawn so it is neatly centered on the screen of a board
+    fn image_location(&self, index: usize) -> (f64, f64) {
+        let (width, height) = self.screen_boards().get(index).map_or((SCREEN_WIDTH, SCREEN_HEIGHT), |board| screen_size(board));
+        (self.board_offset(index) + (width - MENU_IMAGE_WIDTH) / 2.0, (height - MENU_IMAGE_HEIGHT) / 2.0)
+    }
+
+    /// Paints the cell under the mouse in the editor while a mouse button is held, the left button paints and the others erase
+    fn paint(&mut self) {
+        if let (Some(erase), Some(editor)) = (self.painting, &mut self.editor) {
+            editor.paint(self.cursor[0], self.cursor[1] - BORDER, erase);
+        }
     }
 
     /// draws everything needed for the game screen
     fn draw(&self, context: Context, g2d: &mut G2d, device: &mut GfxDevice, renderer: &Renderer, glyphs: &mut Glyphs) {
-        clear(BACK_COLOR, g2d);
-        renderer.draw_image("header", 0.0, 0.0, &context, g2d);
+        let (width, height) = self.window_size();
+        renderer::draw_rect(renderer.theme().background, 0.0, 0.0, width, height, &context, g2d);
+        let [shake_x, shake_y] = self.particles.shake_offset();
+        let context = context.trans(shake_x, shake_y);
+
+        if let (GameStatus::Editing, Some(editor)) = (&self.status, &self.editor) {
+            editor.draw(renderer, glyphs, &context, g2d);
+            glyphs.factory.encoder.flush(device);
+            return;
+        }
+
+        for (index, player) in self.players.iter().enumerate() {
+            let offset = self.board_offset(index);
+            player.draw(offset, renderer, glyphs, &context, g2d);
+
+            if !matches!(self.mode, GameMode::Single | GameMode::Demo | GameMode::Finesse | GameMode::Practice | GameMode::Puzzle) {
+                self.draw_garbage_meter(index, player, &context, g2d);
+            }
+        }
+        self.particles.draw(&context, g2d);
+
+        if self.mode == GameMode::Practice && self.pages.len() > 1 {
+            let text = format!("Page {}/{}", self.page + 1, self.pages.len());
+            renderer.draw_text(&text, PAGE_LOCATION.0, PAGE_LOCATION.1, glyphs, &context, g2d);
+        }
+
+        let hint_y = PAGE_LOCATION.1 + STARTUP_LINE_HEIGHT;
+        match &self.hint {
+            Some(Some(step)) => {
+                let hidden = self.players[0].board().hidden();
+                for (y, x) in step.cells.iter().filter(|(y, _)| *y >= hidden) {
+                    renderer.draw_block(HINT_COLOR, *x as f64, (y - hidden) as f64, 0.0, &context, g2d);
+                }
+                if step.hold {
+                    renderer.draw_text("Hold first", PAGE_LOCATION.0, hint_y, glyphs, &context, g2d);
+                }
+            },
+            Some(None) => renderer.draw_text("No perfect clear", PAGE_LOCATION.0, hint_y, glyphs, &context, g2d),
+            None => {}
+        }
+
+        if let Some(puzzles) = &self.puzzles {
+            let solved = if puzzles.is_solved(puzzles.index()) { " (solved)" } else { "" };
+            let lines = [
+                format!("Puzzle {}/{}{}", puzzles.index() + 1, puzzles.len(), solved),
+                puzzles.current().goal.describe(),
+                format!("Pieces left: {}", puzzles.attempt().pieces_left()),
+            ];
+            for (index, line) in lines.iter().enumerate() {
+                let y = PAGE_LOCATION.1 + STARTUP_LINE_HEIGHT * index as f64;
+                renderer.draw_text(line, PAGE_LOCATION.0, y, glyphs, &context, g2d);
+            }
+        }
+
+        if let Some(online) = &self.online {
+            let first = self.players.len();
+            online.draw(|index| self.board_offset(first + index), renderer, glyphs, &context, g2d);
+        }
 
-        self.board.draw(&context, g2d);
-        let text = format!("Current score: {}", self.score);
-        renderer.draw_text(&text, glyphs, &context, g2d);
-       
         match self.status {
             GameStatus::Startup => {
-                renderer.draw_image("startup", IMAGE_LOCATION_X, IMAGE_LOCATION_Y, &context, g2d);
+                let location = [self.board_offset(0) + MENU_LOCATION.0, MENU_LOCATION.1];
+                self.menu.draw(&self.menu_items(), location, renderer, glyphs, &context, g2d);
+            },
+            GameStatus::Lobby => {
+                if let Some(online) = &self.online {
+                    online.draw_lobby(0.0, renderer, glyphs, &context, g2d);
+                }
             },
             GameStatus::GameOver => {
-                renderer::draw_rect(GAME_OVER_COLOR, 0.0, BORDER, SCREEN_WIDTH, SCREEN_HEIGHT , &context, g2d);
-                renderer.draw_image("game_over", IMAGE_LOCATION_X, IMAGE_LOCATION_Y, &context, g2d);
+                for (index, board) in self.screen_boards().into_iter().enumerate() {
+                    let (image_x, image_y) = self.image_location(index);
+                    if self.has_lost(index) {
+                        let (width, height) = screen_size(board);
+                        renderer::draw_rect(renderer.theme().game_over, self.board_offset(index), BORDER, width, height, &context, g2d);
+                        renderer.draw_image("game_over", image_x, image_y, &context, g2d);
+                    } else if self.boards() > 1 {
+                        renderer.draw_text("Winner!", image_x + 60.0, image_y + 45.0, glyphs, &context, g2d);
+                    }
+                }
+
+                let (image_x, image_y) = self.image_location(0);
+
+                let outcome = match self.puzzles.as_ref().map(Puzzles::outcome) {
+                    Some(Outcome::Solved) => "Solved! Press F for the next one",
+                    Some(Outcome::Failed) => "Failed, press F to try again",
+                    _ => "",
+                };
+                renderer.draw_text(outcome, 10.0, image_y + MENU_IMAGE_HEIGHT + 30.0, glyphs, &context, g2d);
+
+                if self.online.as_ref().is_some_and(|online| online.disconnected) {
+                    renderer.draw_text("Connection lost", image_x + 30.0, image_y + MENU_IMAGE_HEIGHT + 30.0, glyphs, &context, g2d);
+                }
             },
             GameStatus::Paused => {
-                renderer.draw_image("paused", IMAGE_LOCATION_X, IMAGE_LOCATION_Y, &context, g2d);
+                // the menu is on the first board, the others show that the game is paused
+                for index in 1..self.players.len() {
+                    let (image_x, image_y) = self.image_location(index);
+                    renderer.draw_image("paused", image_x, image_y, &context, g2d);
+                }
+                let location = [self.board_offset(0) + MENU_LOCATION.0, MENU_LOCATION.1];
+                self.menu.draw(&self.menu_items(), location, renderer, glyphs, &context, g2d);
             },
             _ => {}
         }
         glyphs.factory.encoder.flush(device);
     }
 
+    /// draws the pending garbage of a player as a bar in the gap between the boards, growing up from the bottom
+    fn draw_garbage_meter(&self, index: usize, player: &Player, context: &Context, g2d: &mut G2d) {
+        let (width, screen_height) = screen_size(player.board());
+        let x = if index == 0 {
+            width
+        } else {
+            self.board_offset(index) - GARBAGE_METER_WIDTH
+        };
+        let height = (player.garbage.pending() as f64 * BLOCK_SIZE).min(screen_height);
+
+        renderer::draw_rect(GARBAGE_METER_COLOR, x, BORDER + screen_height - height, GARBAGE_METER_WIDTH, height, context, g2d);
+    }
+
     /// Handles user input by updating the game state according to input
     fn input(&mut self, key: &Key) {
-        if self.status == GameStatus::Playing {
+        self.held.retain(|held| held != key);
+        // the block stops sliding as soon as its key goes up, whatever happened in the meantime
+        for index in 0..self.players.len() {
+            if let (Some(input_type), None) = (self.players[index].input_for(key), &self.playback) {
+                self.players[index].release(input_type);
+                self.record_input(index, Input::Release(input_type));
+            }
+        }
+
+        if let Some(index) = self.binding.take() {
+            if *key != Key::Escape {
+                let mut controls = self.config.controls.clone();
+                controls.bind(index, *key);
+                self.set_controls(controls);
+                self.save_settings();
+            }
+            self.audio.play_audio(SoundEffect::Menu);
+        } else if *key == Key::F11 {
+            self.config.fullscreen = !self.config.fullscreen;
+        } else if self.status == GameStatus::Editing {
+            match (key, &mut self.editor) {
+                // the position is played as it is, the editor is gone until it is opened again
+                (Key::Return, Some(editor)) => {
+                    self.pages = vec![editor.position.clone()];
+                    self.page = 0;
+                    self.restart(GameMode::Practice);
+                    self.status.update(GameEvent::Start);
+                    self.audio.play_audio(SoundEffect::Menu);
+                },
+                (_, Some(editor)) => editor.input(key),
+                _ => {}
+            }
+        } else if self.status == GameStatus::Lobby {
+            if let (Key::R, Some(online)) = (key, &mut self.online) {
+                self.audio.play_audio(SoundEffect::Menu);
+                online.toggle_ready();
+            }
+        } else if self.status == GameStatus::Playing {
             match key {
-                Key::Left  | Key::A => self.handle_movement_input(InputType::Left),
-                Key::Right | Key::D => self.handle_movement_input(InputType::Right),
-                Key::Down  | Key::S => self.handle_movement_input(InputType::Down),
-                Key::Up    | Key::W | Key::R => self.handle_movement_input(InputType::Rotate),
-                Key::P => { 
+                // the others keep playing online so the game can't be paused
+                Key::P | Key::Escape if self.mode != GameMode::Online => {
                     self.audio.play_audio(SoundEffect::Menu);
+                    self.menu = Menu::new(Screen::Pause);
                     self.status.update(GameEvent::Pause)
                 },
+                Key::PageUp | Key::PageDown if self.mode == GameMode::Practice => {
+                    let page = match key {
+                        Key::PageUp => self.page.saturating_sub(1),
+                        _ => self.page + 1,
+                    };
+                    self.audio.play_audio(SoundEffect::Menu);
+                    self.load_page(page);
+                },
+                Key::H if self.mode == GameMode::Practice => self.find_hint(),
+                Key::PageUp | Key::PageDown if self.mode == GameMode::Puzzle => {
+                    if let Some(puzzles) = &mut self.puzzles {
+                        let index = match key {
+                            Key::PageUp => puzzles.index().saturating_sub(1),
+                            _ => puzzles.index() + 1,
+                        };
+                        self.audio.play_audio(SoundEffect::Menu);
+                        let player = puzzles.select(index);
+                        self.set_players(vec![player]);
+                    }
+                },
+                _ => {}
+            }
+        } else if matches!(self.status, GameStatus::Startup | GameStatus::Paused) {
+            match (key, MenuInput::from_key(key)) {
+                (Key::P, _) if self.status == GameStatus::Paused => self.menu_action(Action::Resume),
+                (Key::C, _) if self.status == GameStatus::Paused => self.menu_action(Action::CopyFumen),
+                // bots and practice games started from the command line are started with F
+                (Key::F, _) if self.status == GameStatus::Startup => self.menu_action(Action::Play),
+                (_, Some(input)) => self.menu_input(input),
                 _ => {}
             }
         } else {
             match key {
-                Key::P => { 
+                Key::Escape if self.mode != GameMode::Online => {
                     self.audio.play_audio(SoundEffect::Menu);
-                    self.status.update(GameEvent::Pause)
+                    self.restart(self.mode);
                 },
-                Key::F => { 
+                // online a new round starts when everyone is ready in the lobby
+                Key::F if self.mode == GameMode::Online && self.online.as_ref().is_some_and(|online| !online.disconnected) => {
+                    self.status.update(GameEvent::Wait);
+                    self.audio.play_audio(SoundEffect::Menu);
+                },
+                Key::F if self.mode != GameMode::Online => {
                     if  self.status == GameStatus::GameOver {
-                        *self = Game::new()
+                        self.restart(self.mode)
                     }
                     self.status.update(GameEvent::Start);
                     self.audio.play_audio(SoundEffect::Menu);
@@ -141,53 +1009,447 @@ impl Game {
         }
     }
 
-    /// handles the movement input to have a cleaner input() function
-    fn handle_movement_input(&mut self, input_type: InputType) {
-        /// to go left go -1 on the x-axis
-        const LEFT_X: i16 = -1;
-        /// to go right go +1 on the x-axis
-        const RIGHT_X: i16 = 1;
+    /// Moves through the menu and does what was picked
+    fn menu_input(&mut self, input: MenuInput) {
+        if !matches!(self.status, GameStatus::Startup | GameStatus::Paused) {
+            return;
+        }
+        let items = self.menu_items();
+        match self.menu.input(input, &items) {
+            Some(action) => self.menu_action(action),
+            None => self.audio.play_audio(SoundEffect::Move),
+        }
+    }
 
-        match input_type {
-            InputType::Left => {
-                self.audio.play_audio(SoundEffect::Move);
-                self.block.move_sideways(&mut self.board, LEFT_X);
+    /// Returns the items of the screen of the menu that is shown, with the current value of every setting
+    fn menu_items(&self) -> Vec<Item> {
+        let on_off = |value: bool| if value { "on" } else { "off" };
+        let back = Item::new("Back", Action::Back);
+        match self.menu.screen() {
+            Screen::Main => vec![
+                Item::new("Play", Action::Play),
+                Item::new("Modes", Action::Open(Screen::Modes)),
+                Item::new("Settings", Action::Open(Screen::Settings)),
+                Item::new("High scores", Action::Open(Screen::HighScores)),
+                Item::new("Quit", Action::Exit),
+            ],
+            Screen::Modes => vec![
+                Item::new("Single player", Action::Mode(GameMode::Single)),
+                Item::new("Versus", Action::Mode(GameMode::Versus)),
+                Item::new("Against the bot", Action::Mode(GameMode::Bot)),
+                Item::new("Bot demo", Action::Mode(GameMode::Demo)),
+                Item::new("Finesse training", Action::Mode(GameMode::Finesse)),
+                Item::new("Editor", Action::Editor),
+                back,
+            ],
+            Screen::Settings => vec![
+                Item::new("Gameplay", Action::Open(Screen::Gameplay)),
+                Item::new("Handling", Action::Open(Screen::Handling)),
+                Item::new("Audio", Action::Open(Screen::Audio)),
+                Item::new("Video", Action::Open(Screen::Video)),
+                Item::new("Controls", Action::Open(Screen::Controls)),
+                back,
+            ],
+            Screen::Gameplay => {
+                let mut items = vec![
+                    Item::new(format!("Top out: {}", self.config.top_out), Action::Change(Setting::TopOut, 0)),
+                    Item::new(format!("Colors: {}", self.config.colors), Action::Change(Setting::Colors, 0)),
+                    Item::new(format!("Randomizer: {}", self.config.randomizer), Action::Change(Setting::Randomizer, 0)),
+                    Item::new(format!("Rotation: {}", self.config.rotation), Action::Change(Setting::Rotation, 0)),
+                ];
+                // a new board size would throw away the game that is paused
+                if self.status == GameStatus::Startup {
+                    items.push(Item::new(format!("Width: {}", self.config.size.width), Action::Change(Setting::Width, 0)));
+                    items.push(Item::new(format!("Height: {}", self.config.size.height), Action::Change(Setting::Height, 0)));
+                }
+                items.push(back);
+                items
             },
-            InputType::Right => {
-                self.audio.play_audio(SoundEffect::Move);
-                self.block.move_sideways(&mut self.board, RIGHT_X);
+            Screen::Handling => vec![
+                Item::new(format!("Auto shift delay: {:.2}s", self.config.handling.das), Action::Change(Setting::Das, 0)),
+                Item::new(format!("Auto repeat rate: {:.2}s", self.config.handling.arr), Action::Change(Setting::Arr, 0)),
+                Item::new(format!("Soft drop: {}x", self.config.handling.sdf), Action::Change(Setting::Sdf, 0)),
+                Item::new(format!("Line clear delay: {:.2}s", self.config.delays.line_clear), Action::Change(Setting::LineClearDelay, 0)),
+                Item::new(format!("Entry delay: {:.2}s", self.config.delays.entry), Action::Change(Setting::EntryDelay, 0)),
+                back,
+            ],
+            Screen::Audio => vec![
+                Item::new(format!("Effects volume: {:.0}%", self.audio.volumes().effects * 100.0), Action::Change(Setting::EffectsVolume, 0)),
+                Item::new(format!("Menu volume: {:.0}%", self.audio.volumes().menu * 100.0), Action::Change(Setting::MenuVolume, 0)),
+                back,
+            ],
+            Screen::Video => vec![
+                Item::new(format!("Theme: {}", self.config.theme.name), Action::Change(Setting::Theme, 0)),
+                Item::new(format!("Fullscreen: {}", on_off(self.config.fullscreen)), Action::Change(Setting::Fullscreen, 0)),
+                Item::new(format!("Particles: {}", on_off(self.particles.enabled)), Action::Change(Setting::Particles, 0)),
+                Item::new(format!("Screen shake: {}", on_off(self.particles.shake)), Action::Change(Setting::Shake, 0)),
+                Item::new(format!("Vision: {}", self.config.accessibility.vision), Action::Change(Setting::Vision, 0)),
+                Item::new(format!("Patterns: {}", on_off(self.config.accessibility.patterns)), Action::Change(Setting::Patterns, 0)),
+                back,
+            ],
+            Screen::Controls => {
+                let mut items: Vec<Item> = self.config.controls.bindings().iter().enumerate()
+                    .map(|(index, (name, keys))| {
+                        let keys: Vec<String> = keys.iter().map(|key| format!("{:?}", key)).collect();
+                        let keys = if self.binding == Some(index) { String::from("press a key") } else { keys.join(", ") };
+                        Item::new(format!("{}: {}", name, keys), Action::Bind(index))
+                    })
+                    .collect();
+                items.push(Item::new("Pause: P, Escape", Action::Nothing));
+                items.push(back);
+                items
             },
-            InputType::Down => {
-                self.audio.play_audio(SoundEffect::Move);
-                self.block.move_down(&mut self.board);
+            Screen::HighScores => {
+                let mut items: Vec<Item> = self.high_scores.scores().iter().enumerate()
+                    .map(|(place, score)| {
+                        let time = Stats { time: score.time, ..Stats::default() }.clock();
+                        Item::new(format!("{}. {} lines {} {:.2} pps", place + 1, score.lines, time, score.pps), Action::Nothing)
+                    })
+                    .collect();
+                if items.is_empty() {
+                    items.push(Item::new("No games yet", Action::Nothing));
+                }
+                items.push(back);
+                items
             },
-            InputType::Rotate => { 
-                self.audio.play_audio(SoundEffect::Rotate);
-                self.block.rotate(&mut self.board);
+            Screen::Pause => vec![
+                Item::new("Resume", Action::Resume),
+                Item::new("Restart", Action::Restart),
+                Item::new("Settings", Action::Open(Screen::Settings)),
+                Item::new("Copy as fumen", Action::CopyFumen),
+                Item::new("Main menu", Action::MainMenu),
+                Item::new("Quit", Action::Exit),
+            ],
+        }
+    }
+
+    /// Does what was picked in the menu, settings are changed right away
+    fn menu_action(&mut self, action: Action) {
+        self.audio.play_audio(SoundEffect::Menu);
+        match action {
+            Action::Play if self.status == GameStatus::Startup => self.status.update(GameEvent::Start),
+            Action::Mode(mode) => self.play(mode),
+            Action::Editor => {
+                self.editor = Some(Editor::new(DEFAULT_POSITION_FILE, self.config.size));
+                self.status.update(GameEvent::Edit);
             },
+            // leaving the pause menu goes back to the game
+            Action::Resume | Action::Back if self.status == GameStatus::Paused => self.status.update(GameEvent::Pause),
+            Action::Restart => self.play(self.mode),
+            Action::MainMenu => self.restart(self.mode),
+            Action::CopyFumen => self.copy_fumen(),
+            Action::Exit => self.exit = true,
+            Action::Change(setting, step) => {
+                self.change_setting(setting, step);
+                self.save_settings();
+            },
+            Action::Bind(index) => self.binding = Some(index),
+            _ => {}
+        }
+    }
+
+    /// Changes a setting a step back or forward
+    fn change_setting(&mut self, setting: Setting, step: i8) {
+        let time = |time: f64, size: f64| ((time + size * step as f64).clamp(0.0, MAX_DELAY) / size).round() * size;
+        let delay = |delay: f64| time(delay, DELAY_STEP);
+        let volume = |volume: f32| volume + VOLUME_STEP * step as f32;
+        match setting {
+            Setting::Width => {
+                let width = (self.config.size.width as isize + step as isize) as usize;
+                self.set_size(Size { width: width.clamp(*board::WIDTHS.start(), *board::WIDTHS.end()), ..self.config.size });
+            },
+            Setting::Height => {
+                let height = (self.config.size.height as isize + step as isize) as usize;
+                self.set_size(Size { height: height.clamp(*board::HEIGHTS.start(), *board::HEIGHTS.end()), ..self.config.size });
+            },
+            Setting::TopOut => self.set_top_out(menu::cycle(&[TopOut::BlockOut, TopOut::LockOut, TopOut::PartialLockOut], self.config.top_out, step)),
+            Setting::Colors => self.set_colors(menu::cycle(&[ColorPolicy::Guideline, ColorPolicy::Cycle, ColorPolicy::Random], self.config.colors, step)),
+            Setting::Randomizer => self.set_randomizer(menu::cycle(&[Randomizer::Classic, Randomizer::Bag, Randomizer::Random], self.config.randomizer, step)),
+            Setting::Rotation => self.set_rotation(menu::cycle(&[RotationSystem::Classic, RotationSystem::WallKicks], self.config.rotation, step)),
+            Setting::LineClearDelay => self.set_delays(Delays { line_clear: delay(self.config.delays.line_clear), ..self.config.delays }),
+            Setting::EntryDelay => self.set_delays(Delays { entry: delay(self.config.delays.entry), ..self.config.delays }),
+            Setting::Das => self.set_handling(Handling { das: time(self.config.handling.das, HANDLING_STEP), ..self.config.handling }),
+            Setting::Arr => self.set_handling(Handling { arr: time(self.config.handling.arr, HANDLING_STEP), ..self.config.handling }),
+            Setting::Sdf => self.set_handling(Handling { sdf: (self.config.handling.sdf + step as f64).clamp(1.0, MAX_SOFT_DROP), ..self.config.handling }),
+            Setting::EffectsVolume => self.set_volumes(Volumes { effects: volume(self.audio.volumes().effects), ..self.audio.volumes() }),
+            Setting::MenuVolume => self.set_volumes(Volumes { menu: volume(self.audio.volumes().menu), ..self.audio.volumes() }),
+            Setting::Theme => self.next_theme(step as isize),
+            Setting::Fullscreen => self.config.fullscreen = !self.config.fullscreen,
+            Setting::Particles => self.particles.enabled = !self.particles.enabled,
+            Setting::Shake => self.particles.shake = !self.particles.shake,
+            Setting::Vision => {
+                let visions = [ColorVision::Normal, ColorVision::Deuteranopia, ColorVision::Protanopia, ColorVision::Tritanopia];
+                self.config.accessibility.vision = menu::cycle(&visions, self.config.accessibility.vision, step);
+            },
+            Setting::Patterns => self.config.accessibility.patterns = !self.config.accessibility.patterns,
+        }
+    }
+
+    /// Handles a key that went down, blocks move as soon as their key is pressed and keep moving while it is held
+    fn press(&mut self, key: &Key) {
+        let repeated = self.held.contains(key);
+        if !repeated {
+            self.held.push(*key);
+        }
+        if self.status != GameStatus::Playing || self.playback.is_some() {
+            return;
+        }
+        for index in 0..self.players.len() {
+            if let Some(input_type) = self.players[index].input_for(key) {
+                self.handle_movement_input(index, input_type);
+            } else if self.players[index].holds_with(key) && !repeated {
+                self.audio.play_audio(SoundEffect::Move);
+                self.players[index].hold();
+                self.record_input(index, Input::Hold);
+            } else if self.players[index].hard_drops_with(key) && !repeated {
+                self.audio.play_audio(SoundEffect::Move);
+                self.players[index].hard_drop();
+                self.record_input(index, Input::HardDrop);
+            }
+        }
+    }
+
+    /// handles the movement input to have a cleaner press() function, keys the keyboard repeats make no sound
+    fn handle_movement_input(&mut self, index: usize, input_type: InputType) {
+        if !self.players[index].press(input_type) {
+            return;
+        }
+        self.record_input(index, Input::Press(input_type));
+        match input_type {
+            InputType::Rotate => self.audio.play_audio(SoundEffect::Rotate),
+            _ => self.audio.play_audio(SoundEffect::Move),
         }
     }
 
     /// Updates the game state based on the elapsed time since the last update
     fn update(&mut self, update_args: &UpdateArgs) {
-        self.waiting_time += update_args.dt;
+        self.update_online();
 
-        if self.waiting_time > MOVING_PERIOD && self.status == GameStatus::Playing {
-            if self.block.status == BlockStatus::Frozen {
-                self.board.update(&mut self.score);
+        if self.status == GameStatus::Playing {
+            // a replay is played one recorded update at a time, whatever the time between updates is now
+            match self.playback.as_mut().map(Iterator::next) {
+                Some(Some(frame)) => {
+                    for input in frame.inputs {
+                        input.apply(&mut self.players[0]);
+                    }
+                    self.update_players(frame.dt);
+                },
+                Some(None) => self.status.update(GameEvent::End),
+                None => self.update_players(update_args.dt),
+            }
+        }
+        self.particles.update(update_args.dt);
 
-                match Block::next(&mut self.board, BLOCK_SPAWN_POSITION, &self.block) {
-                    Some(block) => self.block = block,
-                    None => { 
+        if let Some(spectator) = &mut self.spectator {
+            spectator.broadcast(&self.status, &self.players);
+        }
+    }
+
+    /// Keeps the game of the player if it is one of the best, the file is written right away
+    fn add_high_score(&mut self) {
+        if self.high_scores.add(HighScore::from(&self.players[0])).is_some() {
+            if let Err(error) = self.high_scores.save() {
+                eprintln!("Could not save the high scores: {}", error);
+            }
+        }
+    }
+
+    /// Keeps an update for the replay that is being recorded, the replay starts with the settings of the first update
+    fn record_frame(&mut self, dt: f64) {
+        if self.mode != GameMode::Single {
+            return;
+        }
+        if self.recorder.as_ref().is_some_and(Recorder::is_empty) {
+            let (seed, level, settings) = (self.config.seed.unwrap_or_default(), self.config.level, self.settings());
+            if let Some(recorder) = &mut self.recorder {
+                recorder.start(seed, level, settings);
+            }
+        }
+        if let Some(recorder) = &mut self.recorder {
+            recorder.frame(dt);
+        }
+    }
+
+    /// Lets the blocks of all players fall and handles what happened to them
+    fn update_players(&mut self, dt: f64) {
+        self.record_frame(dt);
+        for (index, bot) in self.bots.iter_mut() {
+            if let Some(input_type) = bot.update(dt, &self.players[*index]) {
+                self.players[*index].handle_input(input_type);
+            }
+        }
+
+        for index in 0..self.players.len() {
+            let event = self.players[index].update(dt);
+            let offset = self.board_offset(index);
+            for visual in self.players[index].take_visuals() {
+                self.particles.show(visual, offset);
+            }
+
+            match event {
+                Some(PlayerEvent::Locked { lines, sent, fault, spin }) => {
+                    self.record(StreamEvent::Locked { player: index, lines, sent, fault });
+                    self.hint = None;
+
+                    if fault && self.mode == GameMode::Finesse {
                         self.audio.play_audio(SoundEffect::Lose);
-                        self.status.update(GameEvent::End)
-                    },
-                }
-            } else {
-                self.block.move_down(&mut self.board);
+                        self.restart(GameMode::Finesse);
+                        self.status.update(GameEvent::Start);
+                        return;
+                    }
+
+                    if let Some(puzzles) = &mut self.puzzles {
+                        match puzzles.locked(self.players[index].board(), lines, spin) {
+                            Outcome::Solved => self.audio.play_audio(SoundEffect::RowCompleted),
+                            Outcome::Failed => self.audio.play_audio(SoundEffect::Lose),
+                            Outcome::Playing => continue,
+                        }
+                        self.status.update(GameEvent::End);
+                        return;
+                    }
+
+                    // the attack goes to every other player
+                    for (other, player) in self.players.iter_mut().enumerate() {
+                        if other != index {
+                            player.garbage.receive(sent);
+                        }
+                    }
+                    if let Some(online) = &mut self.online {
+                        if sent > 0 {
+                            online.send_attack(sent);
+                        }
+                    }
+                },
+                Some(PlayerEvent::ToppedOut) => {
+                    self.record(StreamEvent::ToppedOut { player: index });
+                    // a replay that is watched was already played, it is not a new game
+                    if self.mode == GameMode::Single && self.playback.is_none() {
+                        self.add_high_score();
+                        self.finish_recording();
+                    }
+                    self.audio.play_audio(SoundEffect::Lose);
+                    self.loser = Some(index);
+                    self.status.update(GameEvent::End);
+                    if let Some(online) = &mut self.online {
+                        online.send_topped_out();
+                    }
+                    return;
+                },
+                None => {}
+            }
+        }
+
+        if let Some(online) = &mut self.online {
+            online.send_board(self.players[0].board(), self.players[0].score);
+        }
+    }
+
+    /// Passes an event on to anyone watching
+    fn record(&mut self, event: StreamEvent) {
+        if let Some(spectator) = &mut self.spectator {
+            spectator.push_event(event);
+        }
+    }
+
+    /// Handles everything that came in over the network when playing online
+    fn update_online(&mut self) {
+        let Some(online) = &mut self.online else {
+            return;
+        };
+
+        let events = online.poll();
+        for event in events {
+            match event {
+                OnlineEvent::Start => {
+                    self.set_players(vec![Player::sized(self.config.controls.clone(), self.config.size)]);
+                    self.loser = None;
+                    self.status.update(GameEvent::Start);
+                    self.audio.play_audio(SoundEffect::Menu);
+                },
+                OnlineEvent::Attack(lines) => {
+                    if self.status == GameStatus::Playing {
+                        self.players[0].garbage.receive(lines);
+                    }
+                },
+                // the last player standing wins
+                OnlineEvent::PlayerOut => {
+                    if self.status == GameStatus::Playing && self.online.as_ref().is_some_and(|online| online.remaining() == 0) {
+                        self.status.update(GameEvent::End);
+                    }
+                },
+                OnlineEvent::Disconnected => self.status.update(GameEvent::End),
             }
+        }
+    }
+}
+
+/// The room a board takes up on screen under the header, at least SCREEN_WIDTH by SCREEN_HEIGHT
+fn screen_size(board: &Board) -> (f64, f64) {
+    (
+        (board.width() as f64 * BLOCK_SIZE).max(SCREEN_WIDTH),
+        (board.size().height as f64 * BLOCK_SIZE).max(SCREEN_HEIGHT),
+    )
+}
+
+#[cfg(test)]
+mod game_tests {
+    use super::*;
+    use crate::block::BlockStatus;
+
+    #[test]
+    fn test_hard_drop_on_press() {
+        let mut game = Game::with_mode(GameMode::Single);
+        game.status.update(GameEvent::Start);
+        let top = game.players[0].block().position.0;
+
+        // the block is down as soon as the key is, before it comes up again
+        game.press(&Key::Space);
+        assert!(game.players[0].block().status == BlockStatus::Frozen);
+        assert!(game.players[0].block().position.0 > top);
+
+        // the keyboard repeating the key while it is held does not drop the next block as well
+        for _ in 0..3 {
+            game.update_players(1.0);
+        }
+        assert_eq!(game.players[0].stats.pieces, 1);
+        let next = game.players[0].block().position.0;
+        game.press(&Key::Space);
+        assert_eq!(game.players[0].block().position.0, next);
+        game.input(&Key::Space);
+        game.press(&Key::Space);
+        assert!(game.players[0].block().status == BlockStatus::Frozen);
+    }
+
+    #[test]
+    fn test_hold_on_press() {
+        let mut game = Game::with_mode(GameMode::Single);
+        game.status.update(GameEvent::Start);
+        let shape = game.players[0].block().shape();
+
+        game.press(&Key::C);
+        assert_eq!(game.players[0].held_piece(), Some(shape));
+        game.input(&Key::C);
+        assert_eq!(game.players[0].held_piece(), Some(shape));
+    }
+
+    #[test]
+    fn test_restart() {
+        let mut game = Game::with_mode(GameMode::Single);
+        let size = Size { width: 12, ..Size::default() };
+        game.set_size(size);
+        game.set_level(5);
+        game.status.update(GameEvent::Start);
+        game.press(&Key::Space);
 
-            self.waiting_time = 0.0;
+        // the settings are kept and the players start over
+        game.restart(GameMode::Versus);
+        assert!(game.status == GameStatus::Startup);
+        assert_eq!(game.players.len(), 2);
+        for player in &game.players {
+            assert_eq!(player.board().size(), size);
+            assert_eq!(player.stats.pieces, 0);
+            assert_eq!(player.stats.level(), 5);
         }
+        assert!(game.held.is_empty());
     }
 }
@@ -1,16 +1,60 @@
+use std::str::FromStr;
+
+use serde::Serialize;
+
 /// This enum represents the possible states of a game
-#[derive(PartialEq)]
+#[derive(PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
 pub enum GameStatus {
     Startup,
+    Lobby,
     Playing,
     Paused,
     GameOver,
+    /// putting a position together in the editor
+    Editing,
+}
+/// This enum represents who the game is played against
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum GameMode {
+    Single,
+    Versus,
+    Online,
+    /// a person against a bot
+    Bot,
+    /// a bot plays on its own
+    Demo,
+    /// a single player game that starts over as soon as a piece is placed with more inputs than needed
+    Finesse,
+    /// a single player game on positions loaded from a fumen code or made in the editor
+    Practice,
+    /// boards with a few pieces and a goal to reach with them
+    Puzzle,
+}
+
+impl FromStr for GameMode {
+    type Err = String;
+
+    /// Reads one of the modes that can be started from the command line, written as single, versus, bot, demo or finesse
+    fn from_str(text: &str) -> Result<GameMode, String> {
+        match text {
+            "single" => Ok(GameMode::Single),
+            "versus" => Ok(GameMode::Versus),
+            "bot" => Ok(GameMode::Bot),
+            "demo" => Ok(GameMode::Demo),
+            "finesse" => Ok(GameMode::Finesse),
+            _ => Err(format!("{} is not a mode, use single, versus, bot, demo or finesse", text)),
+        }
+    }
 }
+
 /// This  enum represents the possible events that can occur during a game
 pub enum GameEvent {
     Start,
     Pause,
     End,
+    Wait,
+    Edit,
 }
 
 impl GameStatus {
@@ -22,6 +66,23 @@ impl GameStatus {
                     GameEvent::Start => {
                         *self = GameStatus::Playing;
                     }
+                    GameEvent::Wait => {
+                        *self = GameStatus::Lobby;
+                    }
+                    GameEvent::Edit => {
+                        *self = GameStatus::Editing;
+                    }
+                    _ => {}
+                }
+            }
+            GameStatus::Lobby => {
+                match event {
+                    GameEvent::Start => {
+                        *self = GameStatus::Playing;
+                    }
+                    GameEvent::End => {
+                        *self = GameStatus::GameOver;
+                    }
                     _ => {}
                 }
             }
@@ -52,9 +113,17 @@ impl GameStatus {
                     GameEvent::Start => {
                         *self = GameStatus::Playing;
                     }
+                    GameEvent::Wait => {
+                        *self = GameStatus::Lobby;
+                    }
                     _ => {}
                 }
             }
+            GameStatus::Editing => {
+                if let GameEvent::Start = event {
+                    *self = GameStatus::Playing;
+                }
+            }
         }
     }
 }
\ No newline at end of file
@@ -0,0 +1,79 @@
+/// How many garbage lines are sent to the opponent for clearing a number of lines at once
+/// single: 0, double: 1, triple: 2, tetris: 4
+pub fn attack(lines: usize) -> u16 {
+    match lines {
+        0 | 1 => 0,
+        2 => 1,
+        3 => 2,
+        _ => 4,
+    }
+}
+
+/// keeps track of the garbage lines that are waiting to be added to a player's board
+#[derive(Default)]
+pub struct GarbageMeter {
+    pending: u16,
+}
+
+impl GarbageMeter {
+    /// Adds lines sent by the opponent to the meter
+    pub fn receive(&mut self, lines: u16) {
+        self.pending += lines;
+    }
+
+    /// Uses an attack to cancel pending garbage first
+    /// Returns what is left of the attack, which should be sent to the opponent
+    pub fn offset(&mut self, attack: u16) -> u16 {
+        let cancelled = attack.min(self.pending);
+        self.pending -= cancelled;
+        attack - cancelled
+    }
+
+    /// Empties the meter and returns how many lines should be added to the board
+    pub fn take(&mut self) -> u16 {
+        std::mem::take(&mut self.pending)
+    }
+
+    /// Returns the amount of lines that are waiting
+    pub fn pending(&self) -> u16 {
+        self.pending
+    }
+}
+
+#[cfg(test)]
+mod garbage_tests {
+    use super::*;
+
+    #[test]
+    fn test_attack() {
+        assert_eq!(attack(0), 0);
+        assert_eq!(attack(1), 0);
+        assert_eq!(attack(2), 1);
+        assert_eq!(attack(3), 2);
+        assert_eq!(attack(4), 4);
+    }
+
+    #[test]
+    fn test_offset() {
+        let mut meter = GarbageMeter::default();
+        meter.receive(3);
+
+        // the attack is used up to cancel garbage
+        assert_eq!(meter.offset(2), 0);
+        assert_eq!(meter.pending(), 1);
+
+        // whatever is left over is sent on
+        assert_eq!(meter.offset(4), 3);
+        assert_eq!(meter.pending(), 0);
+    }
+
+    #[test]
+    fn test_take() {
+        let mut meter = GarbageMeter::default();
+        meter.receive(2);
+        meter.receive(1);
+
+        assert_eq!(meter.take(), 3);
+        assert_eq!(meter.pending(), 0);
+    }
+}
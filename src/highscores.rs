@@ -0,0 +1,117 @@
+use std::fs::{self, File};
+use std::io::{self, BufReader};
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::player::Player;
+
+/// where the best single player games are kept
+pub const HIGH_SCORES_FILE: &str = "highscores.json";
+/// how many games are kept
+const KEPT: usize = 10;
+
+/// One finished game
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct HighScore {
+    pub score: u16,
+    pub lines: usize,
+    pub level: usize,
+    /// how many seconds the game went on
+    pub time: f64,
+    pub pps: f64,
+}
+
+impl From<&Player> for HighScore {
+    fn from(player: &Player) -> HighScore {
+        HighScore {
+            score: player.score,
+            lines: player.stats.lines,
+            level: player.stats.level(),
+            time: player.stats.time,
+            pps: player.stats.pps(),
+        }
+    }
+}
+
+/// The best games so far, the highest score first, kept in a json file
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct HighScores {
+    path: PathBuf,
+    scores: Vec<HighScore>,
+}
+
+impl HighScores {
+    /// Reads the scores in the file, a file that isn't there or can't be read has no scores yet
+    pub fn load<P: AsRef<Path>>(path: P) -> HighScores {
+        let scores = File::open(&path).ok()
+            .and_then(|file| serde_json::from_reader(BufReader::new(file)).ok())
+            .unwrap_or_default();
+        HighScores { path: path.as_ref().to_path_buf(), scores }
+    }
+
+    /// Returns the scores, the highest first
+    pub fn scores(&self) -> &[HighScore] {
+        &self.scores
+    }
+
+    /// Adds a game, returns its place when it is good enough to be kept
+    /// Games without a score are not kept
+    pub fn add(&mut self, score: HighScore) -> Option<usize> {
+        if score.score == 0 {
+            return None;
+        }
+        // the faster game goes first when the scores are the same
+        let place = self.scores.iter()
+            .position(|other| (other.score, -other.time) < (score.score, -score.time))
+            .unwrap_or(self.scores.len());
+        if place >= KEPT {
+            return None;
+        }
+        self.scores.insert(place, score);
+        self.scores.truncate(KEPT);
+        Some(place)
+    }
+
+    /// Writes the scores to the file they were read from
+    pub fn save(&self) -> io::Result<()> {
+        fs::write(&self.path, serde_json::to_string_pretty(&self.scores)?)
+    }
+}
+
+#[cfg(test)]
+mod highscores_tests {
+    use super::*;
+
+    fn game(score: u16, time: f64) -> HighScore {
+        HighScore { score, lines: score as usize, level: 1, time, pps: 1.0 }
+    }
+
+    #[test]
+    fn test_add() {
+        let mut scores = HighScores::default();
+        assert_eq!(scores.add(game(5, 60.0)), Some(0));
+        assert_eq!(scores.add(game(8, 60.0)), Some(0));
+        assert_eq!(scores.add(game(5, 30.0)), Some(1));
+        assert_eq!(scores.add(game(0, 10.0)), None);
+        assert_eq!(scores.scores().iter().map(|score| score.time).collect::<Vec<_>>(), [60.0, 30.0, 60.0]);
+
+        for _ in 0..KEPT {
+            scores.add(game(20, 60.0));
+        }
+        assert_eq!(scores.scores().len(), KEPT);
+        assert_eq!(scores.add(game(1, 60.0)), None);
+    }
+
+    #[test]
+    fn test_save() {
+        let path = std::env::temp_dir().join("rust_tetris_test_highscores.json");
+        let _ = fs::remove_file(&path);
+        let mut scores = HighScores::load(&path);
+        assert!(scores.scores().is_empty());
+
+        scores.add(game(3, 20.0));
+        scores.save().unwrap();
+        assert_eq!(HighScores::load(&path), scores);
+    }
+}
@@ -0,0 +1,30 @@
+pub mod game;
+pub mod board;
+pub mod renderer;
+pub mod blockshape;
+pub mod block;
+pub mod blockcolor;
+pub mod gamestate;
+pub mod audio;
+pub mod player;
+pub mod queue;
+pub mod garbage;
+pub mod net;
+pub mod online;
+pub mod spectator;
+pub mod bot;
+pub mod tbp;
+pub mod finesse;
+pub mod fumen;
+pub mod editor;
+pub mod puzzle;
+pub mod solver;
+pub mod pieces;
+pub mod theme;
+pub mod accessibility;
+pub mod effects;
+pub mod particles;
+pub mod menu;
+pub mod highscores;
+pub mod settings;
+pub mod replay;
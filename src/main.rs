@@ -1,14 +1,338 @@
-mod game;
-mod board;
-mod renderer;
-mod blockshape;
-mod block;
-mod blockcolor;
-mod gamestate;
-mod audio;
-
-/// This is the entry point for the application, which creates a new game instance and starts its game loop
+use std::path::PathBuf;
+use std::process::Command;
+
+use clap::error::ErrorKind;
+use clap::{ArgGroup, CommandFactory, Parser};
+use rayon::prelude::*;
+use rust_tetris::{
+    accessibility::ColorVision,
+    blockcolor::ColorPolicy,
+    board::Size,
+    bot::{Bot, Weights},
+    editor, fumen, game,
+    gamestate::GameMode,
+    net,
+    pieces::{PieceSet, RotationSystem},
+    player::{TopOut, MAX_LEVEL},
+    puzzle,
+    queue::Randomizer,
+    replay::{self, Replay},
+    settings::{Settings, SETTINGS_FILE},
+    tbp, theme,
+};
+
+/// the address used when hosting or joining without giving one
+const DEFAULT_ADDRESS: &str = "127.0.0.1:7777";
+/// the address spectators connect to when none is given
+const DEFAULT_SPECTATE_ADDRESS: &str = "127.0.0.1:7878";
+
+/// A game of tetris to play alone, against a bot, against a friend or online
+///
+/// The game starts with the settings in settings.json, which the menu changes, and the flags go over them
+#[derive(Parser)]
+#[command(name = "tetris", version)]
+#[command(group(ArgGroup::new("start").args(["host", "join", "tbp", "fumen", "puzzles", "edit", "replay", "mode"])))]
+struct Cli {
+    /// Start in a mode right away instead of the menu: single, versus, bot, demo or finesse
+    #[arg(long)]
+    mode: Option<GameMode>,
+    /// Host an online game
+    #[arg(long, value_name = "ADDRESS", num_args = 0..=1, default_missing_value = DEFAULT_ADDRESS)]
+    host: Option<String>,
+    /// Join an online game
+    #[arg(long, value_name = "ADDRESS", num_args = 0..=1, default_missing_value = DEFAULT_ADDRESS)]
+    join: Option<String>,
+    /// The name others see online
+    #[arg(long, default_value = "player", value_parser = clap::builder::NonEmptyStringValueParser::new())]
+    name: String,
+    /// Let others watch the game
+    #[arg(long, value_name = "ADDRESS", num_args = 0..=1, default_missing_value = DEFAULT_SPECTATE_ADDRESS)]
+    spectate: Option<String>,
+    /// Watch a bot that speaks the Tetris Bot Protocol play, started with the command
+    #[arg(long, value_name = "COMMAND")]
+    tbp: Option<String>,
+    /// Practice on the boards of a fumen code, a link to fumen works as well
+    #[arg(long, value_name = "CODE", value_parser = parse_fumen)]
+    fumen: Option<Vec<editor::Position>>,
+    /// Play a pack of puzzles, like assets/puzzles.json
+    #[arg(long, value_name = "FILE")]
+    puzzles: Option<PathBuf>,
+    /// Open the editor on a saved position
+    #[arg(long, value_name = "FILE", num_args = 0..=1, default_missing_value = editor::DEFAULT_POSITION_FILE)]
+    edit: Option<String>,
+    /// Play the pieces of the seed, the same seed always gives the same pieces
+    #[arg(long)]
+    seed: Option<u64>,
+    /// The level games start on, the blocks fall faster on every level
+    #[arg(long, value_parser = parse_level)]
+    level: Option<usize>,
+    /// Play on a board of another size, written as <width>x<height>[+hidden] like 10x20, with hidden rows above it to spawn in
+    #[arg(long)]
+    size: Option<Size>,
+    /// What ends the game: block, lock or partial
+    #[arg(long)]
+    top_out: Option<TopOut>,
+    /// How many seconds cleared rows are shown
+    #[arg(long, value_name = "SECONDS", value_parser = parse_seconds)]
+    line_clear_delay: Option<f64>,
+    /// How many seconds the next block takes to spawn
+    #[arg(long, value_name = "SECONDS", value_parser = parse_seconds)]
+    entry_delay: Option<f64>,
+    /// Play with the pieces of a piece set, like assets/pentominoes.json
+    #[arg(long, value_name = "FILE", value_parser = load_pieces)]
+    pieces: Option<PieceSet>,
+    /// How the pieces are picked: classic, bag or random
+    #[arg(long)]
+    randomizer: Option<Randomizer>,
+    /// What a piece tries when it can't rotate where it is: classic or kicks
+    #[arg(long)]
+    rotation: Option<RotationSystem>,
+    /// How the pieces are colored: guideline, cycle or random
+    #[arg(long)]
+    colors: Option<ColorPolicy>,
+    /// Change how the game looks, a name like dark for assets/themes/dark or a directory
+    #[arg(long, value_name = "THEME", value_parser = find_theme)]
+    theme: Option<PathBuf>,
+    /// Block colors that color blind players can tell apart: deuteranopia, protanopia or tritanopia
+    #[arg(long)]
+    vision: Option<ColorVision>,
+    /// Draw a pattern in every block that tells its color without seeing it
+    #[arg(long)]
+    patterns: bool,
+    /// Turn off the bursts of cleared rows and the streaks of hard drops
+    #[arg(long)]
+    no_particles: bool,
+    /// Stop the screen from shaking on a tetris
+    #[arg(long)]
+    no_shake: bool,
+    /// Start with the window filling the screen, F11 switches it while playing
+    #[arg(long)]
+    fullscreen: bool,
+    /// Give the built-in bot the weights found by tetris-train
+    #[arg(long, value_name = "FILE", value_parser = load_weights)]
+    weights: Option<Weights>,
+    /// Read and save the settings in this file instead of settings.json
+    #[arg(long, value_name = "FILE")]
+    config: Option<PathBuf>,
+    /// Watch a recorded game, it plays with the rules it was recorded with
+    #[arg(long, value_name = "FILE", value_parser = load_replay,
+        conflicts_with_all = ["seed", "level", "size", "top_out", "line_clear_delay", "entry_delay", "pieces", "randomizer", "rotation"])]
+    replay: Option<Replay>,
+    /// Record the next single player game to watch it again with --replay
+    #[arg(long, value_name = "FILE", conflicts_with_all = ["pieces", "replay", "headless"])]
+    record: Option<PathBuf>,
+    /// Let the built-in bot play seeded games without a window and print how they went, or play a replay through with --replay
+    #[arg(long, conflicts_with_all = ["host", "join", "spectate", "tbp", "fumen", "puzzles", "edit", "mode", "fullscreen"])]
+    headless: bool,
+    /// How many games the bot plays without a window, every game gets the next seed
+    #[arg(long, default_value_t = 1, requires = "headless", value_parser = clap::value_parser!(u64).range(1..))]
+    games: u64,
+    /// The amount of pieces after which a game without a window is stopped
+    #[arg(long, default_value_t = 1000, requires = "headless", value_parser = clap::builder::RangedU64ValueParser::<usize>::new().range(1..))]
+    max_pieces: usize,
+}
+
+impl Cli {
+    /// Puts the flags that were given over the settings
+    fn settings(&self, mut settings: Settings) -> Settings {
+        if let Some(size) = self.size {
+            settings.size = size;
+        }
+        if let Some(top_out) = self.top_out {
+            settings.top_out = top_out;
+        }
+        if let Some(seconds) = self.line_clear_delay {
+            settings.delays.line_clear = seconds;
+        }
+        if let Some(seconds) = self.entry_delay {
+            settings.delays.entry = seconds;
+        }
+        if let Some(randomizer) = self.randomizer {
+            settings.randomizer = randomizer;
+        }
+        if let Some(rotation) = self.rotation {
+            settings.rotation = rotation;
+        }
+        if let Some(colors) = self.colors {
+            settings.colors = colors;
+        }
+        if let Some(theme) = &self.theme {
+            settings.theme = theme.clone();
+        }
+        if let Some(vision) = self.vision {
+            settings.accessibility.vision = vision;
+        }
+        settings.accessibility.patterns |= self.patterns;
+        settings.particles &= !self.no_particles;
+        settings.shake &= !self.no_shake;
+        settings.fullscreen |= self.fullscreen;
+        settings
+    }
+}
+
+/// This is the entry point for the application, which reads the flags, creates a new game instance and starts its game loop
+/// See tetris --help for the flags
 fn main() {
-    let mut game = game::Game::new();
+    let cli = Cli::parse();
+    if let (Some(_), Some(mode)) = (&cli.record, cli.mode.filter(|mode| *mode != GameMode::Single)) {
+        Cli::command().error(ErrorKind::ArgumentConflict, format!("only single player games can be recorded, not {:?}", mode)).exit();
+    }
+    // the protocol only knows the seven usual pieces
+    if cli.tbp.is_some() && cli.pieces.as_ref().is_some_and(PieceSet::has_custom) {
+        Cli::command().error(ErrorKind::ArgumentConflict, "bots that speak the Tetris Bot Protocol can't play with custom pieces").exit();
+    }
+
+    let settings = cli.settings(match &cli.config {
+        Some(path) => Settings::load(path).unwrap_or_else(|error| fail(format!("could not load the settings: {}", error))),
+        None => Settings::load(SETTINGS_FILE).unwrap_or_else(|error| {
+            eprintln!("Could not load the settings, the defaults are used: {}", error);
+            Settings::default()
+        }),
+    });
+    if cli.tbp.is_some() {
+        if let Err(error) = tbp::check_size(settings.size) {
+            Cli::command().error(ErrorKind::ArgumentConflict, error).exit();
+        }
+    }
+
+    if cli.headless {
+        run_headless(&cli, &settings);
+        return;
+    }
+
+    let mut game = if let Some(address) = &cli.host {
+        game::Game::online(net::Network::host(address, &cli.name).unwrap_or_else(|error| fail(format!("could not host a game: {}", error))), settings)
+    } else if let Some(address) = &cli.join {
+        game::Game::online(net::Network::join(address, &cli.name).unwrap_or_else(|error| fail(format!("could not join the game: {}", error))), settings)
+    } else if let Some(command) = &cli.tbp {
+        let mut parts = command.split_whitespace();
+        let Some(program) = parts.next() else {
+            Cli::command().error(ErrorKind::InvalidValue, "--tbp needs the command that starts the bot").exit()
+        };
+        let mut command = Command::new(program);
+        command.args(parts);
+        game::Game::demo(Box::new(tbp::ExternalBot::spawn(command).unwrap_or_else(|error| fail(format!("could not start the bot: {}", error)))), settings)
+    } else if let Some(positions) = &cli.fumen {
+        game::Game::practice(positions.clone(), settings)
+    } else if let Some(path) = &cli.puzzles {
+        game::Game::puzzles(puzzle::Puzzles::load(path).unwrap_or_else(|error| fail(format!("could not load the puzzles: {}", error))), settings)
+    } else if let Some(path) = &cli.edit {
+        game::Game::editor(path, settings)
+    } else {
+        game::Game::new(settings)
+    };
+
+    if let Some(path) = &cli.config {
+        game.set_settings_file(path);
+    }
+    game.set_pieces(cli.pieces.clone().unwrap_or_else(PieceSet::standard));
+    if let Some(level) = cli.level {
+        game.set_level(level);
+    }
+    if let Some(seed) = cli.seed {
+        game.set_seed(seed);
+    }
+    if let Some(weights) = cli.weights {
+        game.set_weights(weights);
+    }
+    if let Some(address) = &cli.spectate {
+        game.enable_spectators(address).unwrap_or_else(|error| fail(format!("could not start the spectator server: {}", error)));
+    }
+    if let Some(path) = &cli.record {
+        game.record_to(path);
+    }
+    if let Some(mode) = cli.mode {
+        game.play(mode);
+    } else if let Some(replay) = cli.replay.clone() {
+        game.watch(replay);
+    }
     game.start_loop()
 }
+
+/// Plays a replay through, or lets the built-in bot play seeded games, without a window and prints how it went
+fn run_headless(cli: &Cli, settings: &Settings) {
+    if let Some(replay) = &cli.replay {
+        let (player, topped_out) = replay.play();
+        let stats = &player.stats;
+        println!(
+            "{} lines, {} pieces, level {}, {:.2} seconds, {:.2} pieces per second, {}",
+            stats.lines,
+            stats.pieces,
+            stats.level(),
+            stats.time,
+            stats.pps(),
+            if topped_out { "topped out" } else { "did not top out" },
+        );
+        return;
+    }
+
+    let pieces = cli.pieces.clone().unwrap_or_else(PieceSet::standard);
+    let level = cli.level.unwrap_or(1);
+    let first_seed = cli.seed.unwrap_or_else(rand::random);
+    let bot = Bot::new(cli.weights.unwrap_or_default());
+    let results: Vec<_> = (0..cli.games)
+        .into_par_iter()
+        .map(|game| {
+            let seed = first_seed.wrapping_add(game);
+            (seed, bot.play(replay::player(settings, &pieces, seed, level), cli.max_pieces))
+        })
+        .collect();
+
+    for (seed, result) in &results {
+        println!("seed {}: {} lines, {} pieces", seed, result.lines, result.pieces);
+    }
+    let games = results.len() as f64;
+    println!(
+        "average over {} games: {:.1} lines, {:.1} pieces",
+        results.len(),
+        results.iter().map(|(_, result)| result.lines).sum::<usize>() as f64 / games,
+        results.iter().map(|(_, result)| result.pieces).sum::<usize>() as f64 / games,
+    );
+}
+
+/// Stops with an error the same way a bad flag does
+fn fail(message: String) -> ! {
+    Cli::command().error(ErrorKind::Io, message).exit()
+}
+
+/// Reads the level games start on, from 1 up to the highest level
+fn parse_level(text: &str) -> Result<usize, String> {
+    match text.parse::<usize>() {
+        Ok(level) if (1..=MAX_LEVEL).contains(&level) => Ok(level),
+        _ => Err(format!("{} is not a level, use 1 to {}", text, MAX_LEVEL)),
+    }
+}
+
+/// Reads an amount of seconds a delay takes, no delay at all is 0
+fn parse_seconds(text: &str) -> Result<f64, String> {
+    match text.parse::<f64>() {
+        Ok(seconds) if seconds >= 0.0 && seconds.is_finite() => Ok(seconds),
+        _ => Err(format!("{} is not an amount of seconds", text)),
+    }
+}
+
+/// Reads every page of a fumen code into the positions to practice on
+fn parse_fumen(code: &str) -> Result<Vec<editor::Position>, String> {
+    let pages = fumen::decode(code).map_err(|error| format!("could not read the fumen code: {}", error))?;
+    Ok(pages.iter().map(editor::Position::from).collect())
+}
+
+/// Reads the piece set in the file
+fn load_pieces(path: &str) -> Result<PieceSet, String> {
+    PieceSet::load(path).map_err(|error| format!("could not load the pieces: {}", error))
+}
+
+/// Finds the directory of a theme given by its name or its directory
+fn find_theme(name: &str) -> Result<PathBuf, String> {
+    theme::Theme::named(name).map(|theme| theme.directory).map_err(|error| format!("could not load the theme: {}", error))
+}
+
+/// Reads the weights of the built-in bot in the file
+fn load_weights(path: &str) -> Result<Weights, String> {
+    Weights::load(path).map_err(|error| format!("could not load the weights: {}", error))
+}
+
+/// Reads a recorded game
+fn load_replay(path: &str) -> Result<Replay, String> {
+    Replay::load(path).map_err(|error| format!("could not load the replay: {}", error))
+}
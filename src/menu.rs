@@ -0,0 +1,299 @@
+use piston_window::{Button, ControllerButton, Context, G2d, Glyphs, HatState, Key};
+
+use crate::{
+    gamestate::GameMode,
+    renderer::{self, Renderer},
+};
+
+/// the space between the lines of a menu
+const LINE_HEIGHT: f64 = 26.0;
+/// how wide the panel behind a menu is
+const PANEL_WIDTH: f64 = 240.0;
+/// the gamepad buttons that pick an item and go back, the bottom and right face buttons on most gamepads
+const SELECT_BUTTON: u8 = 0;
+const BACK_BUTTON: u8 = 1;
+
+/// The screens of the menu
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Screen {
+    Main,
+    Modes,
+    Settings,
+    Gameplay,
+    Handling,
+    Audio,
+    Video,
+    Controls,
+    HighScores,
+    Pause,
+}
+
+impl Screen {
+    /// Returns the title drawn above the items of the screen
+    pub fn title(self) -> &'static str {
+        match self {
+            Screen::Main => "Tetris",
+            Screen::Modes => "Modes",
+            Screen::Settings => "Settings",
+            Screen::Gameplay => "Gameplay",
+            Screen::Handling => "Handling",
+            Screen::Audio => "Audio",
+            Screen::Video => "Video",
+            Screen::Controls => "Controls",
+            Screen::HighScores => "High scores",
+            Screen::Pause => "Paused",
+        }
+    }
+}
+
+/// A setting that can be changed from the menu, left and right go through its values
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Setting {
+    Width,
+    Height,
+    TopOut,
+    Colors,
+    Randomizer,
+    Rotation,
+    LineClearDelay,
+    EntryDelay,
+    Das,
+    Arr,
+    Sdf,
+    EffectsVolume,
+    MenuVolume,
+    Theme,
+    Fullscreen,
+    Particles,
+    Shake,
+    Vision,
+    Patterns,
+}
+
+/// What happens when an item is picked
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Action {
+    /// the item only shows something
+    Nothing,
+    /// starts the game in the mode it was set up in
+    Play,
+    /// starts a new game in the mode
+    Mode(GameMode),
+    Editor,
+    Open(Screen),
+    /// goes to the screen before, or leaves the menu from its first screen
+    Back,
+    Resume,
+    Restart,
+    /// ends the game and goes to the main menu
+    MainMenu,
+    CopyFumen,
+    /// closes the game
+    Exit,
+    /// changes the setting a step back or forward
+    Change(Setting, i8),
+    /// waits for the key to bind the group of controls at the index to
+    Bind(usize),
+}
+
+/// One line of a menu
+#[derive(Debug, Clone, PartialEq)]
+pub struct Item {
+    pub label: String,
+    pub action: Action,
+}
+
+impl Item {
+    pub fn new<S: Into<String>>(label: S, action: Action) -> Item {
+        Item { label: label.into(), action }
+    }
+}
+
+/// The inputs that move through a menu
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MenuInput {
+    Up,
+    Down,
+    Left,
+    Right,
+    Select,
+    Back,
+}
+
+impl MenuInput {
+    /// The arrow keys or WASD move, Enter or Space picks and Escape or Backspace goes back
+    pub fn from_key(key: &Key) -> Option<MenuInput> {
+        match key {
+            Key::Up | Key::W => Some(MenuInput::Up),
+            Key::Down | Key::S => Some(MenuInput::Down),
+            Key::Left | Key::A => Some(MenuInput::Left),
+            Key::Right | Key::D => Some(MenuInput::Right),
+            Key::Return | Key::Space => Some(MenuInput::Select),
+            Key::Escape | Key::Backspace => Some(MenuInput::Back),
+            _ => None,
+        }
+    }
+
+    /// The pad of a gamepad moves and its face buttons pick and go back
+    pub fn from_button(button: &Button) -> Option<MenuInput> {
+        match button {
+            Button::Hat(hat) => match hat.state {
+                HatState::Up => Some(MenuInput::Up),
+                HatState::Down => Some(MenuInput::Down),
+                HatState::Left => Some(MenuInput::Left),
+                HatState::Right => Some(MenuInput::Right),
+                _ => None,
+            },
+            Button::Controller(ControllerButton { button: SELECT_BUTTON, .. }) => Some(MenuInput::Select),
+            Button::Controller(ControllerButton { button: BACK_BUTTON, .. }) => Some(MenuInput::Back),
+            _ => None,
+        }
+    }
+}
+
+/// The screens that were opened, the last one is shown, with the item that is selected on each
+#[derive(Debug, Clone, PartialEq)]
+pub struct Menu {
+    stack: Vec<(Screen, usize)>,
+}
+
+impl Menu {
+    /// Creates a menu that starts on the screen
+    pub fn new(screen: Screen) -> Menu {
+        Menu { stack: vec![(screen, 0)] }
+    }
+
+    /// Returns the screen that is shown
+    pub fn screen(&self) -> Screen {
+        self.stack.last().map_or(Screen::Main, |(screen, _)| *screen)
+    }
+
+    /// Returns the index of the selected item, within the given amount of items
+    pub fn selected(&self, items: usize) -> usize {
+        self.stack.last().map_or(0, |(_, selected)| *selected).min(items.saturating_sub(1))
+    }
+
+    /// Moves through the items of the screen that is shown
+    /// Screens are opened and closed here, everything else is returned for the game to do
+    pub fn input(&mut self, input: MenuInput, items: &[Item]) -> Option<Action> {
+        if items.is_empty() {
+            return None;
+        }
+        let selected = self.selected(items.len());
+        let action = items[selected].action;
+
+        match input {
+            MenuInput::Up | MenuInput::Down => {
+                let step = if input == MenuInput::Up { items.len() - 1 } else { 1 };
+                // items that only show something are skipped
+                let mut next = selected;
+                for _ in 0..items.len() {
+                    next = (next + step) % items.len();
+                    if items[next].action != Action::Nothing {
+                        break;
+                    }
+                }
+                if let Some(top) = self.stack.last_mut() {
+                    top.1 = next;
+                }
+                None
+            },
+            MenuInput::Left | MenuInput::Right => match action {
+                Action::Change(setting, _) => Some(Action::Change(setting, if input == MenuInput::Left { -1 } else { 1 })),
+                _ => None,
+            },
+            MenuInput::Select => match action {
+                Action::Open(screen) => {
+                    self.stack.push((screen, 0));
+                    None
+                },
+                Action::Back => self.back(),
+                Action::Nothing => None,
+                action => Some(action),
+            },
+            MenuInput::Back => self.back(),
+        }
+    }
+
+    /// Goes to the screen before, on the first screen Back is returned for the game to leave the menu
+    fn back(&mut self) -> Option<Action> {
+        if self.stack.len() > 1 {
+            self.stack.pop();
+            None
+        } else {
+            Some(Action::Back)
+        }
+    }
+
+    /// Draws the title and the items of the screen on a panel, the selected item is marked
+    pub fn draw(&self, items: &[Item], position: [f64; 2], renderer: &Renderer, glyphs: &mut Glyphs, context: &Context, g2d: &mut G2d) {
+        let [x, y] = position;
+        let [red, green, blue, _] = renderer.theme().background;
+        let height = LINE_HEIGHT * (items.len() + 1) as f64 + LINE_HEIGHT / 2.0;
+        renderer::draw_rect([red, green, blue, 0.85], x - 10.0, y - LINE_HEIGHT, PANEL_WIDTH, height, context, g2d);
+
+        renderer.draw_text(self.screen().title(), x, y, glyphs, context, g2d);
+        let selected = self.selected(items.len());
+        for (index, item) in items.iter().enumerate() {
+            let marker = if index == selected { "> " } else { "   " };
+            let y = y + LINE_HEIGHT * (index + 1) as f64;
+            renderer.draw_text(&format!("{}{}", marker, item.label), x, y, glyphs, context, g2d);
+        }
+    }
+}
+
+/// Returns the value a step before or after the current one, going around at the ends
+pub fn cycle<T: PartialEq + Copy>(values: &[T], current: T, step: i8) -> T {
+    let index = values.iter().position(|value| *value == current).unwrap_or(0) as isize;
+    values[(index + step as isize).rem_euclid(values.len() as isize) as usize]
+}
+
+#[cfg(test)]
+mod menu_tests {
+    use super::*;
+
+    fn items() -> Vec<Item> {
+        vec![
+            Item::new("Play", Action::Play),
+            Item::new("Scores", Action::Nothing),
+            Item::new("Settings", Action::Open(Screen::Settings)),
+            Item::new("Volume", Action::Change(Setting::EffectsVolume, 0)),
+        ]
+    }
+
+    #[test]
+    fn test_navigation() {
+        let mut menu = Menu::new(Screen::Main);
+        let items = items();
+
+        // going up from the first item goes around to the last one, and items that only show something are skipped
+        assert_eq!(menu.input(MenuInput::Up, &items), None);
+        assert_eq!(menu.selected(items.len()), 3);
+        assert_eq!(menu.input(MenuInput::Left, &items), Some(Action::Change(Setting::EffectsVolume, -1)));
+        menu.input(MenuInput::Down, &items);
+        menu.input(MenuInput::Down, &items);
+        assert_eq!(menu.selected(items.len()), 2);
+
+        // screens are opened and closed by the menu itself
+        assert_eq!(menu.input(MenuInput::Select, &items), None);
+        assert_eq!(menu.screen(), Screen::Settings);
+        assert_eq!(menu.input(MenuInput::Back, &items), None);
+        assert_eq!(menu.screen(), Screen::Main);
+        assert_eq!(menu.input(MenuInput::Back, &items), Some(Action::Back));
+
+        menu.input(MenuInput::Down, &items);
+        menu.input(MenuInput::Down, &items);
+        assert_eq!(menu.input(MenuInput::Select, &items), Some(Action::Play));
+    }
+
+    #[test]
+    fn test_inputs() {
+        assert_eq!(MenuInput::from_key(&Key::Return), Some(MenuInput::Select));
+        assert_eq!(MenuInput::from_key(&Key::P), None);
+        let button = Button::Controller(ControllerButton { id: 0, button: BACK_BUTTON });
+        assert_eq!(MenuInput::from_button(&button), Some(MenuInput::Back));
+
+        assert_eq!(cycle(&[1, 2, 3], 1, -1), 3);
+        assert_eq!(cycle(&[1, 2, 3], 3, 1), 1);
+    }
+}
@@ -0,0 +1,411 @@
+use std::collections::HashMap;
+use std::io::{self, ErrorKind, Read, Write};
+use std::net::{TcpListener, TcpStream, ToSocketAddrs};
+use std::time::{Duration, Instant};
+
+use piston_window::types::Color;
+use serde::{Deserialize, Serialize};
+
+/// the id of the instance that hosts the game, clients get their id from the host
+pub const HOST_ID: usize = 0;
+/// how often the latency to other instances is measured
+const PING_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Messages that are sent between instances of the game, every message is one line of json
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Message {
+    /// the first message a client sends to the host
+    Hello { name: String },
+    /// the host tells a client which id it got
+    Welcome { id: usize },
+    /// a player is in the lobby
+    Joined { id: usize, name: String },
+    /// a player disconnected
+    Left { id: usize },
+    /// a player is ready or not ready to start
+    Ready { ready: bool },
+    /// the host starts the game
+    Start,
+    /// the cells of a player's board row by row, how many cells there are in a row and their score
+    Board { cells: Vec<Option<Color>>, width: usize, score: u16 },
+    /// garbage lines sent to everyone else
+    Attack { lines: u16 },
+    /// a player had no room left for a new block
+    ToppedOut,
+    /// used to measure latency, time is in milliseconds since the sender started
+    Ping { time: u64 },
+    /// the answer to a ping with the same time
+    Pong { time: u64 },
+}
+
+/// A message together with the id of the player it came from
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct Packet {
+    pub from: usize,
+    pub message: Message,
+}
+
+/// Things that happened on the network since the last poll
+pub enum NetEvent {
+    Joined { id: usize, name: String },
+    Left { id: usize },
+    Message(Packet),
+    /// the connection to the host was lost
+    Disconnected,
+}
+
+/// a non-blocking tcp connection that sends and receives packets line by line
+struct Connection {
+    stream: TcpStream,
+    incoming: Vec<u8>,
+    outgoing: Vec<u8>,
+}
+
+impl Connection {
+    /// Wraps a stream and makes it non-blocking so it can be polled every frame
+    fn new(stream: TcpStream) -> io::Result<Connection> {
+        stream.set_nonblocking(true)?;
+        stream.set_nodelay(true)?;
+
+        Ok(Connection { stream, incoming: Vec::new(), outgoing: Vec::new() })
+    }
+
+    /// Queues a packet and writes as much as the socket accepts
+    fn send(&mut self, packet: &Packet) -> io::Result<()> {
+        serde_json::to_writer(&mut self.outgoing, packet)?;
+        self.outgoing.push(b'\n');
+        self.flush()
+    }
+
+    /// Writes queued data until the socket would block
+    fn flush(&mut self) -> io::Result<()> {
+        while !self.outgoing.is_empty() {
+            match self.stream.write(&self.outgoing) {
+                Ok(0) => return Err(ErrorKind::WriteZero.into()),
+                Ok(written) => { self.outgoing.drain(..written); },
+                Err(error) if error.kind() == ErrorKind::WouldBlock => break,
+                Err(error) => return Err(error),
+            }
+        }
+        Ok(())
+    }
+
+    /// Reads everything that has arrived and returns the complete packets
+    /// Returns an error when the other side closed the connection
+    fn receive(&mut self) -> io::Result<Vec<Packet>> {
+        self.flush()?;
+
+        let mut buffer = [0; 4096];
+        loop {
+            match self.stream.read(&mut buffer) {
+                Ok(0) => return Err(ErrorKind::ConnectionAborted.into()),
+                Ok(read) => self.incoming.extend_from_slice(&buffer[..read]),
+                Err(error) if error.kind() == ErrorKind::WouldBlock => break,
+                Err(error) => return Err(error),
+            }
+        }
+
+        let mut packets = Vec::new();
+        while let Some(end) = self.incoming.iter().position(|byte| *byte == b'\n') {
+            let line: Vec<u8> = self.incoming.drain(..=end).collect();
+            packets.push(serde_json::from_slice(&line)?);
+        }
+        Ok(packets)
+    }
+}
+
+/// a client connected to the host
+struct Peer {
+    id: usize,
+    name: Option<String>,
+    connection: Connection,
+}
+
+/// the host listens for clients and passes their messages on to each other, clients only talk to the host
+enum Role {
+    Host { listener: TcpListener, peers: Vec<Peer>, next_id: usize },
+    Client { host: Option<Connection> },
+}
+
+/// The network side of an online game
+pub struct Network {
+    role: Role,
+    /// the id of this instance, the host always has HOST_ID
+    pub id: usize,
+    pub name: String,
+    started: Instant,
+    last_ping: Instant,
+    /// the round trip time in milliseconds to every directly connected instance
+    latency: HashMap<usize, u64>,
+}
+
+impl Network {
+    /// Starts listening for clients on the given address
+    pub fn host<A: ToSocketAddrs>(address: A, name: &str) -> io::Result<Network> {
+        let listener = TcpListener::bind(address)?;
+        listener.set_nonblocking(true)?;
+
+        Ok(Network::with_role(Role::Host { listener, peers: Vec::new(), next_id: HOST_ID + 1 }, HOST_ID, name))
+    }
+
+    /// Connects to a host and introduces this player
+    pub fn join<A: ToSocketAddrs>(address: A, name: &str) -> io::Result<Network> {
+        let mut host = Connection::new(TcpStream::connect(address)?)?;
+        host.send(&Packet { from: HOST_ID, message: Message::Hello { name: name.to_string() } })?;
+
+        // the real id is handed out by the host in the welcome message
+        Ok(Network::with_role(Role::Client { host: Some(host) }, HOST_ID, name))
+    }
+
+    fn with_role(role: Role, id: usize, name: &str) -> Network {
+        Network {
+            role,
+            id,
+            name: name.to_string(),
+            started: Instant::now(),
+            last_ping: Instant::now(),
+            latency: HashMap::new(),
+        }
+    }
+
+    /// Returns true if this instance hosts the game
+    pub fn is_host(&self) -> bool {
+        matches!(self.role, Role::Host { .. })
+    }
+
+    /// The round trip time to another player in milliseconds, only known for directly connected players
+    pub fn latency(&self, id: usize) -> Option<u64> {
+        self.latency.get(&id).copied()
+    }
+
+    /// Sends a message to every other player
+    pub fn send(&mut self, message: Message) {
+        let packet = Packet { from: self.id, message };
+
+        match &mut self.role {
+            // a connection that can't be written to is dropped on the next poll when reading fails
+            Role::Host { peers, .. } => {
+                for peer in peers.iter_mut().filter(|peer| peer.name.is_some()) {
+                    let _ = peer.connection.send(&packet);
+                }
+            },
+            Role::Client { host } => {
+                if let Some(connection) = host {
+                    let _ = connection.send(&packet);
+                }
+            },
+        }
+    }
+
+    /// Accepts new clients, reads all incoming messages and measures latency
+    pub fn poll(&mut self) -> Vec<NetEvent> {
+        let now = self.millis();
+        let ping = self.last_ping.elapsed() >= PING_INTERVAL;
+        if ping {
+            self.last_ping = Instant::now();
+        }
+
+        match &mut self.role {
+            Role::Host { listener, peers, next_id } => {
+                Network::accept(listener, peers, next_id);
+                Network::poll_peers(peers, &self.name, &mut self.latency, now, ping)
+            },
+            Role::Client { host } => {
+                let Some(connection) = host else {
+                    return Vec::new();
+                };
+
+                if ping {
+                    let _ = connection.send(&Packet { from: self.id, message: Message::Ping { time: now } });
+                }
+
+                match connection.receive() {
+                    Ok(packets) => {
+                        let mut events = Vec::new();
+                        for packet in packets {
+                            match packet.message {
+                                Message::Welcome { id } => self.id = id,
+                                Message::Joined { id, name } => events.push(NetEvent::Joined { id, name }),
+                                Message::Left { id } => events.push(NetEvent::Left { id }),
+                                Message::Ping { time } => {
+                                    let _ = connection.send(&Packet { from: self.id, message: Message::Pong { time } });
+                                },
+                                Message::Pong { time } => { self.latency.insert(packet.from, now.saturating_sub(time)); },
+                                _ => events.push(NetEvent::Message(packet)),
+                            }
+                        }
+                        events
+                    },
+                    Err(_) => {
+                        *host = None;
+                        vec![NetEvent::Disconnected]
+                    },
+                }
+            },
+        }
+    }
+
+    /// Accepts every client that is waiting to connect
+    fn accept(listener: &TcpListener, peers: &mut Vec<Peer>, next_id: &mut usize) {
+        while let Ok((stream, _)) = listener.accept() {
+            let Ok(mut connection) = Connection::new(stream) else {
+                continue;
+            };
+
+            if connection.send(&Packet { from: HOST_ID, message: Message::Welcome { id: *next_id } }).is_ok() {
+                peers.push(Peer { id: *next_id, name: None, connection });
+                *next_id += 1;
+            }
+        }
+    }
+
+    /// Reads from every client and passes their messages on to the other clients
+    fn poll_peers(peers: &mut Vec<Peer>, host_name: &str, latency: &mut HashMap<usize, u64>, now: u64, ping: bool) -> Vec<NetEvent> {
+        let mut events = Vec::new();
+        let mut relay = Vec::new();
+        let mut welcomed = Vec::new();
+        let mut left = Vec::new();
+
+        for peer in peers.iter_mut() {
+            if ping {
+                let _ = peer.connection.send(&Packet { from: HOST_ID, message: Message::Ping { time: now } });
+            }
+
+            let packets = match peer.connection.receive() {
+                Ok(packets) => packets,
+                Err(_) => {
+                    left.push(peer.id);
+                    continue;
+                },
+            };
+
+            for mut packet in packets {
+                // clients can't be trusted to know their own id yet
+                packet.from = peer.id;
+
+                match packet.message {
+                    Message::Hello { name } => {
+                        peer.name = Some(name.clone());
+                        welcomed.push(peer.id);
+                        events.push(NetEvent::Joined { id: peer.id, name: name.clone() });
+                        relay.push(Packet { from: HOST_ID, message: Message::Joined { id: peer.id, name } });
+                    },
+                    Message::Ping { time } => {
+                        let _ = peer.connection.send(&Packet { from: HOST_ID, message: Message::Pong { time } });
+                    },
+                    Message::Pong { time } => { latency.insert(peer.id, now.saturating_sub(time)); },
+                    _ => {
+                        relay.push(packet.clone());
+                        events.push(NetEvent::Message(packet));
+                    },
+                }
+            }
+        }
+
+        // let new players know who is already in the lobby
+        for id in welcomed {
+            let mut lobby = vec![Packet { from: HOST_ID, message: Message::Joined { id: HOST_ID, name: host_name.to_string() } }];
+            for other in peers.iter().filter(|other| other.id != id) {
+                if let Some(name) = &other.name {
+                    lobby.push(Packet { from: HOST_ID, message: Message::Joined { id: other.id, name: name.clone() } });
+                }
+            }
+
+            if let Some(peer) = peers.iter_mut().find(|peer| peer.id == id) {
+                for packet in &lobby {
+                    let _ = peer.connection.send(packet);
+                }
+            }
+        }
+
+        for id in left {
+            peers.retain(|peer| peer.id != id);
+            latency.remove(&id);
+            events.push(NetEvent::Left { id });
+            relay.push(Packet { from: HOST_ID, message: Message::Left { id } });
+        }
+
+        for packet in relay {
+            for peer in peers.iter_mut() {
+                if peer.id != packet.from && peer.name.is_some() {
+                    let _ = peer.connection.send(&packet);
+                }
+            }
+        }
+
+        events
+    }
+
+    /// milliseconds since the network was started
+    fn millis(&self) -> u64 {
+        self.started.elapsed().as_millis() as u64
+    }
+}
+
+#[cfg(test)]
+mod net_tests {
+    use super::*;
+    use std::thread;
+
+    /// polls until the network returned at least one event or gives up after a second
+    fn poll_until_event(network: &mut Network) -> Vec<NetEvent> {
+        for _ in 0..100 {
+            let events = network.poll();
+            if !events.is_empty() {
+                return events;
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+        Vec::new()
+    }
+
+    #[test]
+    fn test_message_format() {
+        let packet = Packet { from: 1, message: Message::Attack { lines: 4 } };
+        let json = serde_json::to_string(&packet).unwrap();
+
+        assert_eq!(json, r#"{"from":1,"message":{"type":"attack","lines":4}}"#);
+        assert_eq!(serde_json::from_str::<Packet>(&json).unwrap(), packet);
+    }
+
+    #[test]
+    fn test_host_and_join() {
+        let mut host = Network::host("127.0.0.1:0", "host").unwrap();
+        let address = match &host.role {
+            Role::Host { listener, .. } => listener.local_addr().unwrap(),
+            Role::Client { .. } => unreachable!(),
+        };
+        let mut client = Network::join(address, "client").unwrap();
+
+        // the host sees the client join with its name
+        match poll_until_event(&mut host).first() {
+            Some(NetEvent::Joined { id, name }) => {
+                assert_eq!(*id, 1);
+                assert_eq!(name, "client");
+            },
+            _ => panic!("the client did not join"),
+        }
+
+        // the client learns its id and who the host is
+        match poll_until_event(&mut client).first() {
+            Some(NetEvent::Joined { id, name }) => {
+                assert_eq!(*id, HOST_ID);
+                assert_eq!(name, "host");
+            },
+            _ => panic!("the client did not hear from the host"),
+        }
+        assert_eq!(client.id, 1);
+
+        // messages from the client arrive at the host with the client's id
+        client.send(Message::Ready { ready: true });
+        match poll_until_event(&mut host).first() {
+            Some(NetEvent::Message(packet)) => assert_eq!(*packet, Packet { from: 1, message: Message::Ready { ready: true } }),
+            _ => panic!("the message did not arrive"),
+        }
+
+        // the client notices when the host is gone
+        drop(host);
+        assert!(matches!(poll_until_event(&mut client).first(), Some(NetEvent::Disconnected)));
+    }
+}
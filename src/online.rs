@@ -0,0 +1,211 @@
+use piston_window::types::Color;
+use piston_window::{Context, G2d, Glyphs};
+
+use crate::{
+    board::Board,
+    net::{Message, NetEvent, Network},
+    renderer::{Renderer, BORDER},
+};
+
+/// the space between the lines of text in the lobby
+const LOBBY_LINE_HEIGHT: f64 = 30.0;
+
+/// a player on another instance of the game, only their board is known
+pub struct RemotePlayer {
+    pub id: usize,
+    pub name: String,
+    pub board: Board,
+    pub score: u16,
+    pub ready: bool,
+    pub topped_out: bool,
+}
+
+/// Things that happened online that the game has to react to
+pub enum OnlineEvent {
+    /// everyone is ready and the host started the game
+    Start,
+    /// another player sent garbage lines
+    Attack(u16),
+    /// another player lost or left, the game might be won
+    PlayerOut,
+    /// the connection to the host was lost
+    Disconnected,
+}
+
+/// keeps track of the other players in an online game and talks to them over the network
+pub struct Online {
+    network: Network,
+    remotes: Vec<RemotePlayer>,
+    pub ready: bool,
+    pub disconnected: bool,
+    /// the board that was sent last, so it is only sent again when it changed
+    last_board: Option<(Vec<Option<Color>>, u16)>,
+}
+
+impl Online {
+    pub fn new(network: Network) -> Online {
+        Online {
+            network,
+            remotes: Vec::new(),
+            ready: false,
+            disconnected: false,
+            last_board: None,
+        }
+    }
+
+    /// Returns all the other players
+    pub fn remotes(&self) -> &[RemotePlayer] {
+        &self.remotes
+    }
+
+    /// The amount of other players that have not topped out yet
+    pub fn remaining(&self) -> usize {
+        self.remotes.iter().filter(|remote| !remote.topped_out).count()
+    }
+
+    /// Reads everything that came in over the network and updates the other players
+    pub fn poll(&mut self) -> Vec<OnlineEvent> {
+        let mut events = Vec::new();
+
+        for event in self.network.poll() {
+            match event {
+                NetEvent::Joined { id, name } => {
+                    self.remotes.push(RemotePlayer { id, name, board: Board::new(), score: 0, ready: false, topped_out: false });
+                    // the new player needs to know if we are ready
+                    self.network.send(Message::Ready { ready: self.ready });
+                },
+                NetEvent::Left { id } => {
+                    self.remotes.retain(|remote| remote.id != id);
+                    events.push(OnlineEvent::PlayerOut);
+                },
+                NetEvent::Disconnected => {
+                    self.remotes.clear();
+                    self.disconnected = true;
+                    events.push(OnlineEvent::Disconnected);
+                },
+                NetEvent::Message(packet) => {
+                    if packet.message == Message::Start {
+                        events.push(self.reset());
+                        continue;
+                    }
+
+                    let Some(remote) = self.remotes.iter_mut().find(|remote| remote.id == packet.from) else {
+                        continue;
+                    };
+
+                    match packet.message {
+                        Message::Ready { ready } => remote.ready = ready,
+                        Message::Board { cells, width, score } => {
+                            remote.board = Board::from_snapshot(&cells, width);
+                            remote.score = score;
+                        },
+                        Message::Attack { lines } => events.push(OnlineEvent::Attack(lines)),
+                        Message::ToppedOut => {
+                            remote.topped_out = true;
+                            events.push(OnlineEvent::PlayerOut);
+                        },
+                        _ => {},
+                    }
+                },
+            }
+        }
+
+        if self.everyone_ready() {
+            self.network.send(Message::Start);
+            events.push(self.reset());
+        }
+
+        events
+    }
+
+    /// The host starts the game as soon as there are other players and everyone is ready
+    fn everyone_ready(&self) -> bool {
+        self.network.is_host()
+            && self.ready
+            && !self.remotes.is_empty()
+            && self.remotes.iter().all(|remote| remote.ready)
+    }
+
+    /// Clears the boards of the other players for a new round, everyone has to ready up again next time
+    fn reset(&mut self) -> OnlineEvent {
+        self.ready = false;
+        self.last_board = None;
+        for remote in self.remotes.iter_mut() {
+            remote.board = Board::with_size(remote.board.size());
+            remote.score = 0;
+            remote.ready = false;
+            remote.topped_out = false;
+        }
+        OnlineEvent::Start
+    }
+
+    /// Switches between ready and not ready and lets the others know
+    pub fn toggle_ready(&mut self) {
+        self.ready = !self.ready;
+        self.network.send(Message::Ready { ready: self.ready });
+    }
+
+    /// Sends the board to the other players if it changed since the last time
+    pub fn send_board(&mut self, board: &Board, score: u16) {
+        let state = (board.snapshot(), score);
+
+        if self.last_board.as_ref() != Some(&state) {
+            self.network.send(Message::Board { cells: state.0.clone(), width: board.width(), score });
+            self.last_board = Some(state);
+        }
+    }
+
+    /// Sends garbage lines to the other players
+    pub fn send_attack(&mut self, lines: u16) {
+        self.network.send(Message::Attack { lines });
+    }
+
+    /// Lets the other players know this player lost
+    pub fn send_topped_out(&mut self) {
+        self.network.send(Message::ToppedOut);
+    }
+
+    /// Draws the header, board, score and latency of every other player at the given offsets
+    pub fn draw(&self, offset: impl Fn(usize) -> f64, renderer: &Renderer, glyphs: &mut Glyphs, context: &Context, g2d: &mut G2d) {
+        for (index, remote) in self.remotes.iter().enumerate() {
+            let offset = offset(index);
+            renderer.draw_image("header", offset, 0.0, context, g2d);
+
+            remote.board.draw(offset, renderer, context, g2d);
+            let text = format!("{}: {}", remote.name, remote.score);
+            renderer.draw_text(&text, offset + 10.0, 50.0, glyphs, context, g2d);
+
+            if let Some(latency) = self.network.latency(remote.id) {
+                renderer.draw_text(&format!("{} ms", latency), offset + 10.0, 75.0, glyphs, context, g2d);
+            }
+        }
+    }
+
+    /// Draws who is in the lobby and who is ready over the board that starts at offset
+    pub fn draw_lobby(&self, offset: f64, renderer: &Renderer, glyphs: &mut Glyphs, context: &Context, g2d: &mut G2d) {
+        let mut lines = vec![
+            String::from("Press R when ready"),
+            format!("{}: {}", self.network.name, Online::ready_text(self.ready)),
+        ];
+
+        for remote in &self.remotes {
+            let mut line = format!("{}: {}", remote.name, Online::ready_text(remote.ready));
+            if let Some(latency) = self.network.latency(remote.id) {
+                line += &format!(" ({} ms)", latency);
+            }
+            lines.push(line);
+        }
+
+        if self.disconnected {
+            lines.push(String::from("Connection lost"));
+        }
+
+        for (index, line) in lines.iter().enumerate() {
+            renderer.draw_text(line, offset + 10.0, BORDER + LOBBY_LINE_HEIGHT * (index + 1) as f64, glyphs, context, g2d);
+        }
+    }
+
+    fn ready_text(ready: bool) -> &'static str {
+        if ready { "ready" } else { "waiting" }
+    }
+}
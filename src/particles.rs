@@ -0,0 +1,196 @@
+use std::collections::VecDeque;
+
+use piston_window::{types::Color, Context, G2d};
+use rand::Rng;
+
+use crate::{
+    player::Visual,
+    renderer::{self, BLOCK_SIZE, BORDER},
+};
+
+/// the most particles there can be at once, the oldest ones make room for new ones
+pub const MAX_PARTICLES: usize = 1500;
+/// how many particles fly out of every cell of a cleared row
+const BURST_SIZE: usize = 4;
+/// how many seconds the particles of a burst live
+const BURST_TIME: f64 = 0.8;
+/// how fast the particles of a burst fly out at most, in pixels per second
+const BURST_SPEED: f64 = 250.0;
+/// how fast the particles of a burst fall, in pixels per second squared
+const GRAVITY: f64 = 900.0;
+/// how many seconds the streaks of a hard drop take to fade
+const TRAIL_TIME: f64 = 0.25;
+/// how many seconds the screen shakes after a tetris
+const SHAKE_TIME: f64 = 0.3;
+/// how far the screen moves at most while it shakes, in pixels
+const SHAKE_DISTANCE: f64 = 6.0;
+
+/// One square that flies, or a streak that only fades
+#[derive(Debug, Clone, PartialEq)]
+struct Particle {
+    position: [f64; 2],
+    size: [f64; 2],
+    velocity: [f64; 2],
+    /// true when gravity pulls the particle down
+    falls: bool,
+    color: Color,
+    /// how many more seconds the particle lives and how many it had, it fades out over that time
+    life: f64,
+    lifetime: f64,
+}
+
+/// The particles drawn over the game, made from what happened to the players
+#[derive(Debug, Clone)]
+pub struct Particles {
+    particles: VecDeque<Particle>,
+    /// how many more seconds the screen shakes
+    shaking: f64,
+    /// whether cleared rows burst and hard drops leave streaks
+    pub enabled: bool,
+    /// whether the screen shakes on a tetris
+    pub shake: bool,
+}
+
+impl Default for Particles {
+    fn default() -> Self {
+        Particles { particles: VecDeque::new(), shaking: 0.0, enabled: true, shake: true }
+    }
+}
+
+impl Particles {
+    /// Makes the particles for something that happened on a board, offset is the x in pixels where the board starts
+    pub fn show(&mut self, visual: Visual, offset: f64) {
+        match visual {
+            Visual::Cleared { rows, cells } => {
+                if rows >= 4 && self.shake {
+                    self.shaking = SHAKE_TIME;
+                }
+                for (y, x, color) in cells {
+                    self.burst(offset + (x as f64 + 0.5) * BLOCK_SIZE, BORDER + (y as f64 + 0.5) * BLOCK_SIZE, color);
+                }
+            },
+            Visual::HardDrop { cells, rows, color } => {
+                // a streak above the top cell of every column of the block, as long as the block fell
+                for (y, x) in cells.iter().filter(|(y, x)| !cells.contains(&(y.wrapping_sub(1), *x))) {
+                    let height = (rows as f64).min(*y as f64) * BLOCK_SIZE;
+                    self.add(Particle {
+                        position: [offset + (*x as f64 + 0.2) * BLOCK_SIZE, BORDER + *y as f64 * BLOCK_SIZE - height],
+                        size: [BLOCK_SIZE * 0.6, height],
+                        velocity: [0.0, 0.0],
+                        falls: false,
+                        color: [color[0], color[1], color[2], 0.5],
+                        life: TRAIL_TIME,
+                        lifetime: TRAIL_TIME,
+                    });
+                }
+            },
+        }
+    }
+
+    /// Sends a few particles of the color flying out of the point
+    fn burst(&mut self, x: f64, y: f64, color: Color) {
+        let mut rng = rand::thread_rng();
+        for _ in 0..BURST_SIZE {
+            let size = rng.gen_range(2.0..6.0);
+            self.add(Particle {
+                position: [x, y],
+                size: [size, size],
+                velocity: [rng.gen_range(-BURST_SPEED..BURST_SPEED), rng.gen_range(-BURST_SPEED..BURST_SPEED / 4.0)],
+                falls: true,
+                color,
+                life: BURST_TIME,
+                lifetime: BURST_TIME,
+            });
+        }
+    }
+
+    /// Adds a particle when particles are enabled, the oldest one goes when there are too many
+    fn add(&mut self, particle: Particle) {
+        if !self.enabled {
+            return;
+        }
+        if self.particles.len() >= MAX_PARTICLES {
+            self.particles.pop_front();
+        }
+        self.particles.push_back(particle);
+    }
+
+    /// Moves the particles along by the elapsed time and removes the ones that are gone
+    pub fn update(&mut self, dt: f64) {
+        for particle in self.particles.iter_mut() {
+            if particle.falls {
+                particle.velocity[1] += GRAVITY * dt;
+            }
+            particle.position[0] += particle.velocity[0] * dt;
+            particle.position[1] += particle.velocity[1] * dt;
+            particle.life -= dt;
+        }
+        self.particles.retain(|particle| particle.life > 0.0);
+        self.shaking = (self.shaking - dt).max(0.0);
+    }
+
+    /// Returns how far the screen is moved by the shaking right now
+    pub fn shake_offset(&self) -> [f64; 2] {
+        let strength = SHAKE_DISTANCE * self.shaking / SHAKE_TIME;
+        [strength * (self.shaking * 90.0).sin(), strength * (self.shaking * 70.0).cos()]
+    }
+
+    /// Draws every particle, they fade out as they get older
+    pub fn draw(&self, context: &Context, g2d: &mut G2d) {
+        for particle in &self.particles {
+            let mut color = particle.color;
+            color[3] *= (particle.life / particle.lifetime) as f32;
+            renderer::draw_rect(color, particle.position[0], particle.position[1], particle.size[0], particle.size[1], context, g2d);
+        }
+    }
+}
+
+#[cfg(test)]
+mod particles_tests {
+    use super::*;
+
+    fn cleared(rows: usize, width: usize) -> Visual {
+        let cells = (0..rows).flat_map(|y| (0..width).map(move |x| (y, x, [1.0, 0.0, 0.0, 1.0]))).collect();
+        Visual::Cleared { rows, cells }
+    }
+
+    #[test]
+    fn test_burst() {
+        let mut particles = Particles::default();
+        particles.show(cleared(1, 10), 0.0);
+        assert_eq!(particles.particles.len(), 10 * BURST_SIZE);
+        assert_eq!(particles.shake_offset(), [0.0, 0.0]);
+
+        // a tetris shakes the screen until the shaking wears off
+        particles.show(cleared(4, 10), 0.0);
+        assert_ne!(particles.shake_offset(), [0.0, 0.0]);
+        particles.update(BURST_TIME);
+        assert!(particles.particles.is_empty());
+        assert_eq!(particles.shake_offset(), [0.0, 0.0]);
+    }
+
+    #[test]
+    fn test_limits() {
+        let mut particles = Particles::default();
+        for _ in 0..100 {
+            particles.show(cleared(4, 10), 0.0);
+        }
+        assert_eq!(particles.particles.len(), MAX_PARTICLES);
+
+        let mut particles = Particles { enabled: false, shake: false, ..Particles::default() };
+        particles.show(cleared(4, 10), 0.0);
+        particles.show(Visual::HardDrop { cells: vec![(10, 4), (11, 4)], rows: 8, color: [1.0, 0.0, 0.0, 1.0] }, 0.0);
+        assert!(particles.particles.is_empty());
+        assert_eq!(particles.shake_offset(), [0.0, 0.0]);
+    }
+
+    #[test]
+    fn test_trail() {
+        let mut particles = Particles::default();
+        // a vertical domino leaves one streak as long as it fell
+        particles.show(Visual::HardDrop { cells: vec![(10, 4), (11, 4)], rows: 8, color: [1.0, 0.0, 0.0, 1.0] }, 0.0);
+        assert_eq!(particles.particles.len(), 1);
+        assert_eq!(particles.particles[0].size[1], 8.0 * BLOCK_SIZE);
+        assert_eq!(particles.particles[0].position[1] + 8.0 * BLOCK_SIZE, BORDER + 10.0 * BLOCK_SIZE);
+    }
+}
@@ -0,0 +1,272 @@
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufReader};
+use std::path::Path;
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    blockcolor::BlockColor,
+    blockshape::{BlockShape, Matrix, SHAPES},
+};
+
+/// the most rows and columns a piece can have
+pub const MAX_PIECE_SIZE: usize = 5;
+/// y, x tried after the kicks of a piece with wall kicks, one to the side, one up and then two to the side for long pieces
+const WALL_KICKS: [(isize, isize); 5] = [(0, 1), (0, -1), (-1, 0), (0, 2), (0, -2)];
+
+/// What a piece tries when it can't rotate where it is
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum RotationSystem {
+    /// only the kicks of the piece set, the usual pieces have none and just don't rotate
+    #[default]
+    Classic,
+    /// after the kicks of the piece set it moves away from walls and the floor to fit
+    WallKicks,
+}
+
+impl FromStr for RotationSystem {
+    type Err = String;
+
+    /// Reads a rotation system written as classic or kicks
+    fn from_str(text: &str) -> Result<RotationSystem, String> {
+        match text {
+            "classic" => Ok(RotationSystem::Classic),
+            "kicks" => Ok(RotationSystem::WallKicks),
+            _ => Err(format!("{} is not a rotation system, use classic or kicks", text)),
+        }
+    }
+}
+
+impl fmt::Display for RotationSystem {
+    /// Writes the rotation system the way it is read
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RotationSystem::Classic => write!(f, "classic"),
+            RotationSystem::WallKicks => write!(f, "kicks"),
+        }
+    }
+}
+
+impl RotationSystem {
+    /// Returns the kicks a piece with the given kicks of its own tries with this rotation system
+    pub fn kicks(self, own: &[(isize, isize)]) -> Vec<(isize, isize)> {
+        match self {
+            RotationSystem::Classic => own.to_vec(),
+            RotationSystem::WallKicks => own.iter().chain(WALL_KICKS.iter().filter(|kick| !own.contains(kick))).copied().collect(),
+        }
+    }
+}
+
+/// A piece as it is written in a piece set file
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PieceDef {
+    pub name: String,
+    /// the rows of the piece, # is a cell and anything else is empty
+    /// left out for the seven usual pieces, which are then picked by their name
+    #[serde(default)]
+    pub rows: Vec<String>,
+    /// every piece of this kind has this color, left out they change color like the usual pieces
+    #[serde(default)]
+    pub color: Option<BlockColor>,
+    /// how many times the rows are turned clockwise before the piece spawns
+    #[serde(default)]
+    pub spawn_rotation: usize,
+    /// y, x the piece moves by, tried in order when it can't rotate where it is
+    #[serde(default)]
+    pub kicks: Vec<(isize, isize)>,
+}
+
+/// A piece ready to be played
+#[derive(Debug, Clone, PartialEq)]
+pub struct Piece {
+    pub shape: BlockShape,
+    pub name: String,
+    /// the cells of the piece as it spawns
+    pub matrix: Matrix,
+    pub color: Option<BlockColor>,
+    pub kicks: Vec<(isize, isize)>,
+}
+
+impl From<BlockShape> for Piece {
+    /// One of the seven usual pieces, they have no kicks and change color
+    fn from(shape: BlockShape) -> Piece {
+        Piece {
+            shape,
+            name: format!("{:?}", shape),
+            matrix: shape.get_shape(),
+            color: None,
+            kicks: Vec::new(),
+        }
+    }
+}
+
+/// The pieces a game is played with
+#[derive(Debug, Clone, PartialEq)]
+pub struct PieceSet {
+    pieces: Vec<Piece>,
+}
+
+impl Default for PieceSet {
+    fn default() -> Self {
+        PieceSet::standard()
+    }
+}
+
+impl PieceSet {
+    /// The seven usual pieces
+    pub fn standard() -> PieceSet {
+        PieceSet { pieces: SHAPES.iter().map(|shape| Piece::from(*shape)).collect() }
+    }
+
+    /// Reads a set from a json file with a list of pieces
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<PieceSet> {
+        let file = File::open(path)?;
+        let defs: Vec<PieceDef> = serde_json::from_reader(BufReader::new(file))?;
+        PieceSet::from_defs(&defs).map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error))
+    }
+
+    /// Turns written pieces into a set, pieces without rows have to be named after one of the seven usual pieces
+    pub fn from_defs(defs: &[PieceDef]) -> Result<PieceSet, String> {
+        if defs.is_empty() {
+            return Err(String::from("a piece set needs at least one piece"));
+        }
+
+        let mut pieces = Vec::with_capacity(defs.len());
+        for (index, def) in defs.iter().enumerate() {
+            let (shape, mut matrix) = if def.rows.is_empty() {
+                let shape = SHAPES.iter()
+                    .find(|shape| format!("{:?}", shape) == def.name)
+                    .ok_or_else(|| format!("{} has no rows and is not one of I, J, L, O, S, T or Z", def.name))?;
+                (*shape, shape.get_shape())
+            } else {
+                let index = u8::try_from(index).map_err(|_| String::from("a piece set can't have more than 256 pieces"))?;
+                (BlockShape::Custom(index), parse_rows(&def.name, &def.rows)?)
+            };
+
+            for _ in 0..def.spawn_rotation % 4 {
+                BlockShape::rotate_matrix(&mut matrix);
+            }
+            pieces.push(Piece {
+                shape,
+                name: def.name.clone(),
+                matrix,
+                color: def.color,
+                kicks: def.kicks.clone(),
+            });
+        }
+
+        Ok(PieceSet { pieces })
+    }
+
+    /// Returns every shape in the set, in the order of the file
+    pub fn shapes(&self) -> Vec<BlockShape> {
+        self.pieces.iter().map(|piece| piece.shape).collect()
+    }
+
+    /// Returns the piece of the shape, one of the seven usual pieces that is not in the set is still given as usual
+    pub fn piece(&self, shape: BlockShape) -> Piece {
+        self.pieces.iter()
+            .find(|piece| piece.shape == shape)
+            .cloned()
+            .unwrap_or_else(|| Piece::from(shape))
+    }
+
+    /// Returns true if the set has pieces other than the seven usual ones
+    pub fn has_custom(&self) -> bool {
+        self.pieces.iter().any(|piece| matches!(piece.shape, BlockShape::Custom(_)))
+    }
+}
+
+/// Turns rows of # into a square matrix that fits all of them
+fn parse_rows(name: &str, rows: &[String]) -> Result<Matrix, String> {
+    let width = rows.iter().map(|row| row.chars().count()).max().unwrap_or(0);
+    let size = width.max(rows.len());
+    if size > MAX_PIECE_SIZE {
+        return Err(format!("{} is bigger than {}x{}", name, MAX_PIECE_SIZE, MAX_PIECE_SIZE));
+    }
+
+    let mut matrix = vec![vec![0; size]; size];
+    for (y, row) in rows.iter().enumerate() {
+        for (x, letter) in row.chars().enumerate() {
+            if letter == '#' {
+                matrix[y][x] = 1;
+            }
+        }
+    }
+
+    if matrix.iter().flatten().all(|cell| *cell == 0) {
+        return Err(format!("{} has no cells", name));
+    }
+    Ok(matrix)
+}
+
+#[cfg(test)]
+mod pieces_tests {
+    use super::*;
+
+    fn def(name: &str, rows: &[&str]) -> PieceDef {
+        PieceDef {
+            name: String::from(name),
+            rows: rows.iter().map(|row| row.to_string()).collect(),
+            color: None,
+            spawn_rotation: 0,
+            kicks: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_from_defs() {
+        let mut plus = def("X", &[".#.", "###", ".#."]);
+        plus.color = Some(BlockColor::Green);
+        let mut bar = def("I5", &["#####"]);
+        bar.spawn_rotation = 1;
+        let set = PieceSet::from_defs(&[def("T", &[]), plus, bar]).unwrap();
+
+        assert_eq!(set.shapes(), [BlockShape::T, BlockShape::Custom(1), BlockShape::Custom(2)]);
+        assert_eq!(set.piece(BlockShape::T), Piece { name: String::from("T"), ..Piece::from(BlockShape::T) });
+        assert!(set.has_custom());
+
+        let plus = set.piece(BlockShape::Custom(1));
+        assert_eq!(plus.matrix, [[0, 1, 0], [1, 1, 1], [0, 1, 0]]);
+        assert_eq!(plus.color, Some(BlockColor::Green));
+
+        // the bar is padded to a square and turned on its side
+        let bar = set.piece(BlockShape::Custom(2));
+        assert_eq!(bar.matrix.len(), 5);
+        assert!(bar.matrix.iter().all(|row| row == &[0, 0, 0, 0, 1]));
+    }
+
+    #[test]
+    fn test_bad_defs() {
+        assert!(PieceSet::from_defs(&[]).is_err());
+        assert!(PieceSet::from_defs(&[def("Q", &[])]).is_err());
+        assert!(PieceSet::from_defs(&[def("empty", &["...", "..."])]).is_err());
+        assert!(PieceSet::from_defs(&[def("long", &["######"])]).is_err());
+        assert!(!PieceSet::standard().has_custom());
+    }
+
+    #[test]
+    fn test_assets() {
+        let pentominoes = PieceSet::load("assets/pentominoes.json").unwrap();
+        assert_eq!(pentominoes.shapes().len(), 12);
+        assert!(pentominoes.shapes().iter()
+            .all(|shape| pentominoes.piece(*shape).matrix.iter().flatten().filter(|cell| **cell == 1).count() == 5));
+
+        let trominoes = PieceSet::load("assets/trominoes.json").unwrap();
+        assert_eq!(trominoes.shapes().len(), 3);
+    }
+
+    #[test]
+    fn test_rotation_system() {
+        assert!(RotationSystem::Classic.kicks(&[]).is_empty());
+        // the kicks of the piece come first and are not tried twice
+        let kicks = RotationSystem::WallKicks.kicks(&[(0, -1)]);
+        assert_eq!(kicks[..2], [(0, -1), (0, 1)]);
+        assert_eq!(kicks.len(), WALL_KICKS.len());
+
+        assert_eq!("kicks".parse::<RotationSystem>(), Ok(RotationSystem::WallKicks));
+        assert!("srs".parse::<RotationSystem>().is_err());
+    }
+}
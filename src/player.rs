@@ -0,0 +1,1019 @@
+use std::fmt;
+use std::str::FromStr;
+
+use piston_window::{types::Color, Context, G2d, Glyphs, Key};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    block::{Block, BlockStatus},
+    blockcolor::{BlockColor, ColorPolicy},
+    blockshape::BlockShape,
+    board::{self, Board, CellStatus, Size},
+    effects::Effects,
+    finesse,
+    garbage::{self, GarbageMeter},
+    pieces::{Piece, PieceSet, RotationSystem},
+    puzzle,
+    queue::{PieceQueue, Randomizer},
+    renderer::Renderer,
+};
+
+/// How many seconds it takes before the piece falls one row down on the first level, and how long a landed piece waits to lock
+const MOVING_PERIOD: f64 = 0.5;
+/// how much less time it takes to fall a row with every level
+const GRAVITY_FACTOR: f64 = 0.85;
+/// the highest level a game can start on
+pub const MAX_LEVEL: usize = 30;
+/// how many seconds the warning is shown after a finesse fault
+const FAULT_FLASH_TIME: f64 = 1.0;
+/// where the finesse warning is drawn, relative to the start of the board
+const FAULT_LOCATION: (f64, f64) = (70.0, 120.0);
+/// how many lines have to be cleared to go up a level
+const LINES_PER_LEVEL: usize = 10;
+/// the held piece is drawn in gray when its shape has no color of its own, so it can't be mistaken for the next piece
+const HOLD_COLOR: Color = [0.5, 0.5, 0.5, 1.0];
+
+#[derive(Debug, PartialEq, Clone, Copy, Serialize, Deserialize)]
+pub enum InputType {
+    Left,
+    Right,
+    Down,
+    Rotate,
+}
+
+/// Returns the y, x where a block starts when the game is loaded or a new block is created
+/// Blocks start in the middle with their top row in the hidden rows, so their bottom row is the first one that is shown
+pub fn spawn_position(board: &Board) -> (isize, isize) {
+    (board.hidden().saturating_sub(1) as isize, (board.width() as isize / 2) - 1)
+}
+
+/// When a player has lost, a block that has no room to spawn always ends the game
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum TopOut {
+    /// only when the next block has no room to spawn
+    BlockOut,
+    /// also when a block locks with all of its cells in the hidden rows
+    #[default]
+    LockOut,
+    /// also when anything is left in the hidden rows after a block locks
+    PartialLockOut,
+}
+
+impl FromStr for TopOut {
+    type Err = String;
+
+    /// Reads a rule written as block, lock or partial
+    fn from_str(text: &str) -> Result<TopOut, String> {
+        match text {
+            "block" => Ok(TopOut::BlockOut),
+            "lock" => Ok(TopOut::LockOut),
+            "partial" => Ok(TopOut::PartialLockOut),
+            _ => Err(format!("{} is not a top out rule, use block, lock or partial", text)),
+        }
+    }
+}
+
+/// How long the game waits around a lock, in seconds, these are part of the rules and not just for show
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Delays {
+    /// how long cleared rows stay on the board before the rows above fall down
+    pub line_clear: f64,
+    /// how long it takes before the next block spawns after one locks, also known as ARE
+    pub entry: f64,
+}
+
+impl Default for Delays {
+    fn default() -> Self {
+        Delays { line_clear: 0.3, entry: 0.1 }
+    }
+}
+
+/// How the block moves while its keys are held down, in seconds
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Handling {
+    /// delayed auto shift, how long left or right is held before the block starts to slide
+    pub das: f64,
+    /// auto repeat rate, the time between the moves while it slides, 0 slides it to the wall right away
+    pub arr: f64,
+    /// soft drop factor, how many times faster the block falls while down is held
+    pub sdf: f64,
+}
+
+impl Default for Handling {
+    fn default() -> Self {
+        Handling { das: 0.17, arr: 0.05, sdf: 10.0 }
+    }
+}
+
+/// What the player is waiting for after a block locked
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Delay {
+    /// the full rows are shown for this many more seconds before they are cleared
+    LineClear(f64),
+    /// the next block spawns in this many seconds
+    Entry(f64),
+}
+
+impl fmt::Display for TopOut {
+    /// Writes the rule the way it is read
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TopOut::BlockOut => write!(f, "block"),
+            TopOut::LockOut => write!(f, "lock"),
+            TopOut::PartialLockOut => write!(f, "partial"),
+        }
+    }
+}
+
+/// the keys a player uses to control their block
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Controls {
+    left: Vec<Key>,
+    right: Vec<Key>,
+    down: Vec<Key>,
+    rotate: Vec<Key>,
+    hold: Vec<Key>,
+    hard_drop: Vec<Key>,
+}
+
+impl Controls {
+    /// Both the arrow keys and WASD, used when there is only one player
+    pub fn single() -> Controls {
+        Controls {
+            left: vec![Key::Left, Key::A],
+            right: vec![Key::Right, Key::D],
+            down: vec![Key::Down, Key::S],
+            rotate: vec![Key::Up, Key::W, Key::R],
+            hold: vec![Key::LShift, Key::C],
+            hard_drop: vec![Key::Space],
+        }
+    }
+
+    /// No keys at all, used for players that are controlled by a bot
+    pub fn none() -> Controls {
+        Controls {
+            left: Vec::new(),
+            right: Vec::new(),
+            down: Vec::new(),
+            rotate: Vec::new(),
+            hold: Vec::new(),
+            hard_drop: Vec::new(),
+        }
+    }
+
+    /// WASD for the player on the left side of the screen
+    pub fn wasd() -> Controls {
+        Controls {
+            left: vec![Key::A],
+            right: vec![Key::D],
+            down: vec![Key::S],
+            rotate: vec![Key::W],
+            hold: vec![Key::Q],
+            hard_drop: vec![Key::E],
+        }
+    }
+
+    /// The arrow keys for the player on the right side of the screen
+    pub fn arrows() -> Controls {
+        Controls {
+            left: vec![Key::Left],
+            right: vec![Key::Right],
+            down: vec![Key::Down],
+            rotate: vec![Key::Up],
+            hold: vec![Key::RShift],
+            hard_drop: vec![Key::Return],
+        }
+    }
+
+    /// Returns what every group of keys does together with the keys, to show them to the player
+    pub fn bindings(&self) -> [(&'static str, &[Key]); 6] {
+        [
+            ("Left", &self.left),
+            ("Right", &self.right),
+            ("Down", &self.down),
+            ("Rotate", &self.rotate),
+            ("Hold", &self.hold),
+            ("Hard drop", &self.hard_drop),
+        ]
+    }
+
+    /// Binds the group of keys at the index of bindings to the one key, the key is taken away from the other groups
+    pub fn bind(&mut self, index: usize, key: Key) {
+        let mut groups = [&mut self.left, &mut self.right, &mut self.down, &mut self.rotate, &mut self.hold, &mut self.hard_drop];
+        for group in groups.iter_mut() {
+            group.retain(|other| *other != key);
+        }
+        if let Some(group) = groups.get_mut(index) {
+            **group = vec![key];
+        }
+    }
+
+    /// Turns a key into the input it is bound to
+    fn input(&self, key: &Key) -> Option<InputType> {
+        if self.left.contains(key) {
+            Some(InputType::Left)
+        } else if self.right.contains(key) {
+            Some(InputType::Right)
+        } else if self.down.contains(key) {
+            Some(InputType::Down)
+        } else if self.rotate.contains(key) {
+            Some(InputType::Rotate)
+        } else {
+            None
+        }
+    }
+}
+
+/// Things that happened to a player during an update that the game has to react to
+pub enum PlayerEvent {
+    /// the block was locked in place and cleared lines, sent is the amount of garbage lines for the opponent
+    /// fault is true when it took more moves and rotations to get the block there than needed
+    /// spin is true when the block was a T that was rotated into a spot it could not have been moved into
+    Locked { lines: usize, sent: u16, fault: bool, spin: bool },
+    /// the player lost, because there was no room for a new block or by the top out rule they play with
+    ToppedOut,
+}
+
+/// What a player has done so far in the game
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Stats {
+    pub lines: usize,
+    /// the blocks that were locked
+    pub pieces: usize,
+    /// how many seconds the player has been playing
+    pub time: f64,
+    /// the level the game started on
+    pub start_level: usize,
+}
+
+impl Default for Stats {
+    fn default() -> Self {
+        Stats { lines: 0, pieces: 0, time: 0.0, start_level: 1 }
+    }
+}
+
+impl Stats {
+    /// Returns the level, which goes up by one for every LINES_PER_LEVEL lines after the level the game started on
+    pub fn level(&self) -> usize {
+        self.start_level + self.lines / LINES_PER_LEVEL
+    }
+
+    /// Returns how many seconds it takes a block to fall a row on the current level
+    pub fn gravity(&self) -> f64 {
+        MOVING_PERIOD * GRAVITY_FACTOR.powi(self.level().min(MAX_LEVEL) as i32 - 1)
+    }
+
+    /// Returns how many pieces were placed per second
+    pub fn pps(&self) -> f64 {
+        if self.time > 0.0 {
+            self.pieces as f64 / self.time
+        } else {
+            0.0
+        }
+    }
+
+    /// Returns the time written as minutes and seconds, like 1:05
+    pub fn clock(&self) -> String {
+        let seconds = self.time as u64;
+        format!("{}:{:02}", seconds / 60, seconds % 60)
+    }
+}
+
+/// Things that happened to a player that are only there to be seen, the particles are made from these
+/// The rows are the ones that are shown, cells in the hidden rows are left out
+#[derive(Debug, Clone, PartialEq)]
+pub enum Visual {
+    /// rows are full and about to be cleared, with the y, x and color of their cells
+    Cleared { rows: usize, cells: Vec<(usize, usize, Color)> },
+    /// the block was dropped all the way down over the given amount of rows
+    HardDrop { cells: Vec<(usize, usize)>, rows: usize, color: Color },
+}
+
+/// struct holding everything that belongs to one player; their board, block and upcoming pieces
+pub struct Player {
+    board: Board,
+    block: Block,
+    queue: PieceQueue,
+    /// the pieces the queue is filled with
+    pieces: PieceSet,
+    /// how the blocks get their colors
+    colors: ColorPolicy,
+    /// the color the next block gets when the colors are random, picked early so the preview can show it
+    random_color: BlockColor,
+    controls: Controls,
+    /// the piece that was put aside to be used later
+    hold: Option<BlockShape>,
+    /// true when the current block came out of hold, a block can only be held once
+    held: bool,
+    pub garbage: GarbageMeter,
+    pub score: u16,
+    /// the amount of pieces that were placed with more inputs than needed
+    pub faults: u16,
+    pub stats: Stats,
+    /// what makes this player lose
+    pub top_out: TopOut,
+    /// how long the game waits to clear lines and spawn blocks
+    pub delays: Delays,
+    /// how the block moves while keys are held
+    pub handling: Handling,
+    /// the inputs whose keys are held down, in the order they were pressed
+    pressed: Vec<InputType>,
+    /// how many more seconds until the block slides again while left or right is held
+    shift_time: f64,
+    /// what the block tries when it can't rotate where it is
+    rotation: RotationSystem,
+    /// what the player is waiting for, None while the block falls
+    delay: Option<Delay>,
+    effects: Effects,
+    /// the board and block as they were when the block spawned, used to work out the finesse of the placement
+    spawn: (Board, Block),
+    /// the moves and rotations that were pressed for the current block
+    presses: usize,
+    /// how many more seconds the finesse warning is shown
+    fault_flash: f64,
+    /// true when the last thing that moved the block was a rotation, a T-spin has to end with one
+    rotated_last: bool,
+    /// what happened since the game last took them, to be shown
+    visuals: Vec<Visual>,
+    waiting_time: f64,
+}
+
+impl Player {
+    /// Creates a new player with an empty board, controlled with the given keys
+    pub fn new(controls: Controls) -> Player {
+        Player::sized(controls, Size::default())
+    }
+
+    /// Creates a new player with an empty board of the given size
+    pub fn sized(controls: Controls, size: Size) -> Player {
+        Player::with_queue(controls, PieceQueue::new(), size)
+    }
+
+    /// Creates a new player that always gets the same pieces for the same seed
+    pub fn seeded(controls: Controls, seed: u64) -> Player {
+        Player::with_queue(controls, PieceQueue::seeded(seed), Size::default())
+    }
+
+    /// Creates a new player that starts on the given board with the given pieces first and the given piece in hold
+    /// The board gets the usual hidden rows on top to spawn the pieces in
+    pub fn practice(controls: Controls, board: &Board, pieces: &[BlockShape], hold: Option<BlockShape>) -> Player {
+        let mut board = board.with_hidden(board::HIDDEN);
+        let mut player = Player::with_queue(controls, PieceQueue::starting_with(pieces), board.size());
+
+        let spawn = spawn_position(&board);
+        player.block = Block::with_shape(&mut board, spawn, player.block.shape());
+        player.board = board;
+        player.spawn = (player.board.clone(), player.block.clone());
+        player.hold = hold;
+        player
+    }
+
+    fn with_queue(controls: Controls, mut queue: PieceQueue, size: Size) -> Player {
+        let mut board = Board::with_size(size);
+        let spawn = spawn_position(&board);
+        let block = Block::with_shape(&mut board, spawn, queue.pop());
+
+        Player {
+            spawn: (board.clone(), block.clone()),
+            board,
+            block,
+            queue,
+            pieces: PieceSet::standard(),
+            colors: ColorPolicy::default(),
+            random_color: BlockColor::random(),
+            controls,
+            hold: None,
+            held: false,
+            garbage: GarbageMeter::default(),
+            score: 0,
+            faults: 0,
+            stats: Stats::default(),
+            top_out: TopOut::default(),
+            delays: Delays::default(),
+            handling: Handling::default(),
+            pressed: Vec::new(),
+            shift_time: 0.0,
+            rotation: RotationSystem::default(),
+            delay: None,
+            effects: Effects::default(),
+            presses: 0,
+            fault_flash: 0.0,
+            rotated_last: false,
+            visuals: Vec::new(),
+            waiting_time: 0.0,
+        }
+    }
+
+    /// Returns the board of the player
+    pub fn board(&self) -> &Board {
+        &self.board
+    }
+
+    /// Returns the block that is currently falling
+    pub fn block(&self) -> &Block {
+        &self.block
+    }
+
+    /// Returns the pieces that come after the current block
+    pub fn queue(&self) -> &PieceQueue {
+        &self.queue
+    }
+
+    /// Returns the shape and color of the piece that comes after the current block
+    pub fn preview(&self) -> (BlockShape, BlockColor) {
+        let piece = self.next_piece();
+        (piece.shape, piece.color.unwrap_or(self.block.color))
+    }
+
+    /// Returns the piece that comes after the current block, with the color it will get
+    pub fn next_piece(&self) -> Piece {
+        self.colored(self.queue.peek())
+    }
+
+    /// Returns the piece of the shape with the color the next block of it gets, pieces with a color of their own keep it
+    fn colored(&self, shape: BlockShape) -> Piece {
+        let mut piece = self.pieces.piece(shape);
+        piece.color = Some(piece.color.unwrap_or_else(|| self.colors.color(shape, self.block.color, self.random_color)));
+        piece.kicks = self.rotation.kicks(&piece.kicks);
+        piece
+    }
+
+    /// Picks the random pieces with the given randomizer, the upcoming pieces are picked again
+    pub fn set_randomizer(&mut self, randomizer: Randomizer) {
+        self.queue.set_randomizer(randomizer);
+    }
+
+    /// Rotates the blocks with the given rotation system from now on, the current block included
+    pub fn set_rotation(&mut self, rotation: RotationSystem) {
+        self.rotation = rotation;
+        let kicks = self.rotation.kicks(&self.pieces.piece(self.block.shape()).kicks);
+        self.block.set_kicks(kicks.clone());
+        self.spawn.1.set_kicks(kicks);
+    }
+
+    /// Changes the keys the player uses
+    pub fn set_controls(&mut self, controls: Controls) {
+        self.controls = controls;
+        self.pressed.clear();
+    }
+
+    /// Colors the blocks with the given policy from now on, for before the game starts
+    /// The current block changes color when its shape has a color of its own
+    pub fn set_colors(&mut self, colors: ColorPolicy) {
+        self.colors = colors;
+
+        let shape = self.block.shape();
+        if let Some(color) = self.pieces.piece(shape).color.or(colors.fixed(shape)) {
+            self.block.color = color;
+            for (y, x) in self.block.cells() {
+                self.board.data[y][x].color = color.to_color();
+            }
+            self.spawn = (self.board.clone(), self.block.clone());
+        }
+    }
+
+    /// Returns the pieces the player plays with
+    pub fn pieces(&self) -> &PieceSet {
+        &self.pieces
+    }
+
+    /// Plays with the given pieces from now on, the board is emptied so this is for before the game starts
+    pub fn set_pieces(&mut self, pieces: PieceSet) {
+        self.queue.set_shapes(&pieces.shapes());
+        self.pieces = pieces;
+        self.respawn();
+    }
+
+    /// Gets the pieces from the seed from now on, the board is emptied so this is for before the game starts
+    /// The same seed with the same pieces and randomizer always gives the same game
+    pub fn set_seed(&mut self, seed: u64) {
+        self.queue.reseed(seed);
+        self.respawn();
+    }
+
+    /// Starts the game on the given level, the blocks fall faster on higher levels
+    pub fn set_level(&mut self, level: usize) {
+        self.stats.start_level = level.clamp(1, MAX_LEVEL);
+    }
+
+    /// Empties the board and spawns the first piece of the queue
+    fn respawn(&mut self) {
+        let mut board = Board::with_size(self.board.size());
+        let spawn = spawn_position(&board);
+        let shape = self.queue.pop();
+        self.block = Block::with_piece(&mut board, spawn, &self.colored(shape));
+        self.board = board;
+        self.spawn = (self.board.clone(), self.block.clone());
+        self.hold = None;
+    }
+
+    /// Returns the piece in hold
+    pub fn held_piece(&self) -> Option<BlockShape> {
+        self.hold
+    }
+
+    /// Returns the input the key is bound to for this player
+    pub fn input_for(&self, key: &Key) -> Option<InputType> {
+        self.controls.input(key)
+    }
+
+    /// Returns true if the key drops the block all the way down for this player
+    pub fn hard_drops_with(&self, key: &Key) -> bool {
+        self.controls.hard_drop.contains(key)
+    }
+
+    /// Returns what happened since the last time, for the game to show
+    pub fn take_visuals(&mut self) -> Vec<Visual> {
+        std::mem::take(&mut self.visuals)
+    }
+
+    /// Returns true if the key puts the block in hold for this player
+    pub fn holds_with(&self, key: &Key) -> bool {
+        self.controls.hold.contains(key)
+    }
+
+    /// Swaps the block with the piece in hold, or with the next piece when nothing is held yet
+    /// The block that comes out of hold starts at the top and can't be held again until it is placed
+    pub fn hold(&mut self) {
+        if self.held || self.block.status == BlockStatus::Frozen {
+            return;
+        }
+
+        let mut board = self.board.frozen();
+        let spawn = spawn_position(&board);
+
+        let shape = self.hold.unwrap_or_else(|| self.queue.peek());
+        if let Some(block) = Block::next(&mut board, spawn, &self.colored(shape), &self.block) {
+            if self.hold.is_none() {
+                self.queue.pop();
+            }
+            self.random_color = BlockColor::random();
+            self.hold = Some(self.block.shape());
+            self.held = true;
+            self.board = board;
+            self.block = block;
+            self.spawn = (self.board.clone(), self.block.clone());
+            self.presses = 0;
+            self.rotated_last = false;
+        }
+    }
+
+    /// Drops the block as far down as it goes, it locks on the next update
+    pub fn hard_drop(&mut self) {
+        if self.delay.is_some() || self.block.status == BlockStatus::Frozen {
+            return;
+        }
+
+        let start = self.block.position.0;
+        while self.block.status != BlockStatus::Frozen {
+            self.block.move_down(&mut self.board);
+        }
+        let rows = (self.block.position.0 - start) as usize;
+        if rows > 0 {
+            self.rotated_last = false;
+        }
+        self.waiting_time = MOVING_PERIOD;
+
+        let hidden = self.board.hidden();
+        let cells = self.block.cells().into_iter().filter(|(y, _)| *y >= hidden).map(|(y, x)| (y - hidden, x)).collect();
+        self.visuals.push(Visual::HardDrop { cells, rows, color: self.block.color.to_color() });
+    }
+
+    /// Moves or rotates the block according to the input, nothing moves while lines are cleared or the next block is waited for
+    pub fn handle_input(&mut self, input_type: InputType) {
+        if self.delay.is_some() {
+            return;
+        }
+        if finesse::counts(input_type) && self.block.status != BlockStatus::Frozen {
+            self.presses += 1;
+        }
+        self.move_block(input_type);
+    }
+
+    /// Handles the input of a key that went down, left and right slide and down drops faster for as long as it is held
+    /// Returns false when the input was already held, the keyboard repeats the presses of keys that are held down
+    pub fn press(&mut self, input_type: InputType) -> bool {
+        if self.pressed.contains(&input_type) {
+            return false;
+        }
+        self.pressed.push(input_type);
+        if matches!(input_type, InputType::Left | InputType::Right) {
+            self.shift_time = self.handling.das;
+        }
+        self.handle_input(input_type);
+        true
+    }
+
+    /// Stops the input of a key that went up, when the other direction is still held it slides that way after the delay
+    pub fn release(&mut self, input_type: InputType) {
+        self.pressed.retain(|pressed| *pressed != input_type);
+        if matches!(input_type, InputType::Left | InputType::Right) {
+            self.shift_time = self.handling.das;
+        }
+    }
+
+    /// Slides the block while left or right is held, the one that was pressed last wins
+    fn auto_shift(&mut self, dt: f64) {
+        let Some(direction) = self.pressed.iter().rev().find(|input| matches!(input, InputType::Left | InputType::Right)).copied() else {
+            return;
+        };
+        self.shift_time -= dt;
+        while self.shift_time <= 0.0 {
+            let before = self.block.position;
+            self.move_block(direction);
+            if self.handling.arr > 0.0 {
+                self.shift_time += self.handling.arr;
+            } else if self.block.position == before {
+                break;
+            }
+        }
+    }
+
+    /// Moves or rotates the block without counting it as a press
+    fn move_block(&mut self, input_type: InputType) {
+        let before = (self.block.position, self.block.matrix().clone());
+        self.block.handle_input(&mut self.board, input_type);
+        if self.block.position != before.0 || *self.block.matrix() != before.1 {
+            self.rotated_last = input_type == InputType::Rotate;
+        }
+    }
+
+    /// Lets the block fall based on the elapsed time since the last update
+    /// When the block is frozen lines are cleared, garbage is added and the next block is spawned, after the delays
+    pub fn update(&mut self, dt: f64) -> Option<PlayerEvent> {
+        self.waiting_time += dt;
+        self.fault_flash = (self.fault_flash - dt).max(0.0);
+        self.effects.update(dt);
+        self.stats.time += dt;
+
+        match self.delay.take() {
+            Some(Delay::LineClear(time)) if time > dt => self.delay = Some(Delay::LineClear(time - dt)),
+            Some(Delay::Entry(time)) if time > dt => self.delay = Some(Delay::Entry(time - dt)),
+            Some(Delay::LineClear(_)) => return Some(self.lock_and_wait()),
+            Some(Delay::Entry(_)) => {
+                self.waiting_time = 0.0;
+                return self.spawn_block();
+            },
+            None => {}
+        }
+        if self.delay.is_some() {
+            return None;
+        }
+        self.auto_shift(dt);
+
+        // holding down makes the block fall faster, but it waits as long as always to lock on every level
+        let period = if self.block.status == BlockStatus::Frozen {
+            MOVING_PERIOD
+        } else if self.pressed.contains(&InputType::Down) {
+            self.stats.gravity() / self.handling.sdf.max(1.0)
+        } else {
+            self.stats.gravity()
+        };
+        if self.waiting_time <= period {
+            return None;
+        }
+        self.waiting_time = 0.0;
+
+        if self.block.status != BlockStatus::Frozen {
+            let before = self.block.position;
+            self.block.move_down(&mut self.board);
+            if self.block.position != before {
+                self.rotated_last = false;
+            }
+            return None;
+        }
+
+        self.effects.lock(self.block.cells());
+        let rows = self.board.full_rows();
+        if !rows.is_empty() {
+            let hidden = self.board.hidden();
+            let cells = rows.iter()
+                .filter(|y| **y >= hidden)
+                .flat_map(|y| self.board.data[*y].iter().enumerate().map(move |(x, cell)| (y - hidden, x, cell.color)))
+                .collect();
+            self.visuals.push(Visual::Cleared { rows: rows.len(), cells });
+        }
+        if !rows.is_empty() && self.delays.line_clear > 0.0 {
+            self.effects.clear(rows, self.delays.line_clear);
+            self.delay = Some(Delay::LineClear(self.delays.line_clear));
+            return None;
+        }
+        Some(self.lock_and_wait())
+    }
+
+    /// Locks the block and spawns the next one, or waits for the entry delay first
+    fn lock_and_wait(&mut self) -> PlayerEvent {
+        let event = self.lock_block();
+        if !matches!(event, PlayerEvent::Locked { .. }) {
+            return event;
+        }
+        if self.delays.entry > 0.0 {
+            self.delay = Some(Delay::Entry(self.delays.entry));
+            return event;
+        }
+        self.spawn_block().unwrap_or(event)
+    }
+
+    /// Clears lines, adds garbage and spawns the next block, should only be called once the block is frozen
+    /// This is what update does without waiting, so games can be simulated as fast as possible
+    pub fn next_block(&mut self) -> PlayerEvent {
+        match self.lock_block() {
+            event @ PlayerEvent::Locked { .. } => self.spawn_block().unwrap_or(event),
+            event => event,
+        }
+    }
+
+    /// Clears lines and adds garbage for the frozen block, the player can top out here by the top out rule
+    fn lock_block(&mut self) -> PlayerEvent {
+        let spot = (self.block.position, self.block.matrix().clone());
+        let fault = finesse::minimum_inputs(&self.spawn.0, &self.spawn.1, spot).is_some_and(|minimum| self.presses > minimum);
+        if fault {
+            self.faults += 1;
+            self.fault_flash = FAULT_FLASH_TIME;
+        }
+
+        let spin = puzzle::is_t_spin(&self.board, &self.block, self.rotated_last);
+        let hidden = self.board.hidden();
+        let locked_out = self.block.cells().iter().all(|(y, _)| *y < hidden);
+        self.effects.collapse(&self.board.full_rows());
+        let lines = self.board.update(&mut self.score);
+        self.stats.lines += lines;
+        self.stats.pieces += 1;
+        let sent = self.garbage.offset(garbage::attack(lines));
+
+        let partly_locked_out = self.board.data[..hidden].iter().flatten().any(|cell| cell.status == CellStatus::Frozen);
+        match self.top_out {
+            TopOut::LockOut if locked_out => return PlayerEvent::ToppedOut,
+            TopOut::PartialLockOut if partly_locked_out => return PlayerEvent::ToppedOut,
+            _ => {}
+        }
+
+        // garbage only comes in when the player did not clear any lines
+        if lines == 0 {
+            let incoming = self.garbage.take();
+            if incoming > 0 {
+                // the hole comes from the seed of the pieces, so a seeded game gets the same garbage as well
+                let hole = self.queue.random_below(self.board.width());
+                if !self.board.add_garbage(incoming as usize, hole) {
+                    return PlayerEvent::ToppedOut;
+                }
+            }
+        }
+        PlayerEvent::Locked { lines, sent, fault, spin }
+    }
+
+    /// Spawns the next block, returns ToppedOut when it has no room
+    fn spawn_block(&mut self) -> Option<PlayerEvent> {
+        let spawn = spawn_position(&self.board);
+        let shape = self.queue.pop();
+        let piece = self.colored(shape);
+        match Block::next(&mut self.board, spawn, &piece, &self.block) {
+            Some(block) => {
+                self.block = block;
+                self.random_color = BlockColor::random();
+                self.spawn = (self.board.clone(), self.block.clone());
+                self.presses = 0;
+                self.held = false;
+                self.rotated_last = false;
+                None
+            },
+            None => Some(PlayerEvent::ToppedOut),
+        }
+    }
+
+    /// Draws the header, board, score and next piece, offset is the x in pixels where the board starts
+    pub fn draw(&self, offset: f64, renderer: &Renderer, glyphs: &mut Glyphs, context: &Context, g2d: &mut G2d) {
+        renderer.draw_image("header", offset, 0.0, context, g2d);
+
+        self.board.draw(offset, renderer, context, g2d);
+        self.effects.draw(&self.board, offset, renderer, context, g2d);
+
+        let hud = &renderer.theme().hud;
+        let texts = [
+            (hud.score, format!("Current score: {}", self.score)),
+            (hud.level, format!("Level: {}", self.stats.level())),
+            (hud.lines, format!("Lines: {}", self.stats.lines)),
+            (hud.time, format!("Time: {}", self.stats.clock())),
+            (hud.pps, format!("PPS: {:.2}", self.stats.pps())),
+            (hud.faults, format!("Finesse faults: {}", self.faults)),
+        ];
+        for (position, text) in texts {
+            if let Some([x, y]) = position {
+                renderer.draw_sized_text(&text, hud.text_size, [offset + x, y], glyphs, context, g2d);
+            }
+        }
+
+        if self.fault_flash > 0.0 {
+            renderer.draw_text("Finesse fault!", offset + FAULT_LOCATION.0, FAULT_LOCATION.1, glyphs, context, g2d);
+        }
+
+        if let Some([x, y]) = hud.next {
+            let (shape, color) = self.preview();
+            renderer.draw_preview(&self.pieces.piece(shape).matrix, color.to_color(), offset + x, y, context, g2d);
+        }
+
+        if let (Some([x, y]), Some(shape)) = (hud.hold, self.hold) {
+            let piece = self.pieces.piece(shape);
+            let color = piece.color.or(self.colors.fixed(shape)).map_or(HOLD_COLOR, |color| color.to_color());
+            renderer.draw_preview(&piece.matrix, color, offset + x, y, context, g2d);
+        }
+    }
+}
+
+#[cfg(test)]
+mod player_tests {
+    use super::*;
+    use crate::board::{Cell, GARBAGE_COLOR};
+
+    /// Plays an O on top of a stack that reaches the first row that is shown, with the given rule
+    fn lock_on_stack(top_out: TopOut) -> PlayerEvent {
+        let mut board = Board::new();
+        for y in 1..board::HEIGHT {
+            for x in 0..4 {
+                board.data[y][x] = Cell{color: GARBAGE_COLOR, status: CellStatus::Frozen};
+            }
+        }
+
+        let mut player = Player::practice(Controls::none(), &board, &[BlockShape::O, BlockShape::O], None);
+        player.top_out = top_out;
+        for input_type in [InputType::Left; 4].into_iter().chain([InputType::Down; 2]) {
+            player.handle_input(input_type);
+        }
+        player.next_block()
+    }
+
+    #[test]
+    fn test_top_out() {
+        // the O ends up with its top row in the hidden rows
+        assert!(matches!(lock_on_stack(TopOut::LockOut), PlayerEvent::Locked { .. }));
+        assert!(matches!(lock_on_stack(TopOut::PartialLockOut), PlayerEvent::ToppedOut));
+
+        assert_eq!("partial".parse(), Ok(TopOut::PartialLockOut));
+        assert_eq!(TopOut::PartialLockOut.to_string(), "partial");
+        assert!("never".parse::<TopOut>().is_err());
+    }
+
+    #[test]
+    fn test_delays() {
+        let mut board = Board::new();
+        for x in (0..4).chain(8..board::WIDTH) {
+            board.data[board::HEIGHT - 1][x] = Cell{color: GARBAGE_COLOR, status: CellStatus::Frozen};
+        }
+        let mut player = Player::practice(Controls::none(), &board, &[BlockShape::I, BlockShape::O], None);
+        player.delays = Delays { line_clear: 0.3, entry: 0.1 };
+        while player.block().status != BlockStatus::Frozen {
+            player.handle_input(InputType::Down);
+        }
+
+        // the full row stays on the board until the line clear delay is over
+        let bottom = player.board().height() - 1;
+        assert!(player.update(0.6).is_none());
+        assert_eq!(player.board().full_rows(), [bottom]);
+        assert!(player.update(0.2).is_none());
+        assert!(matches!(player.update(0.2), Some(PlayerEvent::Locked { lines: 1, .. })));
+        assert!(player.board().is_empty());
+        assert_eq!((player.stats.lines, player.stats.pieces, player.stats.level()), (1, 1, 1));
+
+        // then the next block waits for the entry delay and nothing moves before it spawns
+        player.handle_input(InputType::Left);
+        assert_eq!(player.block().shape(), BlockShape::I);
+        assert!(player.update(0.2).is_none());
+        assert_eq!(player.block().shape(), BlockShape::O);
+        assert!(player.block().status == BlockStatus::Moving);
+    }
+
+    #[test]
+    fn test_stats() {
+        let stats = Stats { lines: 25, pieces: 150, time: 65.5, ..Stats::default() };
+        assert_eq!(stats.level(), 3);
+        assert!(stats.gravity() < Stats::default().gravity());
+        assert_eq!(Stats { start_level: 5, ..stats }.level(), 7);
+        assert!((stats.pps() - 2.29).abs() < 0.01);
+        assert_eq!(stats.clock(), "1:05");
+        assert_eq!(Stats::default().pps(), 0.0);
+    }
+
+    #[test]
+    fn test_seeded_garbage() {
+        let frozen = |seed| {
+            let mut player = Player::seeded(Controls::none(), seed);
+            for _ in 0..4 {
+                player.garbage.receive(2);
+                player.hard_drop();
+                player.next_block();
+            }
+            player.board().data.iter().flatten().map(|cell| cell.status == CellStatus::Frozen).collect::<Vec<_>>()
+        };
+        // the same seed gets its holes in the same columns
+        assert_eq!(frozen(7), frozen(7));
+        assert!(frozen(7).iter().filter(|cell| **cell).count() >= 8 * (board::WIDTH - 1));
+    }
+
+    #[test]
+    fn test_hard_drop() {
+        let mut player = Player::new(Controls::single());
+        assert!(player.hard_drops_with(&Key::Space));
+
+        player.hard_drop();
+        assert!(player.block().status == BlockStatus::Frozen);
+        assert!(player.block().cells().iter().any(|(y, _)| *y == player.board().height() - 1));
+        assert!(matches!(player.take_visuals()[..], [Visual::HardDrop { rows, .. }] if rows > 0));
+        assert!(player.take_visuals().is_empty());
+
+        // it locks on the next update without waiting for the block to fall
+        assert!(matches!(player.update(0.01), Some(PlayerEvent::Locked { .. })));
+        player.update(Delays::default().entry);
+        assert!(player.block().status == BlockStatus::Moving);
+    }
+
+    #[test]
+    fn test_handling() {
+        let mut player = Player::new(Controls::single());
+        player.delays = Delays { line_clear: 0.0, entry: 0.0 };
+        player.handling = Handling { das: 0.2, arr: 0.05, sdf: 10.0 };
+        let x = player.block().position.1;
+
+        // a press moves right away and the keyboard repeating it does nothing
+        assert!(player.press(InputType::Left));
+        assert!(!player.press(InputType::Left));
+        assert_eq!(player.block().position.1, x - 1);
+
+        // after the delay the block slides once for every repeat
+        player.update(0.15);
+        assert_eq!(player.block().position.1, x - 1);
+        player.update(0.15);
+        assert_eq!(player.block().position.1, x - 3);
+
+        // with no repeat delay it slides to the wall
+        player.release(InputType::Left);
+        player.handling.arr = 0.0;
+        player.press(InputType::Right);
+        player.update(0.25);
+        let right = player.block().cells().iter().map(|(_, x)| *x).max();
+        assert_eq!(right, Some(player.board().width() - 1));
+
+        // holding down drops a row every tenth of the usual time
+        player.release(InputType::Right);
+        let y = player.block().position.0;
+        player.press(InputType::Down);
+        player.update(MOVING_PERIOD / 10.0 + 0.01);
+        assert_eq!(player.block().position.0, y + 2);
+        player.release(InputType::Down);
+        player.update(MOVING_PERIOD / 10.0 + 0.01);
+        assert_eq!(player.block().position.0, y + 2);
+    }
+
+    #[test]
+    fn test_controls() {
+        let mut controls = Controls::single();
+        controls.bind(3, Key::X);
+        controls.bind(0, Key::W);
+        assert_eq!(controls.bindings()[3].1, [Key::X]);
+        assert_eq!(controls.bindings()[0].1, [Key::W]);
+        assert_eq!(controls.input(&Key::W), Some(InputType::Left));
+        assert_eq!(controls.input(&Key::Up), None);
+
+        let json = serde_json::to_string(&controls).unwrap();
+        assert_eq!(serde_json::from_str::<Controls>(&json).unwrap(), controls);
+    }
+
+    #[test]
+    fn test_set_pieces() {
+        let mut player = Player::seeded(Controls::none(), 1);
+        player.set_pieces(PieceSet::load("assets/pentominoes.json").unwrap());
+
+        for _ in 0..3 {
+            assert!(matches!(player.block().shape(), BlockShape::Custom(_)));
+            assert_eq!(player.block().cells().len(), 5);
+            assert_eq!(Some(player.preview().1), player.pieces().piece(player.preview().0).color);
+
+            while player.block().status != BlockStatus::Frozen {
+                player.handle_input(InputType::Down);
+            }
+            assert!(matches!(player.next_block(), PlayerEvent::Locked { .. }));
+        }
+    }
+
+    #[test]
+    fn test_colors() {
+        let mut player = Player::new(Controls::none());
+        player.set_colors(ColorPolicy::Guideline);
+
+        for _ in 0..5 {
+            let shape = player.block().shape();
+            assert_eq!(Some(player.block().color), BlockColor::guideline(shape));
+            let (y, x) = player.block().cells()[0];
+            assert_eq!(player.board().data[y][x].color, player.block().color.to_color());
+
+            // the preview shows the color the next block really gets
+            let preview = player.preview();
+            while player.block().status != BlockStatus::Frozen {
+                player.handle_input(InputType::Down);
+            }
+            player.next_block();
+            assert_eq!((player.block().shape(), player.block().color), preview);
+        }
+    }
+}
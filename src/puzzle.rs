@@ -0,0 +1,343 @@
+use std::collections::BTreeSet;
+use std::fs::{self, File};
+use std::io::{self, BufReader};
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    block::Block,
+    blockshape::BlockShape,
+    board::{Board, CellStatus},
+    editor,
+    player::{Controls, Player},
+};
+
+/// how many of the corners around the center of a T have to be filled for a T-spin
+const T_SPIN_CORNERS: usize = 3;
+/// the corners around the center of a T, y and x
+const CORNERS: [(isize, isize); 4] = [(-1, -1), (-1, 1), (1, -1), (1, 1)];
+
+/// Returns true if the block is a T that was rotated into place and at least three corners around its center are filled
+/// The walls and the floor count as filled
+pub fn is_t_spin(board: &Board, block: &Block, rotated_last: bool) -> bool {
+    if block.shape() != BlockShape::T || !rotated_last {
+        return false;
+    }
+
+    // the center is the only cell that touches the three others
+    let cells = block.cells();
+    let Some(&(y, x)) = cells.iter().find(|(y, x)| {
+        cells.iter().filter(|(other_y, other_x)| y.abs_diff(*other_y) + x.abs_diff(*other_x) == 1).count() == 3
+    }) else {
+        return false;
+    };
+
+    let filled = CORNERS.iter()
+        .filter(|(dy, dx)| {
+            let row = usize::try_from(y as isize + dy).ok().and_then(|row| board.data.get(row));
+            let cell = row.and_then(|row| usize::try_from(x as isize + dx).ok().and_then(|column| row.get(column)));
+            cell.is_none_or(|cell| cell.status == CellStatus::Frozen)
+        })
+        .count();
+
+    filled >= T_SPIN_CORNERS
+}
+
+/// What has to be done to solve a puzzle
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Goal {
+    /// clear at least this many lines in total
+    Lines { count: usize },
+    /// clear lines until nothing is left on the board
+    PerfectClear,
+    /// clear this many lines at once with a T-spin
+    TSpin { lines: usize },
+    /// place every piece without leaving a single hole
+    NoHoles,
+}
+
+impl Goal {
+    /// Returns the goal the way it is shown to the player
+    pub fn describe(&self) -> String {
+        match self {
+            Goal::Lines { count: 1 } => String::from("Clear a line"),
+            Goal::Lines { count } => format!("Clear {} lines", count),
+            Goal::PerfectClear => String::from("Perfect clear"),
+            Goal::TSpin { lines: 1 } => String::from("T-spin single"),
+            Goal::TSpin { lines: 2 } => String::from("T-spin double"),
+            Goal::TSpin { lines: 3 } => String::from("T-spin triple"),
+            Goal::TSpin { lines } => format!("T-spin clearing {} lines", lines),
+            Goal::NoHoles => String::from("Leave no holes"),
+        }
+    }
+}
+
+/// A board with a fixed set of pieces and a goal to reach with them
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Puzzle {
+    pub name: String,
+    /// the bottom rows of the board, written the way the editor saves boards
+    pub rows: Vec<String>,
+    /// the pieces in the order they come, the puzzle is failed when they run out
+    pub pieces: Vec<BlockShape>,
+    pub goal: Goal,
+}
+
+impl Puzzle {
+    /// Creates a player on the board of the puzzle with its pieces
+    pub fn player(&self) -> Player {
+        Player::practice(Controls::single(), &editor::board_from_rows(&self.rows), &self.pieces, None)
+    }
+}
+
+/// A file with puzzles that are played one after the other
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PuzzlePack {
+    pub name: String,
+    pub puzzles: Vec<Puzzle>,
+}
+
+impl PuzzlePack {
+    /// Reads a pack from a json file
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<PuzzlePack> {
+        let file = File::open(path)?;
+        Ok(serde_json::from_reader(BufReader::new(file))?)
+    }
+}
+
+/// Where a puzzle stands after a piece was placed
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Outcome {
+    Playing,
+    Solved,
+    Failed,
+}
+
+/// Keeps track of a single try at a puzzle
+pub struct Attempt {
+    goal: Goal,
+    pieces_left: usize,
+    lines: usize,
+}
+
+impl Attempt {
+    pub fn new(puzzle: &Puzzle) -> Attempt {
+        Attempt { goal: puzzle.goal, pieces_left: puzzle.pieces.len(), lines: 0 }
+    }
+
+    /// Returns how many of the pieces of the puzzle have not been placed yet
+    pub fn pieces_left(&self) -> usize {
+        self.pieces_left
+    }
+
+    /// Judges the puzzle after a piece was locked, the board should be the one after lines were cleared
+    pub fn locked(&mut self, board: &Board, lines: usize, spin: bool) -> Outcome {
+        self.pieces_left = self.pieces_left.saturating_sub(1);
+        self.lines += lines;
+
+        let solved = match self.goal {
+            Goal::Lines { count } => self.lines >= count,
+            Goal::PerfectClear => lines > 0 && board.is_empty(),
+            Goal::TSpin { lines: goal } => spin && lines == goal,
+            Goal::NoHoles => self.pieces_left == 0 && board.holes() == 0,
+        };
+
+        if solved {
+            Outcome::Solved
+        } else if self.pieces_left == 0 || (self.goal == Goal::NoHoles && board.holes() > 0) {
+            Outcome::Failed
+        } else {
+            Outcome::Playing
+        }
+    }
+}
+
+/// A pack of puzzles that is being played, remembers which puzzles are solved in a file next to the pack
+pub struct Puzzles {
+    pack: PuzzlePack,
+    /// the names of the solved puzzles are saved here
+    progress_path: PathBuf,
+    solved: BTreeSet<String>,
+    current: usize,
+    attempt: Attempt,
+    outcome: Outcome,
+}
+
+impl Puzzles {
+    /// Loads a pack and what was solved of it before, starting at the first puzzle that is not solved yet
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<Puzzles> {
+        let pack = PuzzlePack::load(&path)?;
+        if pack.puzzles.is_empty() {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "the pack has no puzzles"));
+        }
+
+        let progress_path = path.as_ref().with_extension("progress.json");
+        let solved: BTreeSet<String> = File::open(&progress_path).ok()
+            .and_then(|file| serde_json::from_reader(BufReader::new(file)).ok())
+            .unwrap_or_default();
+
+        let current = pack.puzzles.iter().position(|puzzle| !solved.contains(&puzzle.name)).unwrap_or(0);
+        let attempt = Attempt::new(&pack.puzzles[current]);
+        Ok(Puzzles { pack, progress_path, solved, current, attempt, outcome: Outcome::Playing })
+    }
+
+    /// Returns the puzzle that is being played
+    pub fn current(&self) -> &Puzzle {
+        &self.pack.puzzles[self.current]
+    }
+
+    /// Returns the place of the current puzzle in the pack
+    pub fn index(&self) -> usize {
+        self.current
+    }
+
+    /// Returns the amount of puzzles in the pack
+    pub fn len(&self) -> usize {
+        self.pack.puzzles.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pack.puzzles.is_empty()
+    }
+
+    /// Returns true if the puzzle at the index was solved, now or some time before
+    pub fn is_solved(&self, index: usize) -> bool {
+        self.pack.puzzles.get(index).is_some_and(|puzzle| self.solved.contains(&puzzle.name))
+    }
+
+    pub fn attempt(&self) -> &Attempt {
+        &self.attempt
+    }
+
+    /// Returns how the last try at the current puzzle ended
+    pub fn outcome(&self) -> Outcome {
+        self.outcome
+    }
+
+    /// Starts the puzzle at the index from the beginning and returns the player for it
+    pub fn select(&mut self, index: usize) -> Player {
+        self.current = index.min(self.len() - 1);
+        self.attempt = Attempt::new(self.current());
+        self.outcome = Outcome::Playing;
+        self.current().player()
+    }
+
+    /// Goes on with the next puzzle when the current one was solved, otherwise the current one starts over
+    pub fn restart(&mut self) -> Player {
+        let index = if self.outcome == Outcome::Solved { (self.current + 1) % self.len() } else { self.current };
+        self.select(index)
+    }
+
+    /// Judges the current puzzle after a piece was locked, a solved puzzle is saved right away
+    pub fn locked(&mut self, board: &Board, lines: usize, spin: bool) -> Outcome {
+        self.outcome = self.attempt.locked(board, lines, spin);
+
+        if self.outcome == Outcome::Solved {
+            let name = self.current().name.clone();
+            self.solved.insert(name);
+            let saved = serde_json::to_string_pretty(&self.solved).map_err(io::Error::from)
+                .and_then(|json| fs::write(&self.progress_path, json));
+            if let Err(error) = saved {
+                eprintln!("Could not save which puzzles are solved: {}", error);
+            }
+        }
+        self.outcome
+    }
+}
+
+#[cfg(test)]
+mod puzzle_tests {
+    use super::*;
+    use crate::{
+        block::BlockStatus,
+        player::{InputType, PlayerEvent},
+    };
+
+    /// the pack that comes with the game
+    fn pack() -> PuzzlePack {
+        serde_json::from_str(include_str!("../assets/puzzles.json")).unwrap()
+    }
+
+    /// Plays the inputs, then lets the block fall until it locks, returns how the puzzle stands after that
+    fn play(player: &mut Player, attempt: &mut Attempt, inputs: &[InputType]) -> Outcome {
+        for input_type in inputs {
+            player.handle_input(*input_type);
+        }
+        while player.block().status != BlockStatus::Frozen {
+            player.handle_input(InputType::Down);
+        }
+
+        match player.next_block() {
+            PlayerEvent::Locked { lines, spin, .. } => attempt.locked(player.board(), lines, spin),
+            PlayerEvent::ToppedOut => Outcome::Failed,
+        }
+    }
+
+    fn puzzle(goal: Goal) -> Puzzle {
+        pack().puzzles.into_iter().find(|puzzle| puzzle.goal == goal).unwrap()
+    }
+
+    #[test]
+    fn test_t_spin_double() {
+        let puzzle = puzzle(Goal::TSpin { lines: 2 });
+        let mut player = puzzle.player();
+        let mut attempt = Attempt::new(&puzzle);
+
+        // the T goes down standing up and is turned flat right above the slot
+        let mut inputs = vec![InputType::Left, InputType::Rotate];
+        inputs.extend([InputType::Down; 13]);
+        inputs.push(InputType::Rotate);
+        assert_eq!(play(&mut player, &mut attempt, &inputs), Outcome::Solved);
+    }
+
+    #[test]
+    fn test_goals() {
+        // dropping the T flat in the middle does not clear anything and uses up the only piece
+        let t_spin = puzzle(Goal::TSpin { lines: 2 });
+        assert_eq!(play(&mut t_spin.player(), &mut Attempt::new(&t_spin), &[]), Outcome::Failed);
+
+        let perfect_clear = puzzle(Goal::PerfectClear);
+        assert_eq!(play(&mut perfect_clear.player(), &mut Attempt::new(&perfect_clear), &[InputType::Left]), Outcome::Solved);
+
+        let lines = puzzle(Goal::Lines { count: 1 });
+        assert_eq!(play(&mut lines.player(), &mut Attempt::new(&lines), &[InputType::Right; 2]), Outcome::Solved);
+
+        // the first O leaves no holes but there is another piece to place, the second one fills the row
+        let no_holes = puzzle(Goal::NoHoles);
+        let mut player = no_holes.player();
+        let mut attempt = Attempt::new(&no_holes);
+        assert_eq!(play(&mut player, &mut attempt, &[InputType::Left]), Outcome::Playing);
+        assert_eq!(play(&mut player, &mut attempt, &[InputType::Right]), Outcome::Solved);
+
+        // an O on top of the stack leaves a hole under it
+        let mut player = no_holes.player();
+        let mut attempt = Attempt::new(&no_holes);
+        assert_eq!(play(&mut player, &mut attempt, &[InputType::Right; 2]), Outcome::Failed);
+    }
+
+    #[test]
+    fn test_progress() {
+        let directory = std::env::temp_dir().join("rust_tetris_test_puzzles");
+        fs::create_dir_all(&directory).unwrap();
+        let path = directory.join("pack.json");
+        fs::write(&path, include_str!("../assets/puzzles.json")).unwrap();
+        let _ = fs::remove_file(path.with_extension("progress.json"));
+
+        let mut puzzles = Puzzles::load(&path).unwrap();
+        assert_eq!(puzzles.index(), 0);
+        let player = puzzles.select(0);
+        puzzles.attempt = Attempt { goal: Goal::Lines { count: 0 }, ..Attempt::new(puzzles.current()) };
+        assert_eq!(puzzles.locked(player.board(), 0, false), Outcome::Solved);
+
+        // solving a puzzle moves on to the next one, and the next time the pack is loaded it starts there as well
+        puzzles.restart();
+        assert_eq!(puzzles.index(), 1);
+        let loaded = Puzzles::load(&path).unwrap();
+        assert!(loaded.is_solved(0));
+        assert_eq!(loaded.index(), 1);
+
+        fs::remove_dir_all(&directory).unwrap();
+    }
+}
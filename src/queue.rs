@@ -0,0 +1,259 @@
+use std::collections::VecDeque;
+use std::fmt;
+use std::str::FromStr;
+
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::{Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
+
+use crate::blockshape::{BlockShape, SHAPES};
+
+/// how many upcoming pieces are known ahead of time
+const QUEUE_SIZE: usize = 3;
+
+/// How the random pieces are picked
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum Randomizer {
+    /// any shape but the one that came right before it
+    #[default]
+    Classic,
+    /// every shape once in a shuffled bag, then the next bag
+    Bag,
+    /// any shape, the same one can come again and again
+    Random,
+}
+
+impl FromStr for Randomizer {
+    type Err = String;
+
+    /// Reads a randomizer written as classic, bag or random
+    fn from_str(text: &str) -> Result<Randomizer, String> {
+        match text {
+            "classic" => Ok(Randomizer::Classic),
+            "bag" => Ok(Randomizer::Bag),
+            "random" => Ok(Randomizer::Random),
+            _ => Err(format!("{} is not a randomizer, use classic, bag or random", text)),
+        }
+    }
+}
+
+impl fmt::Display for Randomizer {
+    /// Writes the randomizer the way it is read
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Randomizer::Classic => write!(f, "classic"),
+            Randomizer::Bag => write!(f, "bag"),
+            Randomizer::Random => write!(f, "random"),
+        }
+    }
+}
+
+/// holds the upcoming pieces of a player
+pub struct PieceQueue {
+    pieces: VecDeque<BlockShape>,
+    /// the shapes the random pieces are picked from
+    shapes: Vec<BlockShape>,
+    randomizer: Randomizer,
+    /// what is left in the bag, the last shape comes out first
+    bag: Vec<BlockShape>,
+    rng: StdRng,
+}
+
+impl Default for PieceQueue {
+    fn default() -> Self {
+        PieceQueue::new()
+    }
+}
+
+impl PieceQueue {
+    /// Creates a new queue with random pieces, two pieces in a row are never the same
+    pub fn new() -> PieceQueue {
+        PieceQueue::with_rng(StdRng::from_entropy())
+    }
+
+    /// Creates a new queue that always gives the same pieces for the same seed
+    pub fn seeded(seed: u64) -> PieceQueue {
+        PieceQueue::with_rng(StdRng::seed_from_u64(seed))
+    }
+
+    /// Creates a new queue that gives the given pieces first and random ones after them
+    pub fn starting_with(pieces: &[BlockShape]) -> PieceQueue {
+        let mut queue = PieceQueue::new();
+        if !pieces.is_empty() {
+            queue.pieces = pieces.iter().copied().collect();
+            queue.fill();
+        }
+        queue
+    }
+
+    fn with_rng(rng: StdRng) -> PieceQueue {
+        let mut queue = PieceQueue {
+            pieces: VecDeque::with_capacity(QUEUE_SIZE),
+            shapes: SHAPES.to_vec(),
+            randomizer: Randomizer::default(),
+            bag: Vec::new(),
+            rng,
+        };
+        queue.fill();
+        queue
+    }
+
+    /// Picks the random pieces from the given shapes from now on, the pieces already in the queue are replaced
+    pub fn set_shapes(&mut self, shapes: &[BlockShape]) {
+        assert!(!shapes.is_empty(), "The queue needs at least one shape");
+        self.shapes = shapes.to_vec();
+        self.pieces.clear();
+        self.bag.clear();
+        self.fill();
+    }
+
+    /// Starts the random pieces over from the seed, the pieces already in the queue are replaced
+    /// The pieces only depend on the seed, the shapes and the randomizer, whatever came before
+    pub fn reseed(&mut self, seed: u64) {
+        self.rng = StdRng::seed_from_u64(seed);
+        self.pieces.clear();
+        self.bag.clear();
+        self.fill();
+    }
+
+    /// Picks the random pieces with the given randomizer from now on, the pieces already in the queue are replaced
+    pub fn set_randomizer(&mut self, randomizer: Randomizer) {
+        self.randomizer = randomizer;
+        self.pieces.clear();
+        self.bag.clear();
+        self.fill();
+    }
+
+    /// Takes the first piece out of the queue and adds a new one at the end
+    pub fn pop(&mut self) -> BlockShape {
+        let shape = self.pieces.pop_front().expect("The queue is never empty");
+        self.fill();
+        shape
+    }
+
+    /// Adds random pieces at the end until the queue is full
+    fn fill(&mut self) {
+        while self.pieces.len() < QUEUE_SIZE {
+            let shape = self.random_shape();
+            self.pieces.push_back(shape);
+        }
+    }
+
+    /// Picks the next random shape with the randomizer
+    /// The classic one never picks the same shape as the one before it, unless there is only one shape
+    fn random_shape(&mut self) -> BlockShape {
+        match self.randomizer {
+            Randomizer::Classic => {
+                let last = self.pieces.back().copied();
+                let mut shape = self.shapes[self.rng.gen_range(0..self.shapes.len())];
+                while self.shapes.len() > 1 && Some(shape) == last {
+                    shape = self.shapes[self.rng.gen_range(0..self.shapes.len())];
+                }
+                shape
+            },
+            Randomizer::Bag => {
+                if self.bag.is_empty() {
+                    self.bag = self.shapes.clone();
+                    self.bag.shuffle(&mut self.rng);
+                }
+                self.bag.pop().expect("The bag was just filled")
+            },
+            Randomizer::Random => self.shapes[self.rng.gen_range(0..self.shapes.len())],
+        }
+    }
+
+    /// Picks a number below the limit from the same random numbers as the pieces, so a seeded game plays out the same every time
+    pub fn random_below(&mut self, limit: usize) -> usize {
+        self.rng.gen_range(0..limit)
+    }
+
+    /// Returns every piece in the queue in the order they will come
+    pub fn pieces(&self) -> Vec<BlockShape> {
+        self.pieces.iter().copied().collect()
+    }
+
+    /// Returns the piece that will come next without taking it out of the queue
+    pub fn peek(&self) -> BlockShape {
+        self.pieces[0]
+    }
+}
+
+#[cfg(test)]
+mod queue_tests {
+    use super::*;
+
+    #[test]
+    fn test_seeded() {
+        let mut first = PieceQueue::seeded(7);
+        let mut second = PieceQueue::seeded(7);
+
+        let mut last = None;
+        for _ in 0..50 {
+            let shape = first.pop();
+            assert_eq!(shape, second.pop());
+            // the same shape never comes twice in a row
+            assert_ne!(Some(shape), last);
+            last = Some(shape);
+        }
+    }
+
+    #[test]
+    fn test_starting_with() {
+        let pieces = [BlockShape::T, BlockShape::T, BlockShape::I, BlockShape::O];
+        let mut queue = PieceQueue::starting_with(&pieces);
+
+        assert_eq!(queue.pieces(), pieces);
+        for shape in pieces {
+            assert_eq!(queue.pop(), shape);
+        }
+        // random pieces follow once the given ones are used up
+        assert_eq!(queue.pieces().len(), QUEUE_SIZE);
+        assert_ne!(queue.peek(), BlockShape::O);
+    }
+
+    #[test]
+    fn test_set_shapes() {
+        let mut queue = PieceQueue::seeded(7);
+        queue.set_shapes(&[BlockShape::Custom(0), BlockShape::Custom(1)]);
+        for _ in 0..20 {
+            assert!(matches!(queue.pop(), BlockShape::Custom(0 | 1)));
+        }
+
+        // a single shape repeats
+        queue.set_shapes(&[BlockShape::Custom(2)]);
+        assert_eq!(queue.pieces(), [BlockShape::Custom(2); QUEUE_SIZE]);
+    }
+
+    #[test]
+    fn test_bag() {
+        let mut queue = PieceQueue::seeded(7);
+        queue.set_randomizer(Randomizer::Bag);
+
+        // every bag has every shape once
+        for _ in 0..3 {
+            let mut bag: Vec<BlockShape> = (0..SHAPES.len()).map(|_| queue.pop()).collect();
+            bag.sort_by_key(|shape| SHAPES.iter().position(|other| other == shape));
+            assert_eq!(bag, SHAPES);
+        }
+
+        assert_eq!("bag".parse::<Randomizer>(), Ok(Randomizer::Bag));
+        assert!("sevens".parse::<Randomizer>().is_err());
+        assert_eq!(Randomizer::Random.to_string(), "random");
+    }
+
+    #[test]
+    fn test_reseed() {
+        let mut first = PieceQueue::new();
+        first.set_randomizer(Randomizer::Bag);
+        first.pop();
+        first.reseed(3);
+
+        let mut second = PieceQueue::seeded(5);
+        second.set_randomizer(Randomizer::Bag);
+        second.reseed(3);
+        for _ in 0..20 {
+            assert_eq!(first.pop(), second.pop());
+        }
+    }
+}
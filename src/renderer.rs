@@ -1,51 +1,113 @@
 use std::path::Path;
 use std::collections::HashMap;
-use piston_window::{types::Color, rectangle, image, Context, G2d, PistonWindow, Texture, Flip, TextureSettings, Transformed, Text, color::BLACK, Glyphs, G2dTextureContext, G2dTexture};
+use sdl2_window::Sdl2Window;
+use piston_window::{types::Color, rectangle, ellipse, line, polygon, image, Context, G2d, PistonWindow, Texture, Flip, TextureSettings, Transformed, Text, Glyphs, G2dTextureContext, G2dTexture, Image, ImageSize, Rectangle};
+
+use crate::{
+    accessibility::{Accessibility, Pattern},
+    blockcolor::BlockColor,
+    theme::Theme,
+};
 
 /// how big the blocks will be in pixels
 pub const BLOCK_SIZE: f64 = 25.0;
-/// this is used to take out a border on the blocks to give it a more retro feel
-const BLOCK_BORDER_SIZE: f64 = 1.0;
 /// the size of the border at the top of the screen wherein the score is displayed
 pub const BORDER: f64 = 80.0;
+/// how big the blocks of the next piece preview are in pixels
+const PREVIEW_BLOCK_SIZE: f64 = 10.0;
+/// how big text is in pixels of the layout
+pub const TEXT_SIZE: f64 = 20.0;
+/// patterns are dark and see-through so they show on every block color
+const PATTERN_COLOR: Color = [0.0, 0.0, 0.0, 0.55];
 
-/// Renderer struct holds all the images
+/// Renderer struct holds all the images and the theme they came from
 pub struct Renderer {
    images: HashMap<String, G2dTexture>,
+   /// the tiles the blocks are drawn with, when the theme has them
+   skin: Option<G2dTexture>,
+   theme: Theme,
+   accessibility: Accessibility,
+   /// how many pixels of the screen a pixel of the layout takes up, text is drawn at this size so it stays sharp
+   text_scale: f64,
+}
+
+/// Where the layout of the game is in the window, scaled to fit it and centered with bars on the sides that are left over
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Viewport {
+    /// how many pixels of the window a pixel of the layout takes up
+    pub scale: f64,
+    /// where the top left corner of the layout is in the window
+    pub offset: [f64; 2],
+}
+
+impl Default for Viewport {
+    fn default() -> Self {
+        Viewport { scale: 1.0, offset: [0.0, 0.0] }
+    }
+}
+
+impl Viewport {
+    /// Fits a layout of the given size in a window of the given size, keeping its aspect ratio
+    pub fn fit(layout: [f64; 2], window: [f64; 2]) -> Viewport {
+        let scale = (window[0] / layout[0]).min(window[1] / layout[1]);
+        // a minimized window has no size, nothing can be seen then anyway
+        if !scale.is_normal() {
+            return Viewport::default();
+        }
+        Viewport {
+            scale,
+            offset: [(window[0] - layout[0] * scale) / 2.0, (window[1] - layout[1] * scale) / 2.0],
+        }
+    }
+
+    /// Returns the context the layout is drawn with
+    pub fn transform(&self, context: Context) -> Context {
+        context.trans(self.offset[0], self.offset[1]).scale(self.scale, self.scale)
+    }
+
+    /// Turns a position in the window into one in the layout
+    pub fn to_layout(&self, position: [f64; 2]) -> [f64; 2] {
+        [(position[0] - self.offset[0]) / self.scale, (position[1] - self.offset[1]) / self.scale]
+    }
 }
 
 impl Renderer {
-    /// Creates a new instance of Renderer and loads all necessary textures
-    pub fn new(window: &mut PistonWindow) -> Self {
+    /// Creates a new instance of Renderer and loads all the images of the theme
+    pub fn new(window: &mut PistonWindow<Sdl2Window>, theme: &Theme) -> Self {
         let mut texture_context: G2dTextureContext = G2dTextureContext {
             factory: window.factory.clone(),
             encoder: window.factory.create_command_buffer().into(),
         };
 
         let textures = HashMap::new();
-        let mut renderer: Renderer = Renderer { images: textures };
+        let mut renderer: Renderer = Renderer { images: textures, skin: None, theme: theme.clone(), accessibility: Accessibility::default(), text_scale: 1.0 };
 
-        renderer.add_image_file("paused", &mut texture_context);
-        renderer.add_image_file("game_over", &mut texture_context);
-        renderer.add_image_file("startup", &mut texture_context);
-        renderer.add_image_file("header", &mut texture_context);
+        for (name, file) in &theme.images {
+            renderer.add_image_file(name, &theme.file(file), &mut texture_context);
+        }
+        renderer.skin = theme.skin.as_ref().map(|file| load_texture(&theme.file(file), &mut texture_context));
 
         renderer
     }
 
-    /// Adds an image file to the textures in the struct according to a given string
-    fn add_image_file(&mut self, name: &str, texture_context: &mut G2dTextureContext ) {
-        let filename = format!("assets/{}.png", name);
+    /// Returns the theme the images were loaded from
+    pub fn theme(&self) -> &Theme {
+        &self.theme
+    }
 
-        let texture = Texture::from_path(
-            texture_context,
-            Path::new(filename.as_str()),
-            Flip::None,
-            &TextureSettings::new(),
-        )
-        .expect("Failed to load texture");
+    /// Changes the palette and patterns used for players who can't tell every block color apart
+    pub fn set_accessibility(&mut self, accessibility: Accessibility) {
+        self.accessibility = accessibility;
+    }
 
-        self.images.insert(String::from(name), texture);
+    /// Changes how many pixels of the screen a pixel of the layout takes up
+    pub fn set_text_scale(&mut self, text_scale: f64) {
+        self.text_scale = text_scale;
+    }
+
+    /// Adds an image file to the textures in the struct under the given name
+    fn add_image_file(&mut self, name: &str, path: &Path, texture_context: &mut G2dTextureContext ) {
+        self.images.insert(String::from(name), load_texture(path, texture_context));
     }
 
     /// Draws an image from the textures in the struct according to a given string
@@ -53,31 +115,124 @@ impl Renderer {
        image(self.images.get(name).expect("Image failed to load!"), context.transform.trans(x, y), g2d);
     }
 
-    /// Draws text on the screen with a given string
-    pub fn draw_text(&self, text: &str, glyphs: &mut Glyphs, context: &Context, g2d: &mut G2d) {
-        Text::new_color(BLACK, 20)
+    /// Draws text on the screen with a given string at the given coordinates
+    pub fn draw_text(&self, text: &str, x: f64, y: f64, glyphs: &mut Glyphs, context: &Context, g2d: &mut G2d) {
+        self.draw_sized_text(text, TEXT_SIZE, [x, y], glyphs, context, g2d);
+    }
+
+    /// Draws text of the given size in pixels at the given coordinates
+    pub fn draw_sized_text(&self, text: &str, size: f64, position: [f64; 2], glyphs: &mut Glyphs, context: &Context, g2d: &mut G2d) {
+        // the font is drawn at the size it has on screen and scaled back, so it is not blown up from a small one
+        Text::new_color(self.theme.text, (size * self.text_scale).round() as u32)
         .draw(
             text,
             glyphs,
             &context.draw_state,
-            context.transform.trans(10.0, 50.0),
+            context.transform.trans(position[0], position[1]).scale(1.0 / self.text_scale, 1.0 / self.text_scale),
             g2d,
         )
         .unwrap();
     }
+
+    /// Draws a block with a given color at the specified coordinates on the screen, the border of the theme is taken out of BLOCK_SIZE
+    /// offset is the x in pixels where the board starts, so multiple boards can be drawn next to each other
+    pub fn draw_block(&self, color: Color, x: f64, y: f64, offset: f64, context: &Context, g2d: &mut G2d) {
+        let border = self.theme.block_border;
+        self.draw_tile(
+            color,
+            [x * BLOCK_SIZE - border + offset, 
+                y * BLOCK_SIZE - border + BORDER, 
+                BLOCK_SIZE - border,
+                BLOCK_SIZE - border],
+            context, g2d,
+        )
+    }
+
+    /// Draws a small version of a block matrix in the header, used to preview the next piece
+    pub fn draw_preview(&self, matrix: &[Vec<i32>], color: Color, x: f64, y: f64, context: &Context, g2d: &mut G2d) {
+        let border = self.theme.block_border;
+        // empty rows at the top are left out so bigger pieces still fit
+        let rows = matrix.iter().skip_while(|cells| cells.iter().all(|cell| *cell == 0));
+        for (row, cells) in rows.enumerate() {
+            for (column, cell) in cells.iter().enumerate() {
+                if *cell == 1 {
+                    self.draw_tile(
+                        color,
+                        [x + column as f64 * PREVIEW_BLOCK_SIZE,
+                            y + row as f64 * PREVIEW_BLOCK_SIZE,
+                            PREVIEW_BLOCK_SIZE - border,
+                            PREVIEW_BLOCK_SIZE - border],
+                        context, g2d,
+                    );
+                }
+            }
+        }
+    }
+
+    /// Draws one block in the rectangle, with its tile when the theme has a skin and in the color of the palette otherwise
+    /// The palette for color blindness goes over both, and the pattern of the color is drawn on top when patterns are on
+    fn draw_tile(&self, color: Color, rect: [f64; 4], context: &Context, g2d: &mut G2d) {
+        let block_color = BlockColor::from_color(color);
+        let accessible = block_color.and_then(|block_color| self.accessibility.vision.color(block_color));
+
+        match (&self.skin, self.theme.tile(color), accessible) {
+            (Some(skin), Some(tile), None) => {
+                let size = skin.get_height() as f64;
+                Image::new()
+                    .src_rect([tile as f64 * size, 0.0, size, size])
+                    .rect(rect)
+                    .draw(skin, &context.draw_state, context.transform, g2d);
+            },
+            _ => rectangle(accessible.unwrap_or_else(|| self.theme.color(color)), rect, context.transform, g2d),
+        }
+
+        if let (true, Some(block_color)) = (self.accessibility.patterns, block_color) {
+            draw_pattern(Pattern::of(block_color), rect, context, g2d);
+        }
+    }
 }
 
-/// Draws a block with a given color at the specified coordinates on the screen, the size is always BLOCK_SIZE - BLOCK_BORDER_SIZE
-pub fn draw_block(color: Color, x: f64, y: f64, context: &Context, g2d: &mut G2d) {
-    rectangle(
-        color,
-        [x * BLOCK_SIZE - BLOCK_BORDER_SIZE, 
-            y * BLOCK_SIZE - BLOCK_BORDER_SIZE + BORDER, 
-            BLOCK_SIZE - BLOCK_BORDER_SIZE,
-            BLOCK_SIZE - BLOCK_BORDER_SIZE],
-        context.transform,
-        g2d,
+/// Draws a pattern inside the rectangle of a block, it keeps some space from the edges
+fn draw_pattern(pattern: Pattern, rect: [f64; 4], context: &Context, g2d: &mut G2d) {
+    let [x, y, width, height] = rect;
+    let inset = width / 5.0;
+    let thickness = (width / 6.0).max(1.0);
+    let (left, top, right, bottom) = (x + inset, y + inset, x + width - inset, y + height - inset);
+    let (center_x, center_y) = (x + width / 2.0, y + height / 2.0);
+
+    let horizontal = [left, center_y - thickness / 2.0, right - left, thickness];
+    let vertical = [center_x - thickness / 2.0, top, thickness, bottom - top];
+    match pattern {
+        Pattern::Dot => ellipse(PATTERN_COLOR, [center_x - width / 5.0, center_y - height / 5.0, width * 0.4, height * 0.4], context.transform, g2d),
+        Pattern::HorizontalBar => rectangle(PATTERN_COLOR, horizontal, context.transform, g2d),
+        Pattern::VerticalBar => rectangle(PATTERN_COLOR, vertical, context.transform, g2d),
+        Pattern::Plus => {
+            rectangle(PATTERN_COLOR, horizontal, context.transform, g2d);
+            rectangle(PATTERN_COLOR, vertical, context.transform, g2d);
+        },
+        Pattern::Cross => {
+            line(PATTERN_COLOR, thickness / 2.0, [left, top, right, bottom], context.transform, g2d);
+            line(PATTERN_COLOR, thickness / 2.0, [left, bottom, right, top], context.transform, g2d);
+        },
+        Pattern::Frame => Rectangle::new_border(PATTERN_COLOR, thickness / 2.0)
+            .draw([left, top, right - left, bottom - top], &context.draw_state, context.transform, g2d),
+        Pattern::Corners => {
+            rectangle(PATTERN_COLOR, [left, top, thickness * 1.5, thickness * 1.5], context.transform, g2d);
+            rectangle(PATTERN_COLOR, [right - thickness * 1.5, bottom - thickness * 1.5, thickness * 1.5, thickness * 1.5], context.transform, g2d);
+        },
+        Pattern::Triangle => polygon(PATTERN_COLOR, &[[center_x, top], [left, bottom], [right, bottom]], context.transform, g2d),
+    }
+}
+
+/// Loads a png file as a texture
+fn load_texture(path: &Path, texture_context: &mut G2dTextureContext) -> G2dTexture {
+    Texture::from_path(
+        texture_context,
+        path,
+        Flip::None,
+        &TextureSettings::new(),
     )
+    .unwrap_or_else(|error| panic!("Failed to load texture {}: {}", path.display(), error))
 }
 
 /// Draws a rectangle at the given coordinates (this allows you to set the size of the rect)
@@ -90,3 +245,20 @@ pub fn draw_rect(color: Color, x: f64, y: f64, x2: f64, y2: f64, context: &Conte
     )
 }
 
+#[cfg(test)]
+mod renderer_tests {
+    use super::*;
+
+    #[test]
+    fn test_viewport() {
+        // a window twice as wide as it needs to be has bars on the left and right
+        let viewport = Viewport::fit([250.0, 580.0], [1000.0, 1160.0]);
+        assert_eq!(viewport, Viewport { scale: 2.0, offset: [250.0, 0.0] });
+        assert_eq!(viewport.to_layout([250.0, 0.0]), [0.0, 0.0]);
+        assert_eq!(viewport.to_layout([750.0, 1160.0]), [250.0, 580.0]);
+
+        // and a tall one has them at the top and bottom
+        assert_eq!(Viewport::fit([250.0, 580.0], [125.0, 1000.0]), Viewport { scale: 0.5, offset: [0.0, 355.0] });
+        assert_eq!(Viewport::fit([250.0, 580.0], [0.0, 0.0]), Viewport::default());
+    }
+}
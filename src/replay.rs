@@ -0,0 +1,183 @@
+use std::fs::{self, File};
+use std::io::{self, BufReader};
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    pieces::PieceSet,
+    player::{Controls, InputType, Player, PlayerEvent},
+    settings::Settings,
+};
+
+/// Something the player did to their block
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum Input {
+    Press(InputType),
+    Release(InputType),
+    Hold,
+    HardDrop,
+}
+
+impl Input {
+    /// Does the input to the player
+    pub fn apply(self, player: &mut Player) {
+        match self {
+            Input::Press(input_type) => {
+                player.press(input_type);
+            },
+            Input::Release(input_type) => player.release(input_type),
+            Input::Hold => player.hold(),
+            Input::HardDrop => player.hard_drop(),
+        }
+    }
+}
+
+/// One update of the game, with the inputs that came before it
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Frame {
+    /// how many seconds the update was for
+    pub dt: f64,
+    pub inputs: Vec<Input>,
+}
+
+/// A single player game with the standard pieces, played again the same way from its seed and inputs
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Replay {
+    /// the seed the pieces came from
+    pub seed: u64,
+    /// the level the game started on
+    pub level: usize,
+    /// the settings the game started with, only the ones that change how it plays are used
+    pub settings: Settings,
+    pub frames: Vec<Frame>,
+}
+
+impl Replay {
+    /// Reads a replay from a json file
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<Replay> {
+        let file = File::open(path)?;
+        Ok(serde_json::from_reader(BufReader::new(file))?)
+    }
+
+    /// Writes the replay to a json file
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        fs::write(path, serde_json::to_string(self)?)
+    }
+
+    /// Plays the whole game without a window, returns the player as it ended and true if it topped out
+    pub fn play(&self) -> (Player, bool) {
+        let mut player = player(&self.settings, &PieceSet::standard(), self.seed, self.level);
+        for frame in &self.frames {
+            for input in &frame.inputs {
+                input.apply(&mut player);
+            }
+            if let Some(PlayerEvent::ToppedOut) = player.update(frame.dt) {
+                return (player, true);
+            }
+        }
+        (player, false)
+    }
+}
+
+/// Keeps the inputs and updates of a game as it is played, to be written to a file once it is over
+pub struct Recorder {
+    path: PathBuf,
+    replay: Replay,
+    /// what the player did since the last update
+    inputs: Vec<Input>,
+}
+
+impl Recorder {
+    /// Starts a replay that is written to the given file
+    pub fn new<P: AsRef<Path>>(path: P) -> Recorder {
+        Recorder { path: path.as_ref().to_path_buf(), replay: Replay::default(), inputs: Vec::new() }
+    }
+
+    /// Returns true before the first update, while the game has not started yet
+    pub fn is_empty(&self) -> bool {
+        self.replay.frames.is_empty()
+    }
+
+    /// Keeps the seed, level and settings the game starts with
+    pub fn start(&mut self, seed: u64, level: usize, settings: Settings) {
+        self.replay.seed = seed;
+        self.replay.level = level;
+        self.replay.settings = settings;
+    }
+
+    /// Keeps an input, it belongs to the next update
+    pub fn input(&mut self, input: Input) {
+        self.inputs.push(input);
+    }
+
+    /// Keeps an update together with the inputs that came before it
+    pub fn frame(&mut self, dt: f64) {
+        let inputs = std::mem::take(&mut self.inputs);
+        self.replay.frames.push(Frame { dt, inputs });
+    }
+
+    /// Writes the replay to its file
+    pub fn save(&self) -> io::Result<()> {
+        self.replay.save(&self.path)
+    }
+
+    /// Returns the file the replay is written to
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+/// Creates a player the way the game sets one up with the settings, the pieces come from the seed
+pub fn player(settings: &Settings, pieces: &PieceSet, seed: u64, level: usize) -> Player {
+    let mut player = Player::sized(Controls::none(), settings.size);
+    player.set_pieces(pieces.clone());
+    player.set_randomizer(settings.randomizer);
+    player.set_colors(settings.colors);
+    player.set_rotation(settings.rotation);
+    player.top_out = settings.top_out;
+    player.delays = settings.delays;
+    player.handling = settings.handling;
+    player.set_level(level);
+    player.set_seed(seed);
+    player
+}
+
+#[cfg(test)]
+mod replay_tests {
+    use super::*;
+    use crate::{blockcolor::ColorPolicy, queue::Randomizer};
+
+    #[test]
+    fn test_play() {
+        // the colors are random with the other policies, which does not change how the game plays but would change the board
+        let settings = Settings { randomizer: Randomizer::Bag, colors: ColorPolicy::Guideline, ..Settings::default() };
+        let mut recorder = Recorder::new(std::env::temp_dir().join("rust_tetris_test_replay.json"));
+        recorder.start(9, 3, settings.clone());
+
+        // a game played live, with the inputs and updates going to the recorder as well
+        let mut live = player(&settings, &PieceSet::standard(), 9, 3);
+        let inputs = [Input::Press(InputType::Left), Input::Release(InputType::Left), Input::Hold, Input::HardDrop];
+        let mut topped_out = false;
+        for step in 0..100 {
+            if let Some(input) = inputs.get(step % 20) {
+                input.apply(&mut live);
+                recorder.input(*input);
+            }
+            recorder.frame(0.05);
+            if let Some(PlayerEvent::ToppedOut) = live.update(0.05) {
+                topped_out = true;
+                break;
+            }
+        }
+        recorder.save().unwrap();
+
+        let replay = Replay::load(recorder.path()).unwrap();
+        let (replayed, replay_topped_out) = replay.play();
+        assert_eq!(replay_topped_out, topped_out);
+        assert_eq!(replayed.board().data, live.board().data);
+        assert_eq!(replayed.stats, live.stats);
+        assert_eq!(replayed.stats.level(), 3);
+        assert!(replayed.stats.pieces > 0);
+    }
+}
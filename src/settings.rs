@@ -0,0 +1,119 @@
+use std::fs::{self, File};
+use std::io::{self, BufReader, ErrorKind};
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    accessibility::Accessibility,
+    audio::Volumes,
+    blockcolor::ColorPolicy,
+    board::Size,
+    pieces::RotationSystem,
+    player::{Controls, Delays, Handling, TopOut},
+    queue::Randomizer,
+    theme::Theme,
+};
+
+/// where the settings are kept between games
+pub const SETTINGS_FILE: &str = "settings.json";
+
+/// Everything that can be changed in the menu, kept in a json file
+/// Anything the file leaves out keeps its default
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    pub size: Size,
+    pub top_out: TopOut,
+    pub colors: ColorPolicy,
+    pub randomizer: Randomizer,
+    pub rotation: RotationSystem,
+    pub delays: Delays,
+    pub handling: Handling,
+    pub volumes: Volumes,
+    /// the directory of the theme
+    pub theme: PathBuf,
+    pub fullscreen: bool,
+    pub particles: bool,
+    pub shake: bool,
+    pub accessibility: Accessibility,
+    /// the keys of a player that plays alone or against a bot
+    pub controls: Controls,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            size: Size::default(),
+            top_out: TopOut::default(),
+            colors: ColorPolicy::default(),
+            randomizer: Randomizer::default(),
+            rotation: RotationSystem::default(),
+            delays: Delays::default(),
+            handling: Handling::default(),
+            volumes: Volumes::default(),
+            theme: Theme::default().directory,
+            fullscreen: false,
+            particles: true,
+            shake: true,
+            accessibility: Accessibility::default(),
+            controls: Controls::single(),
+        }
+    }
+}
+
+impl Settings {
+    /// Reads the settings in the file, when there is no file yet they are all the defaults
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<Settings> {
+        let file = match File::open(path) {
+            Ok(file) => file,
+            Err(error) if error.kind() == ErrorKind::NotFound => return Ok(Settings::default()),
+            Err(error) => return Err(error),
+        };
+        let settings: Settings = serde_json::from_reader(BufReader::new(file))?;
+
+        // the size goes through the same checks as one given on the command line
+        settings.size.to_string().parse::<Size>().map_err(|error| io::Error::new(ErrorKind::InvalidData, error))?;
+        Ok(settings)
+    }
+
+    /// Writes the settings to the file
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        fs::write(path, serde_json::to_string_pretty(self)?)
+    }
+}
+
+#[cfg(test)]
+mod settings_tests {
+    use super::*;
+
+    #[test]
+    fn test_save() {
+        let path = std::env::temp_dir().join("rust_tetris_test_settings.json");
+        let _ = fs::remove_file(&path);
+        assert_eq!(Settings::load(&path).unwrap(), Settings::default());
+
+        let settings = Settings {
+            randomizer: Randomizer::Bag,
+            handling: Handling { das: 0.1, arr: 0.0, sdf: 20.0 },
+            volumes: Volumes { effects: 0.5, menu: 0.0 },
+            ..Settings::default()
+        };
+        settings.save(&path).unwrap();
+        assert_eq!(Settings::load(&path).unwrap(), settings);
+    }
+
+    #[test]
+    fn test_partial() {
+        let path = std::env::temp_dir().join("rust_tetris_test_partial_settings.json");
+        fs::write(&path, r#"{ "rotation": "WallKicks", "handling": { "das": 0.1 } }"#).unwrap();
+        let settings = Settings::load(&path).unwrap();
+        assert_eq!(settings.rotation, RotationSystem::WallKicks);
+        assert_eq!(settings.handling, Handling { das: 0.1, ..Handling::default() });
+        assert_eq!(settings.size, Size::default());
+
+        // a board that can't be played on is not loaded
+        fs::write(&path, r#"{ "size": { "width": 2, "height": 20, "hidden": 2 } }"#).unwrap();
+        assert!(Settings::load(&path).is_err());
+    }
+}
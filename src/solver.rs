@@ -0,0 +1,273 @@
+use std::collections::HashSet;
+
+use crate::{
+    block::Block,
+    blockshape::BlockShape,
+    board::{Board, Cell, CellStatus},
+    bot,
+    fumen::{self, Page},
+    player::{self, InputType},
+    tbp::PieceLocation,
+};
+
+/// every piece fills this many cells
+const PIECE_CELLS: usize = 4;
+
+/// One piece of a solution
+#[derive(Debug, Clone, PartialEq)]
+pub struct Step {
+    pub shape: BlockShape,
+    /// true when hold is used for this piece, the piece that would have come goes into hold
+    pub hold: bool,
+    /// y, x of the cells the piece is locked in, on the board as it is before this step
+    pub cells: Vec<(usize, usize)>,
+    /// the inputs that bring the piece there from where it spawns
+    pub inputs: Vec<InputType>,
+}
+
+/// The pieces that clear the whole board, in the order they are placed
+#[derive(Debug, Clone, PartialEq)]
+pub struct Solution {
+    pub steps: Vec<Step>,
+}
+
+impl Solution {
+    /// What the best solution is picked by, fewer pieces first, then fewer holds and then fewer inputs
+    pub fn cost(&self) -> (usize, usize, usize) {
+        (
+            self.steps.len(),
+            self.steps.iter().filter(|step| step.hold).count(),
+            self.steps.iter().map(|step| step.inputs.len()).sum(),
+        )
+    }
+
+    /// Writes the solution as a fumen code with a page for every piece, starting on the given board
+    pub fn fumen(&self, board: &Board) -> String {
+        let mut board = board.frozen();
+        let mut pages = Vec::new();
+
+        for step in &self.steps {
+            pages.push(Page { board: board.clone(), piece: PieceLocation::from_cells(step.shape, step.cells.clone(), &board), comment: None });
+            for (y, x) in &step.cells {
+                board.data[*y][*x] = Cell{color: fumen::color(step.shape), status: CellStatus::Frozen};
+            }
+            board.update(&mut 0);
+        }
+
+        fumen::encode(&pages)
+    }
+}
+
+/// Finds every way to clear the whole board with the pieces, without any piece going above the given amount of lines
+/// The queue starts with the piece that is falling now, the solutions come out with the best one first
+pub fn solve(board: &Board, hold: Option<BlockShape>, queue: &[BlockShape], lines: usize) -> Vec<Solution> {
+    let board = board.frozen();
+    let mut search = Search { queue, solutions: Vec::new(), dead: HashSet::new() };
+
+    if lines <= board.height() && fits(&board, lines) {
+        search.step(&board, hold, 0, lines, &mut Vec::new());
+    }
+
+    let mut solutions = search.solutions;
+    solutions.sort_by_key(Solution::cost);
+    solutions
+}
+
+/// Returns true if nothing is frozen above the lines and the empty cells under it can be filled by whole pieces
+fn fits(board: &Board, lines: usize) -> bool {
+    let (width, height) = (board.width(), board.height());
+    let top = height - lines;
+    if board.data[..top].iter().flatten().any(|cell| cell.status == CellStatus::Frozen) {
+        return false;
+    }
+
+    // every separate area of empty cells has to be filled by whole pieces
+    let mut seen = HashSet::new();
+    for y in top..height {
+        for x in 0..width {
+            if board.data[y][x].status != CellStatus::Frozen && seen.insert((y, x)) {
+                let mut area = 0;
+                let mut stack = vec![(y, x)];
+                while let Some((y, x)) = stack.pop() {
+                    area += 1;
+                    let neighbours = [(y.wrapping_sub(1), x), (y + 1, x), (y, x.wrapping_sub(1)), (y, x + 1)];
+                    for (y, x) in neighbours {
+                        if (top..height).contains(&y) && x < width
+                            && board.data[y][x].status != CellStatus::Frozen && seen.insert((y, x)) {
+                            stack.push((y, x));
+                        }
+                    }
+                }
+                if area % PIECE_CELLS != 0 {
+                    return false;
+                }
+            }
+        }
+    }
+    true
+}
+
+/// the state of the search that is shared between all the branches
+struct Search<'a> {
+    queue: &'a [BlockShape],
+    solutions: Vec<Solution>,
+    /// boards, hold, place in the queue and lines left that are known to have no solution
+    dead: HashSet<(Vec<u32>, Option<BlockShape>, usize, usize)>,
+}
+
+impl Search<'_> {
+    /// Tries every piece that can be used next in every spot, returns true if any of them leads to a solution
+    fn step(&mut self, board: &Board, hold: Option<BlockShape>, next: usize, lines: usize, steps: &mut Vec<Step>) -> bool {
+        if !steps.is_empty() && board.is_empty() {
+            self.solutions.push(Solution { steps: steps.clone() });
+            return true;
+        }
+
+        // there have to be enough pieces left to fill the empty cells
+        let empty = lines * board.width() - board.data.iter().flatten().filter(|cell| cell.status == CellStatus::Frozen).count();
+        let pieces = self.queue.len().saturating_sub(next) + usize::from(hold.is_some());
+        let key = (rows(board), hold, next, lines);
+        if empty > pieces * PIECE_CELLS || self.dead.contains(&key) {
+            return false;
+        }
+
+        let Some(&current) = self.queue.get(next) else {
+            return false;
+        };
+
+        // the piece that is used, whether hold was used, what is in hold after and where the queue continues
+        let mut choices = vec![(current, false, hold, next + 1)];
+        match hold {
+            Some(held) if held != current => choices.push((held, true, Some(current), next + 1)),
+            None => {
+                if let Some(&after) = self.queue.get(next + 1) {
+                    if after != current {
+                        choices.push((after, true, Some(current), next + 2));
+                    }
+                }
+            },
+            _ => {}
+        }
+
+        let mut found = false;
+        for (shape, used_hold, hold, next) in choices {
+            let mut spawned = board.clone();
+            let block = Block::with_shape(&mut spawned, player::spawn_position(board), shape);
+
+            let mut tried = HashSet::new();
+            for placement in bot::placements(&spawned, &block) {
+                let mut cells = placement.cells();
+                cells.sort();
+                if !tried.insert(cells.clone()) || cells.iter().any(|(y, _)| *y < board.height() - lines) {
+                    continue;
+                }
+
+                let lines = lines - placement.lines;
+                if !fits(&placement.board, lines) {
+                    continue;
+                }
+
+                steps.push(Step { shape, hold: used_hold, cells, inputs: placement.inputs });
+                found |= self.step(&placement.board, hold, next, lines, steps);
+                steps.pop();
+            }
+        }
+
+        if !found {
+            self.dead.insert(key);
+        }
+        found
+    }
+}
+
+/// The frozen cells of every row as bits, used to recognize boards that were seen before
+/// A u32 has room for the widest board there can be
+fn rows(board: &Board) -> Vec<u32> {
+    board.data.iter()
+        .map(|row| row.iter().enumerate().fold(0, |bits, (x, cell)| {
+            if cell.status == CellStatus::Frozen { bits | 1 << x } else { bits }
+        }))
+        .collect()
+}
+
+#[cfg(test)]
+mod solver_tests {
+    use super::*;
+    use crate::board::{self, Size, GARBAGE_COLOR};
+
+    /// Creates a board with the bottom rows filled except for the given columns
+    fn board_with_gap(lines: usize, gap: &[usize]) -> Board {
+        let mut board = Board::new();
+        for y in board::HEIGHT - lines..board::HEIGHT {
+            for x in (0..board::WIDTH).filter(|x| !gap.contains(x)) {
+                board.data[y][x] = Cell{color: GARBAGE_COLOR, status: CellStatus::Frozen};
+            }
+        }
+        board
+    }
+
+    #[test]
+    fn test_single_piece() {
+        let board = board_with_gap(2, &[4, 5]);
+        let solutions = solve(&board, None, &[BlockShape::O], 2);
+
+        assert_eq!(solutions.len(), 1);
+        assert_eq!(solutions[0].steps[0].cells, vec![(board::HEIGHT - 2, 4), (board::HEIGHT - 2, 5), (board::HEIGHT - 1, 4), (board::HEIGHT - 1, 5)]);
+
+        // the same gap can't be filled within a single line and not at all by an I
+        assert!(solve(&board, None, &[BlockShape::O], 1).is_empty());
+        assert!(solve(&board, None, &[BlockShape::I], 2).is_empty());
+    }
+
+    #[test]
+    fn test_wide_board() {
+        let size = Size { width: *board::WIDTHS.end(), ..Size::default() };
+        let mut board = Board::with_size(size);
+        let bottom = board.height() - 1;
+        for y in bottom - 1..=bottom {
+            for x in 0..size.width - 2 {
+                board.data[y][x] = Cell{color: GARBAGE_COLOR, status: CellStatus::Frozen};
+            }
+        }
+
+        // the gap is in the columns past the 16th, only the O fits it
+        let solutions = solve(&board, None, &[BlockShape::O], 2);
+        assert_eq!(solutions.len(), 1);
+        assert_eq!(solutions[0].steps[0].cells, vec![(bottom - 1, 18), (bottom - 1, 19), (bottom, 18), (bottom, 19)]);
+        assert!(solve(&board, None, &[BlockShape::I, BlockShape::I], 2).is_empty());
+    }
+
+    #[test]
+    fn test_hold() {
+        let board = board_with_gap(2, &[4, 5]);
+
+        // the I goes into hold so the O can be used
+        let solutions = solve(&board, None, &[BlockShape::I, BlockShape::O], 2);
+        assert_eq!(solutions.len(), 1);
+        assert!(solutions[0].steps[0].hold);
+
+        // or the O comes out of hold
+        let solutions = solve(&board, Some(BlockShape::O), &[BlockShape::I], 2);
+        assert_eq!(solutions.len(), 1);
+        assert_eq!(solutions[0].steps[0].shape, BlockShape::O);
+    }
+
+    #[test]
+    fn test_empty_board() {
+        let queue = [BlockShape::I, BlockShape::I, BlockShape::O, BlockShape::O, BlockShape::O];
+        let solutions = solve(&Board::new(), None, &queue, 2);
+
+        assert!(!solutions.is_empty());
+        for solution in &solutions {
+            assert_eq!(solution.steps.len(), queue.len());
+        }
+        assert!(solutions.windows(2).all(|pair| pair[0].cost() <= pair[1].cost()));
+
+        // the fumen has a page for every piece, with the pieces where the solution puts them
+        let pages = fumen::decode(&solutions[0].fumen(&Board::new())).unwrap();
+        assert_eq!(pages.len(), queue.len());
+        for (page, step) in pages.iter().zip(&solutions[0].steps) {
+            assert_eq!(page.piece.and_then(|piece| piece.cells(&page.board)), Some(step.cells.clone()));
+        }
+    }
+}
@@ -0,0 +1,150 @@
+use std::io::{self, Write};
+use std::net::{TcpListener, TcpStream, ToSocketAddrs};
+
+use piston_window::types::Color;
+use serde::Serialize;
+
+use crate::{
+    blockshape::BlockShape,
+    gamestate::GameStatus,
+    player::Player,
+};
+
+/// Things that happened since the last frame, so watchers don't have to work them out from the boards
+#[derive(Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum StreamEvent {
+    Locked { player: usize, lines: usize, sent: u16, fault: bool },
+    ToppedOut { player: usize },
+}
+
+/// The falling block of a player
+#[derive(Serialize)]
+pub struct PieceFrame {
+    pub shape: BlockShape,
+    pub color: Color,
+    /// y, x of every cell of the block on the board, cells in the hidden rows are left out
+    pub cells: Vec<(usize, usize)>,
+}
+
+/// Everything a watcher needs to draw the board of a player
+#[derive(Serialize)]
+pub struct PlayerFrame {
+    pub score: u16,
+    pub garbage: u16,
+    pub width: usize,
+    /// the rows that are shown, the hidden rows above them are not sent
+    pub height: usize,
+    /// the color of every cell row by row, null for empty cells
+    pub cells: Vec<Option<Color>>,
+    pub piece: PieceFrame,
+}
+
+impl PlayerFrame {
+    pub fn new(player: &Player) -> PlayerFrame {
+        let block = player.block();
+        let board = player.board();
+        let hidden = board.hidden();
+
+        PlayerFrame {
+            score: player.score,
+            garbage: player.garbage.pending(),
+            width: board.width(),
+            height: board.size().height,
+            cells: board.snapshot(),
+            piece: PieceFrame {
+                shape: block.shape(),
+                color: block.color.to_color(),
+                cells: block.cells().into_iter().filter(|(y, _)| *y >= hidden).map(|(y, x)| (y - hidden, x)).collect(),
+            },
+        }
+    }
+}
+
+/// One line of json that is sent to every watcher each frame
+#[derive(Serialize)]
+pub struct Frame<'a> {
+    pub status: &'a GameStatus,
+    pub players: Vec<PlayerFrame>,
+    pub events: &'a [StreamEvent],
+}
+
+/// Lets other programs watch the game by streaming its state over tcp
+pub struct SpectatorServer {
+    listener: TcpListener,
+    watchers: Vec<TcpStream>,
+    events: Vec<StreamEvent>,
+}
+
+impl SpectatorServer {
+    /// Starts listening for watchers on the given address
+    pub fn new<A: ToSocketAddrs>(address: A) -> io::Result<SpectatorServer> {
+        let listener = TcpListener::bind(address)?;
+        listener.set_nonblocking(true)?;
+
+        Ok(SpectatorServer { listener, watchers: Vec::new(), events: Vec::new() })
+    }
+
+    /// Remembers an event so it is sent with the next frame
+    pub fn push_event(&mut self, event: StreamEvent) {
+        self.events.push(event);
+    }
+
+    /// Accepts new watchers and sends the state of the game to everyone that is watching
+    pub fn broadcast(&mut self, status: &GameStatus, players: &[Player]) {
+        while let Ok((stream, _)) = self.listener.accept() {
+            if stream.set_nonblocking(true).and_then(|_| stream.set_nodelay(true)).is_ok() {
+                self.watchers.push(stream);
+            }
+        }
+
+        if !self.watchers.is_empty() {
+            let frame = Frame {
+                status,
+                players: players.iter().map(PlayerFrame::new).collect(),
+                events: &self.events,
+            };
+            let mut line = serde_json::to_vec(&frame).expect("A frame can always be turned into json");
+            line.push(b'\n');
+
+            // watchers that went away or can't keep up are dropped
+            self.watchers.retain_mut(|stream| stream.write_all(&line).is_ok());
+        }
+
+        self.events.clear();
+    }
+}
+
+#[cfg(test)]
+mod spectator_tests {
+    use super::*;
+    use std::io::{BufRead, BufReader};
+    use crate::{board, player::{Controls, InputType}};
+
+    #[test]
+    fn test_broadcast() {
+        let mut server = SpectatorServer::new("127.0.0.1:0").unwrap();
+        let watcher = TcpStream::connect(server.listener.local_addr().unwrap()).unwrap();
+
+        let mut players = vec![Player::new(Controls::single())];
+        // the block spawns with its top row hidden, one row down all of it is shown
+        players[0].handle_input(InputType::Down);
+        server.push_event(StreamEvent::ToppedOut { player: 0 });
+
+        // the watcher is accepted on the first broadcast, give the connection a moment to arrive
+        std::thread::sleep(std::time::Duration::from_millis(50));
+        server.broadcast(&GameStatus::Playing, &players);
+
+        let mut line = String::new();
+        BufReader::new(watcher).read_line(&mut line).unwrap();
+        let frame: serde_json::Value = serde_json::from_str(&line).unwrap();
+
+        assert_eq!(frame["status"], "playing");
+        assert_eq!(frame["players"][0]["cells"].as_array().unwrap().len(), board::WIDTH * board::HEIGHT);
+        assert_eq!(frame["players"][0]["piece"]["cells"].as_array().unwrap().len(), 4);
+        assert_eq!(frame["events"][0]["type"], "topped_out");
+
+        // events are only sent once
+        assert!(server.events.is_empty());
+    }
+}
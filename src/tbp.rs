@@ -0,0 +1,543 @@
+use std::io::{self, BufRead, BufReader, ErrorKind, Write};
+use std::process::{Child, ChildStdin, Command, Stdio};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    block::{Block, BlockStatus},
+    blockshape::BlockShape,
+    board::{Board, CellStatus, Size},
+    bot::{self, Controller, Spot, MOVE_PERIOD},
+    player::{InputType, Player},
+};
+
+/// how long a bot gets to introduce itself and read the rules
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+/// how long a bot gets to exit after it was asked to, after that it is killed
+const QUIT_TIMEOUT: Duration = Duration::from_secs(1);
+/// the amount of rows of the board the protocol sends, the rows above our board are always empty
+const PROTOCOL_HEIGHT: usize = 40;
+/// the amount of columns of the board the protocol sends, no other width can be played
+const PROTOCOL_WIDTH: usize = 10;
+/// what filled cells are sent as, the board does not remember which piece a cell came from
+const FILLED_CELL: char = 'G';
+/// every orientation, in the order they are tried when working out how a block is rotated
+const ORIENTATIONS: [Orientation; 4] = [Orientation::North, Orientation::East, Orientation::South, Orientation::West];
+
+/// which way a piece is rotated, north is how it spawns
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+pub enum Orientation {
+    North,
+    East,
+    South,
+    West,
+}
+
+/// if a move ended with a spin, only used by the bot to tell us what it was going for
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+pub enum Spin {
+    None,
+    Mini,
+    Full,
+}
+
+/// where a piece ends up, x and y are the center of the piece with y counting up from the bottom row
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Copy)]
+pub struct PieceLocation {
+    #[serde(rename = "type")]
+    pub shape: BlockShape,
+    pub orientation: Orientation,
+    pub x: i32,
+    pub y: i32,
+}
+
+/// a placement the bot wants to make
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Copy)]
+pub struct Move {
+    pub location: PieceLocation,
+    pub spin: Spin,
+}
+
+impl PieceLocation {
+    /// Returns x, y of every cell of the piece with y going up from the bottom row, the cells can be outside of the board
+    /// Custom pieces have no cells, other programs don't know them
+    pub fn positions(&self) -> Vec<(i32, i32)> {
+        self.shape.srs_cells().iter().map(|&(x, y)| {
+            let (x, y) = match self.orientation {
+                Orientation::North => (x, y),
+                Orientation::East => (y, -x),
+                Orientation::South => (-x, -y),
+                Orientation::West => (-y, x),
+            };
+            (self.x + x, self.y + y)
+        }).collect()
+    }
+
+    /// Returns the y, x of every cell on the board the piece fills, sorted
+    /// Returns None when part of the piece is outside of the board
+    pub fn cells(&self, board: &Board) -> Option<Vec<(usize, usize)>> {
+        let mut cells = Vec::new();
+        for (x, y) in self.positions() {
+            if x < 0 || x >= board.width() as i32 || y < 0 || y >= board.height() as i32 {
+                return None;
+            }
+            cells.push((board.height() - 1 - y as usize, x as usize));
+        }
+
+        cells.sort();
+        Some(cells)
+    }
+
+    /// Works out where a block on the board is and how it is rotated the way other programs see it
+    pub fn from_block(block: &Block, board: &Board) -> Option<PieceLocation> {
+        PieceLocation::from_cells(block.shape(), block.cells(), board)
+    }
+
+    /// Works out where a piece of the shape is and how it is rotated from the y, x of its cells on the board
+    pub fn from_cells(shape: BlockShape, mut cells: Vec<(usize, usize)>, board: &Board) -> Option<PieceLocation> {
+        cells.sort();
+
+        ORIENTATIONS.iter().find_map(|orientation| {
+            // put the center anywhere, then move it so the lowest and leftmost cells line up with the block
+            let location = PieceLocation { shape, orientation: *orientation, x: 0, y: 0 };
+            let positions = location.positions();
+            let left = positions.iter().map(|(x, _)| *x).min()?;
+            let bottom = positions.iter().map(|(_, y)| *y).min()?;
+            let block_left = cells.iter().map(|(_, x)| *x as i32).min()?;
+            let block_bottom = cells.iter().map(|(y, _)| (board.height() - 1 - y) as i32).min()?;
+
+            let location = PieceLocation { x: block_left - left, y: block_bottom - bottom, ..location };
+            (location.cells(board).as_ref() == Some(&cells)).then_some(location)
+        })
+    }
+}
+
+/// Messages we send to the bot, every message is one line of json
+#[derive(Serialize, Debug, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum FrontendMessage {
+    /// the rules of the game, we only use the defaults
+    Rules {},
+    /// the bot should start thinking about this position, rows go from the bottom up
+    Start {
+        hold: Option<BlockShape>,
+        queue: Vec<BlockShape>,
+        combo: u32,
+        back_to_back: bool,
+        board: Vec<Vec<Option<char>>>,
+    },
+    /// the bot should forget the position it was thinking about
+    Stop,
+    /// asks the bot for the moves it wants to make
+    Suggest,
+    /// tells the bot which move was made
+    Play {
+        r#move: Move,
+    },
+    /// a piece was added to the end of the queue
+    NewPiece { piece: BlockShape },
+    /// the bot should exit
+    Quit,
+}
+
+/// Messages the bot sends to us
+#[derive(Deserialize, Debug, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum BotMessage {
+    /// the first message a bot sends
+    Info { name: String, version: String, author: String },
+    /// the bot accepted the rules
+    Ready,
+    /// the bot can't play with the rules
+    Error { reason: String },
+    /// the moves the bot wants to make, the best one first
+    Suggestion { moves: Vec<Move> },
+}
+
+/// Returns for every cell of the board if it is filled, the falling block does not count
+fn frozen_cells(board: &Board) -> Vec<bool> {
+    board.data.iter().flatten().map(|cell| cell.status == CellStatus::Frozen).collect()
+}
+
+/// Checks that a board of the size fits the protocol, which only knows boards 10 wide with up to 40 rows
+pub fn check_size(size: Size) -> Result<(), String> {
+    if size.width != PROTOCOL_WIDTH || size.height + size.hidden > PROTOCOL_HEIGHT {
+        return Err(format!(
+            "bots that speak the Tetris Bot Protocol only play on boards {} wide with up to {} rows, hidden ones included, not {}",
+            PROTOCOL_WIDTH, PROTOCOL_HEIGHT, size,
+        ));
+    }
+    Ok(())
+}
+
+/// Turns our board into the rows the protocol uses, starting with the bottom row
+fn protocol_board(board: &Board) -> Vec<Vec<Option<char>>> {
+    (0..PROTOCOL_HEIGHT)
+        .map(|y| match board.height().checked_sub(y + 1) {
+            Some(row) => board.data[row].iter()
+                .map(|cell| (cell.status == CellStatus::Frozen).then_some(FILLED_CELL))
+                .collect(),
+            None => vec![None; PROTOCOL_WIDTH],
+        })
+        .collect()
+}
+
+/// A bot that runs as its own program and talks the Tetris Bot Protocol over stdin and stdout
+/// Its moves are made with the same inputs a person has, so moves that our rules can't reach are skipped
+pub struct ExternalBot {
+    child: Child,
+    stdin: ChildStdin,
+    messages: Receiver<BotMessage>,
+    pub name: String,
+    /// the frozen cells the bot thinks the board has, None when it has to be told about the board again
+    expected: Option<Vec<bool>>,
+    /// true while we wait for an answer to a suggest
+    thinking: bool,
+    /// true when none of the suggested moves could be made, gravity locks the block instead
+    stuck: bool,
+    /// the last block was locked, the bot still has to hear about the piece that was added to the queue
+    locked: bool,
+    /// the position and rotation the current block is heading for
+    target: Option<Spot>,
+    waiting_time: f64,
+}
+
+impl ExternalBot {
+    /// Starts the bot program and waits until it introduced itself and accepted the rules
+    pub fn spawn(mut command: Command) -> io::Result<ExternalBot> {
+        let mut child = command.stdin(Stdio::piped()).stdout(Stdio::piped()).spawn()?;
+        let stdin = child.stdin.take().expect("stdin is piped");
+        let stdout = child.stdout.take().expect("stdout is piped");
+
+        // reading blocks until the bot writes something, so it happens on its own thread and the game polls a channel
+        let (sender, messages) = mpsc::channel();
+        thread::spawn(move || {
+            for line in BufReader::new(stdout).lines().map_while(Result::ok) {
+                if let Ok(message) = serde_json::from_str(&line) {
+                    if sender.send(message).is_err() {
+                        break;
+                    }
+                }
+            }
+        });
+
+        let mut bot = ExternalBot {
+            child,
+            stdin,
+            messages,
+            name: String::new(),
+            expected: None,
+            thinking: false,
+            stuck: false,
+            locked: false,
+            target: None,
+            waiting_time: 0.0,
+        };
+
+        match bot.receive()? {
+            BotMessage::Info { name, .. } => bot.name = name,
+            message => return Err(io::Error::new(ErrorKind::InvalidData, format!("Expected info, got {:?}", message))),
+        }
+        bot.send(&FrontendMessage::Rules {})?;
+        match bot.receive()? {
+            BotMessage::Ready => Ok(bot),
+            BotMessage::Error { reason } => Err(io::Error::other(reason)),
+            message => Err(io::Error::new(ErrorKind::InvalidData, format!("Expected ready, got {:?}", message))),
+        }
+    }
+
+    /// Waits for the next message of the bot during the handshake
+    fn receive(&self) -> io::Result<BotMessage> {
+        self.messages.recv_timeout(HANDSHAKE_TIMEOUT).map_err(|error| match error {
+            RecvTimeoutError::Timeout => io::Error::from(ErrorKind::TimedOut),
+            RecvTimeoutError::Disconnected => io::Error::from(ErrorKind::UnexpectedEof),
+        })
+    }
+
+    /// Writes a message to the bot as one line of json
+    fn send(&mut self, message: &FrontendMessage) -> io::Result<()> {
+        let mut line = serde_json::to_vec(message)?;
+        line.push(b'\n');
+        self.stdin.write_all(&line)?;
+        self.stdin.flush()
+    }
+
+    /// Tells the bot about the board and queue when they are not what it expects, garbage or gravity can change them
+    fn sync(&mut self, player: &Player) -> io::Result<()> {
+        if self.locked {
+            self.locked = false;
+            if let Some(piece) = player.queue().pieces().last() {
+                self.send(&FrontendMessage::NewPiece { piece: *piece })?;
+            }
+        }
+
+        let cells = frozen_cells(player.board());
+        if self.expected.as_ref() != Some(&cells) {
+            if self.expected.is_some() {
+                self.send(&FrontendMessage::Stop)?;
+            }
+
+            let mut queue = vec![player.block().shape()];
+            queue.extend(player.queue().pieces());
+            self.send(&FrontendMessage::Start {
+                hold: None,
+                queue,
+                combo: 0,
+                back_to_back: false,
+                board: protocol_board(player.board()),
+            })?;
+            self.expected = Some(cells);
+            self.thinking = false;
+        }
+
+        if !self.thinking {
+            self.send(&FrontendMessage::Suggest)?;
+            self.thinking = true;
+        }
+        Ok(())
+    }
+
+    /// Plays the first suggested move that can be reached with our inputs
+    fn play(&mut self, moves: &[Move], player: &Player) -> io::Result<()> {
+        self.thinking = false;
+        let options = bot::placements(player.board(), player.block());
+
+        let chosen = moves.iter()
+            .filter(|suggestion| suggestion.location.shape == player.block().shape())
+            .find_map(|suggestion| {
+                let cells = suggestion.location.cells(player.board())?;
+                options.iter().find(|option| option.cells() == cells).map(|option| (suggestion, option.spot()))
+            });
+
+        match chosen {
+            Some((suggestion, spot)) => {
+                self.send(&FrontendMessage::Play { r#move: *suggestion })?;
+                self.target = Some(spot);
+            },
+            None => {
+                eprintln!("{} suggested no move that can be made, the block is dropped instead", self.name);
+                self.stuck = true;
+                self.expected = None;
+            },
+        }
+        Ok(())
+    }
+
+    /// Returns the next input towards the target, forgets the target when the block can't get there anymore
+    fn steer(&mut self, player: &Player) -> Option<InputType> {
+        let target = self.target.as_ref()?;
+        let options = bot::placements(player.board(), player.block());
+
+        let Some(placement) = options.iter().find(|option| option.spot() == *target) else {
+            // gravity moved the block past the spot, the board will be different from what the bot expects
+            self.target = None;
+            self.expected = None;
+            return None;
+        };
+
+        if placement.inputs.len() == 1 {
+            self.target = None;
+            self.locked = true;
+            self.expected = Some(frozen_cells(&placement.board));
+        }
+        placement.inputs.first().copied()
+    }
+}
+
+impl Controller for ExternalBot {
+    /// Talks to the bot and presses one key every MOVE_PERIOD seconds to make the move it chose
+    fn update(&mut self, dt: f64, player: &Player) -> Option<InputType> {
+        self.waiting_time += dt;
+
+        if player.block().status == BlockStatus::Frozen {
+            // the block the bot could not place is locked, the next one gets a new chance
+            self.stuck = false;
+            return None;
+        }
+        if self.waiting_time < MOVE_PERIOD {
+            return None;
+        }
+        self.waiting_time = 0.0;
+
+        if self.target.is_some() {
+            return self.steer(player);
+        }
+        if self.stuck {
+            return None;
+        }
+
+        if let Err(error) = self.sync(player) {
+            eprintln!("Lost the connection to {}: {}", self.name, error);
+            self.stuck = true;
+            return None;
+        }
+
+        while let Ok(message) = self.messages.try_recv() {
+            if let BotMessage::Suggestion { moves } = message {
+                if let Err(error) = self.play(&moves, player) {
+                    eprintln!("Lost the connection to {}: {}", self.name, error);
+                }
+            }
+        }
+        None
+    }
+}
+
+impl Drop for ExternalBot {
+    /// Asks the bot to exit and waits a moment for it, a bot that does not listen is killed
+    fn drop(&mut self) {
+        if self.send(&FrontendMessage::Quit).is_ok() {
+            let start = Instant::now();
+            while start.elapsed() < QUIT_TIMEOUT {
+                if let Ok(Some(_)) = self.child.try_wait() {
+                    return;
+                }
+                thread::sleep(Duration::from_millis(10));
+            }
+        }
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+#[cfg(test)]
+mod tbp_tests {
+    use super::*;
+    use crate::{board, pieces::Piece, player::Controls};
+
+    #[test]
+    fn test_message_format() {
+        let location = PieceLocation { shape: BlockShape::T, orientation: Orientation::North, x: 4, y: 0 };
+        let play = FrontendMessage::Play { r#move: Move { location, spin: Spin::None } };
+
+        assert_eq!(
+            serde_json::to_string(&play).unwrap(),
+            r#"{"type":"play","move":{"location":{"type":"T","orientation":"north","x":4,"y":0},"spin":"none"}}"#
+        );
+        assert_eq!(serde_json::to_string(&FrontendMessage::Rules {}).unwrap(), r#"{"type":"rules"}"#);
+
+        let suggestion: BotMessage = serde_json::from_str(
+            r#"{"type":"suggestion","moves":[{"location":{"type":"I","orientation":"east","x":0,"y":1},"spin":"none"}],"move_info":{}}"#
+        ).unwrap();
+        assert!(matches!(suggestion, BotMessage::Suggestion { moves } if moves[0].location.orientation == Orientation::East));
+    }
+
+    #[test]
+    fn test_location_cells() {
+        let board = Board::new();
+        let bottom = board::HEIGHT - 1;
+
+        // a flat T in the middle of the bottom row with its nose up
+        let t = PieceLocation { shape: BlockShape::T, orientation: Orientation::North, x: 4, y: 0 };
+        assert_eq!(t.cells(&board), Some(vec![(bottom - 1, 4), (bottom, 3), (bottom, 4), (bottom, 5)]));
+
+        // a standing I in the left column, its center is the second cell from the top
+        let i = PieceLocation { shape: BlockShape::I, orientation: Orientation::East, x: 0, y: 2 };
+        assert_eq!(i.cells(&board), Some(vec![(bottom - 3, 0), (bottom - 2, 0), (bottom - 1, 0), (bottom, 0)]));
+
+        // sticking out of the side of the board
+        let outside = PieceLocation { shape: BlockShape::O, orientation: Orientation::North, x: 9, y: 0 };
+        assert_eq!(outside.cells(&board), None);
+    }
+
+    #[test]
+    fn test_from_block() {
+        let mut board = Board::new();
+        let old_block = Block::new(&mut Board::new(), (0, 0));
+        let mut block = Block::next(&mut board, (5, 3), &Piece::from(BlockShape::L), &old_block).unwrap();
+        block.rotate(&mut board);
+
+        // whatever way the block is turned the location has to cover the same cells
+        let location = PieceLocation::from_block(&block, &board).unwrap();
+        assert_eq!(location.shape, BlockShape::L);
+        assert_eq!(location.orientation, Orientation::East);
+
+        let mut cells = block.cells();
+        cells.sort();
+        assert_eq!(location.cells(&board), Some(cells));
+    }
+
+    #[test]
+    fn test_protocol_board() {
+        let mut board = Board::new();
+        board.data[board::HEIGHT - 1][2].status = CellStatus::Frozen;
+
+        let rows = protocol_board(&board);
+        assert_eq!(rows.len(), PROTOCOL_HEIGHT);
+        assert_eq!(rows[0][2], Some(FILLED_CELL));
+        assert_eq!(rows[0][3], None);
+        assert!(rows.iter().all(|row| row.len() == PROTOCOL_WIDTH));
+
+        assert!(check_size(Size::default()).is_ok());
+        assert!(check_size("10x20+20".parse().unwrap()).is_ok());
+        assert!(check_size("12x20".parse().unwrap()).is_err());
+        assert!(check_size("10x40".parse().unwrap()).is_err());
+    }
+
+    /// a bot written in shell that always asks for the piece to be put as far left as it goes, flat
+    #[cfg(unix)]
+    #[test]
+    fn test_external_bot() {
+        let script = r#"
+            echo '{"type":"info","name":"left","version":"1","author":"test","features":[]}'
+            while read -r line; do
+                case "$line" in
+                    *'"rules"'*) echo '{"type":"ready"}' ;;
+                    *'"suggest"'*) echo '{"type":"suggestion","moves":[{"location":{"type":"O","orientation":"north","x":0,"y":0},"spin":"none"}]}' ;;
+                    *'"quit"'*) exit 0 ;;
+                esac
+            done
+        "#;
+        let mut command = Command::new("sh");
+        command.arg("-c").arg(script);
+        let mut bot = ExternalBot::spawn(command).unwrap();
+        assert_eq!(bot.name, "left");
+
+        // only an O can be placed where the script wants it
+        let mut player = Player::practice(Controls::none(), &Board::new(), &[BlockShape::O], None);
+
+        // the suggestion arrives a little later, keep asking until the bot starts moving
+        let mut inputs = Vec::new();
+        for _ in 0..200 {
+            if let Some(input_type) = bot.update(MOVE_PERIOD, &player) {
+                player.handle_input(input_type);
+                inputs.push(input_type);
+            }
+            if player.block().status == BlockStatus::Frozen {
+                break;
+            }
+            thread::sleep(Duration::from_millis(5));
+        }
+
+        assert_eq!(inputs.last(), Some(&InputType::Down));
+        let bottom = player.board().height() - 1;
+        assert_eq!(player.block().cells(), vec![(bottom - 1, 0), (bottom - 1, 1), (bottom, 0), (bottom, 1)]);
+    }
+
+    /// a bot that never exits on its own is killed once it had its chance
+    #[cfg(unix)]
+    #[test]
+    fn test_stuck_bot() {
+        let script = r#"
+            echo '{"type":"info","name":"stuck","version":"1","author":"test","features":[]}'
+            while read -r line; do
+                case "$line" in
+                    *'"rules"'*) echo '{"type":"ready"}' ;;
+                esac
+            done
+        "#;
+        let mut command = Command::new("sh");
+        command.arg("-c").arg(script);
+        let bot = ExternalBot::spawn(command).unwrap();
+
+        let start = Instant::now();
+        drop(bot);
+        assert!(start.elapsed() >= QUIT_TIMEOUT);
+        assert!(start.elapsed() < QUIT_TIMEOUT * 5);
+    }
+}
@@ -0,0 +1,238 @@
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::{self, BufReader};
+use std::path::{Path, PathBuf};
+
+use piston_window::types::Color;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    blockcolor::{BlockColor, COLORS},
+    renderer::TEXT_SIZE,
+};
+
+/// where the themes are, every directory in it with a manifest is a theme
+pub const THEMES_DIR: &str = "assets/themes";
+/// the theme that is used when none is picked, it has every file the other themes leave out
+pub const DEFAULT_THEME: &str = "classic";
+/// the file in the directory of a theme that describes it
+const MANIFEST: &str = "theme.json";
+/// the images the game shows, a theme that leaves one out uses the one of the default theme
+const IMAGES: [&str; 4] = ["header", "startup", "paused", "game_over"];
+
+/// Where the parts of the display above every board are drawn, relative to where the board starts
+/// An element that is set to null is not drawn, one that is left out is where it is in the default theme
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Hud {
+    /// how big the text of the elements is in pixels
+    pub text_size: f64,
+    pub score: Option<[f64; 2]>,
+    pub level: Option<[f64; 2]>,
+    pub lines: Option<[f64; 2]>,
+    /// how long the game has gone on
+    pub time: Option<[f64; 2]>,
+    /// pieces per second
+    pub pps: Option<[f64; 2]>,
+    /// how many pieces were placed with more inputs than needed
+    pub faults: Option<[f64; 2]>,
+    /// the top left of the preview of the next piece
+    pub next: Option<[f64; 2]>,
+    /// the top left of the piece in hold
+    pub hold: Option<[f64; 2]>,
+}
+
+impl Default for Hud {
+    fn default() -> Self {
+        Hud {
+            text_size: TEXT_SIZE,
+            score: Some([10.0, 50.0]),
+            level: None,
+            lines: None,
+            time: None,
+            pps: None,
+            faults: Some([10.0, 75.0]),
+            next: Some([200.0, 25.0]),
+            hold: Some([200.0, 55.0]),
+        }
+    }
+}
+
+/// How the game looks, read from the manifest in the directory of the theme
+/// Everything a manifest leaves out looks like it does in the default theme
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Theme {
+    pub name: String,
+    /// the color behind everything
+    pub background: Color,
+    /// drawn over the board of a player that lost
+    pub game_over: Color,
+    pub text: Color,
+    /// the colors blocks are drawn in instead of their usual ones
+    pub palette: HashMap<BlockColor, Color>,
+    /// how many pixels are left out around every block
+    pub block_border: f64,
+    /// a png with a square tile for every block color next to each other, in the order of blockcolor::COLORS
+    /// blocks in one of those colors are drawn with their tile instead of a plain square
+    pub skin: Option<PathBuf>,
+    pub font: PathBuf,
+    /// the png files of the header and the menu overlays by their name
+    pub images: HashMap<String, PathBuf>,
+    /// what is shown above every board and where
+    pub hud: Hud,
+    /// the directory the theme was read from, its files are looked for here first
+    #[serde(skip)]
+    pub directory: PathBuf,
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Theme {
+            name: String::from("Classic"),
+            background: [0.2, 0.5, 0.5, 1.0],
+            game_over: [0.8, 0.0, 0.0, 0.8],
+            text: [0.0, 0.0, 0.0, 1.0],
+            palette: HashMap::new(),
+            block_border: 1.0,
+            skin: None,
+            font: PathBuf::from("gillsans.ttf"),
+            images: IMAGES.iter().map(|name| (name.to_string(), PathBuf::from(format!("{}.png", name)))).collect(),
+            hud: Hud::default(),
+            directory: Path::new(THEMES_DIR).join(DEFAULT_THEME),
+        }
+    }
+}
+
+impl Theme {
+    /// Reads the theme in the given directory, fails when a file it uses is missing or can't be read as an image or font
+    pub fn load<P: AsRef<Path>>(directory: P) -> io::Result<Theme> {
+        let file = File::open(directory.as_ref().join(MANIFEST))?;
+        let mut theme: Theme = serde_json::from_reader(BufReader::new(file))?;
+        theme.directory = directory.as_ref().to_path_buf();
+
+        for (name, file) in Theme::default().images {
+            theme.images.entry(name).or_insert(file);
+        }
+        theme.check_files()?;
+        Ok(theme)
+    }
+
+    /// Checks that the images and the font the theme uses are there and decode, so drawing with it can't panic later
+    fn check_files(&self) -> io::Result<()> {
+        let invalid = |path: &Path, error: String| io::Error::new(io::ErrorKind::InvalidData, format!("{}: {}", path.display(), error));
+        for file in self.images.values().chain(&self.skin) {
+            let path = self.file(file);
+            image::open(&path).map_err(|error| invalid(&path, error.to_string()))?;
+        }
+        let path = self.file(&self.font);
+        let font = fs::read(&path).map_err(|error| invalid(&path, error.to_string()))?;
+        match rusttype::Font::try_from_vec(font) {
+            Some(_) => Ok(()),
+            None => Err(invalid(&path, String::from("not a font"))),
+        }
+    }
+
+    /// Reads a theme from a directory, or by its name from the themes directory
+    pub fn named(name: &str) -> io::Result<Theme> {
+        if Path::new(name).join(MANIFEST).exists() {
+            Theme::load(name)
+        } else {
+            Theme::load(Path::new(THEMES_DIR).join(name))
+        }
+    }
+
+    /// Returns the directories of every theme in the themes directory, sorted
+    pub fn available() -> Vec<PathBuf> {
+        let mut directories: Vec<PathBuf> = fs::read_dir(THEMES_DIR).into_iter()
+            .flatten()
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| path.join(MANIFEST).exists())
+            .collect();
+        directories.sort();
+        directories
+    }
+
+    /// Returns where a file of the theme is, the default theme has the files that are not in the directory of the theme
+    pub fn file(&self, name: &Path) -> PathBuf {
+        let path = self.directory.join(name);
+        if path.exists() {
+            path
+        } else {
+            Path::new(THEMES_DIR).join(DEFAULT_THEME).join(name)
+        }
+    }
+
+    /// Returns the color a block of the given color is drawn in
+    pub fn color(&self, color: Color) -> Color {
+        BlockColor::from_color(color)
+            .and_then(|block_color| self.palette.get(&block_color))
+            .copied()
+            .unwrap_or(color)
+    }
+
+    /// Returns which tile of the skin a block of the given color is drawn with, None for colors that are not block colors
+    pub fn tile(&self, color: Color) -> Option<usize> {
+        BlockColor::from_color(color).and_then(|block_color| COLORS.iter().position(|other| *other == block_color))
+    }
+}
+
+#[cfg(test)]
+mod theme_tests {
+    use super::*;
+
+    #[test]
+    fn test_default_theme() {
+        // the shipped default theme looks like the game always did
+        let classic = Theme::named(DEFAULT_THEME).unwrap();
+        assert_eq!(classic, Theme::default());
+        for file in classic.images.values() {
+            assert!(classic.file(file).exists());
+        }
+        assert!(classic.file(&classic.font).exists());
+    }
+
+    #[test]
+    fn test_palette() {
+        let dark = Theme::named("dark").unwrap();
+        assert!(Theme::available().contains(&dark.directory));
+
+        // files the theme leaves out come from the default theme
+        assert_eq!(dark.file(Path::new("header.png")), Path::new(THEMES_DIR).join(DEFAULT_THEME).join("header.png"));
+        assert!(dark.file(dark.skin.as_ref().unwrap()).starts_with(&dark.directory));
+
+        let red = BlockColor::Red.to_color();
+        assert_eq!(dark.color(red), dark.palette[&BlockColor::Red]);
+        assert_eq!(dark.tile(red), Some(0));
+        // colors that are not block colors stay as they are
+        let gray = [0.5, 0.5, 0.5, 1.0];
+        assert_eq!(dark.color(gray), gray);
+        assert_eq!(dark.tile(gray), None);
+
+        // the dark theme shows everything, the default one only what the game always showed
+        let hud = &dark.hud;
+        assert!([hud.score, hud.level, hud.lines, hud.time, hud.pps, hud.faults, hud.next, hud.hold].iter().all(Option::is_some));
+        assert_eq!(Theme::default().hud.pps, None);
+    }
+
+    #[test]
+    fn test_broken_files() {
+        let directory = std::env::temp_dir().join("rust_tetris_test_theme");
+        fs::create_dir_all(&directory).unwrap();
+        fs::write(directory.join(MANIFEST), r#"{"name": "Broken", "skin": "skin.png"}"#).unwrap();
+
+        // a file that is in neither the theme nor the default theme
+        assert!(Theme::load(&directory).is_err());
+
+        // a file that is there but is no png
+        fs::write(directory.join("skin.png"), "not a png").unwrap();
+        assert_eq!(Theme::load(&directory).unwrap_err().kind(), io::ErrorKind::InvalidData);
+
+        // the same for the font
+        fs::write(directory.join(MANIFEST), r#"{"name": "Broken", "font": "font.ttf"}"#).unwrap();
+        fs::write(directory.join("font.ttf"), "not a font").unwrap();
+        assert!(Theme::load(&directory).is_err());
+
+        fs::remove_dir_all(&directory).unwrap();
+    }
+}